@@ -0,0 +1,25 @@
+-- Sub-accounts and per-position fields tracked by the API
+ALTER TABLE users ADD COLUMN sub_accounts INT[] NOT NULL DEFAULT '{0}';
+
+-- Positions
+ALTER TABLE positions ADD COLUMN sub_account_id INT NOT NULL DEFAULT 0;
+ALTER TABLE positions ADD COLUMN margin_ratio DOUBLE PRECISION NOT NULL DEFAULT 1.0;
+
+-- Referral Codes
+CREATE TABLE referral_codes (
+    code VARCHAR(16) PRIMARY KEY,
+    referrer VARCHAR(255) NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+-- Referrals (one referrer per referee)
+CREATE TABLE referrals (
+    referee VARCHAR(255) PRIMARY KEY,
+    referrer VARCHAR(255) NOT NULL,
+    code VARCHAR(16) NOT NULL REFERENCES referral_codes(code),
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX idx_positions_owner ON positions(owner);
+CREATE INDEX idx_referral_codes_referrer ON referral_codes(referrer);
+CREATE INDEX idx_referrals_referrer ON referrals(referrer);
\ No newline at end of file
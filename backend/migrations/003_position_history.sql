@@ -0,0 +1,7 @@
+-- Entries are queried by owner address
+ALTER TABLE position_history ADD COLUMN owner VARCHAR(255) NOT NULL DEFAULT '';
+
+-- Orders entries written within the same second
+ALTER TABLE position_history ADD COLUMN seq BIGSERIAL;
+
+CREATE INDEX idx_position_history_owner ON position_history(owner, created_at);
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- Snapshots are queried by owner address
+ALTER TABLE pnl_snapshots ADD COLUMN owner VARCHAR(255) NOT NULL DEFAULT '';
+
+-- Margin ratios are unbounded, and null without open positions
+ALTER TABLE pnl_snapshots ALTER COLUMN margin_ratio TYPE DOUBLE PRECISION;
+
+CREATE INDEX idx_pnl_snapshots_owner ON pnl_snapshots(owner, snapshot_time);
\ No newline at end of file
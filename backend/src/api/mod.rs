@@ -0,0 +1,209 @@
+//! Request bodies and the API's error type. Failures the program also checks
+//! carry its `ErrorCode`, so clients get the same number, name and message from
+//! the chain and from the API.
+
+mod requests;
+
+use std::fmt;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use position_management_client::ErrorCode;
+use serde::Serialize;
+
+use crate::storage::StorageError;
+
+pub use requests::{
+    ClosePositionRequest, CloseAllRequest, HistoryQuery, InitializeUserRequest, LiquidatePositionRequest,
+    ModifyPositionRequest, OpenPositionRequest, PnlHistoryQuery, PostPricesRequest, PriceBounds, ReferralCodeRequest,
+    RegisterReferralRequest,
+};
+
+/// Failures with no program counterpart, numbered after the program's ranges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiErrorCode {
+    /// Malformed body or query string.
+    InvalidRequest = 9001,
+    /// A user, referral code or other record that does not exist.
+    NotFound = 9002,
+    /// The record already exists, or changed while the request ran.
+    Conflict = 9003,
+    Storage = 9004,
+    /// Missing or wrong credentials for an admin endpoint, or the endpoint is disabled.
+    Unauthorized = 9005,
+}
+
+impl ApiErrorCode {
+    fn message(self) -> &'static str {
+        match self {
+            ApiErrorCode::InvalidRequest => "Invalid request",
+            ApiErrorCode::NotFound => "Not found",
+            ApiErrorCode::Conflict => "Conflict",
+            ApiErrorCode::Storage => "Storage error",
+            ApiErrorCode::Unauthorized => "Unauthorized",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ErrorKind {
+    Program(ErrorCode),
+    Api(ApiErrorCode),
+}
+
+/// Answered as `{"code", "name", "error", "detail"}` plus any context fields,
+/// with the status `status_code` picks for the code.
+#[derive(Debug)]
+pub struct ApiError {
+    kind: ErrorKind,
+    detail: Option<String>,
+    context: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ApiError {
+    pub fn program(code: ErrorCode) -> Self {
+        Self { kind: ErrorKind::Program(code), detail: None, context: serde_json::Map::new() }
+    }
+
+    fn api(code: ApiErrorCode, detail: String) -> Self {
+        Self { kind: ErrorKind::Api(code), detail: Some(detail), context: serde_json::Map::new() }
+    }
+
+    pub fn invalid_request(detail: impl Into<String>) -> Self {
+        Self::api(ApiErrorCode::InvalidRequest, detail.into())
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self::api(ApiErrorCode::NotFound, detail.into())
+    }
+
+    pub fn conflict(detail: impl Into<String>) -> Self {
+        Self::api(ApiErrorCode::Conflict, detail.into())
+    }
+
+    pub fn unauthorized(detail: impl Into<String>) -> Self {
+        Self::api(ApiErrorCode::Unauthorized, detail.into())
+    }
+
+    /// Explains this occurrence, e.g. which limit was exceeded.
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Adds a field to the response body.
+    pub fn with(mut self, key: &str, value: impl Serialize) -> Self {
+        self.context.insert(key.to_string(), serde_json::json!(value));
+        self
+    }
+
+    /// The program's `ErrorCode` number, or an `ApiErrorCode`.
+    pub fn code(&self) -> u32 {
+        match self.kind {
+            ErrorKind::Program(code) => code as u32,
+            ErrorKind::Api(code) => code as u32,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self.kind {
+            ErrorKind::Program(code) => format!("{:?}", code),
+            ErrorKind::Api(code) => format!("{:?}", code),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self.kind {
+            ErrorKind::Program(code) => code.to_string(),
+            ErrorKind::Api(code) => code.message().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.name(), self.code(), self.message())?;
+        if let Some(detail) = &self.detail {
+            write!(f, ": {}", detail)?;
+        }
+        Ok(())
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self.kind {
+            ErrorKind::Program(ErrorCode::PositionNotFound) => StatusCode::NOT_FOUND,
+            ErrorKind::Program(ErrorCode::PositionAlreadyClosed) => StatusCode::CONFLICT,
+            ErrorKind::Program(
+                ErrorCode::Unauthorized
+                | ErrorCode::CannotModifyOthersPosition
+                | ErrorCode::DelegateExpired
+                | ErrorCode::DelegatePermissionDenied
+                | ErrorCode::DelegateNotionalExceeded,
+            ) => StatusCode::FORBIDDEN,
+            ErrorKind::Program(_) => StatusCode::BAD_REQUEST,
+            ErrorKind::Api(ApiErrorCode::InvalidRequest) => StatusCode::BAD_REQUEST,
+            ErrorKind::Api(ApiErrorCode::NotFound) => StatusCode::NOT_FOUND,
+            ErrorKind::Api(ApiErrorCode::Conflict) => StatusCode::CONFLICT,
+            ErrorKind::Api(ApiErrorCode::Storage) => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorKind::Api(ApiErrorCode::Unauthorized) => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut body = serde_json::Map::new();
+        body.insert("code".to_string(), self.code().into());
+        body.insert("name".to_string(), self.name().into());
+        body.insert("error".to_string(), self.message().into());
+        if let Some(detail) = &self.detail {
+            body.insert("detail".to_string(), detail.clone().into());
+        }
+        body.extend(self.context.clone());
+        HttpResponse::build(self.status_code()).json(body)
+    }
+}
+
+impl From<ErrorCode> for ApiError {
+    fn from(code: ErrorCode) -> Self {
+        ApiError::program(code)
+    }
+}
+
+impl From<StorageError> for ApiError {
+    fn from(e: StorageError) -> Self {
+        // The cause stays in the log; clients only learn that storage failed.
+        log::error!("Storage error: {}", e);
+        ApiError { kind: ErrorKind::Api(ApiErrorCode::Storage), detail: None, context: serde_json::Map::new() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn errors_mirror_program_codes() {
+        let error = ApiError::program(ErrorCode::InvalidLeverageValue).detail("Leverage 0").with("leverage", 0);
+        assert_eq!((error.code(), error.name().as_str()), (4001, "InvalidLeverageValue"));
+        assert_eq!(error.status_code(), StatusCode::BAD_REQUEST);
+
+        let bytes = actix_web::body::to_bytes(error.error_response().into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "code": 4001,
+                "name": "InvalidLeverageValue",
+                "error": "Invalid leverage",
+                "detail": "Leverage 0",
+                "leverage": 0
+            })
+        );
+
+        assert_eq!(ApiError::from(ErrorCode::PositionNotFound).status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(ApiError::from(ErrorCode::PositionAlreadyClosed).status_code(), StatusCode::CONFLICT);
+        assert_eq!(ApiError::conflict("Referral code already registered").code(), 9003);
+        assert_eq!(ApiError::not_found("User not found").status_code(), StatusCode::NOT_FOUND);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,311 @@
+use std::collections::{BTreeMap, HashMap};
+
+use position_management_client::ErrorCode;
+use serde::Deserialize;
+
+use super::ApiError;
+use crate::prices::PriceTick;
+use crate::storage::TimeRange;
+use crate::parse_interval;
+
+fn require_text(value: &str, field: &str) -> Result<(), ApiError> {
+    if value.trim().is_empty() {
+        return Err(ApiError::invalid_request(format!("Missing {}", field)));
+    }
+    Ok(())
+}
+
+fn time_range(from: Option<i64>, to: Option<i64>) -> Result<TimeRange, ApiError> {
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err(ApiError::invalid_request("from must not be after to"));
+        }
+    }
+    Ok(TimeRange { from, to })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InitializeUserRequest {
+    pub address: String,
+    #[serde(default)]
+    pub sub_account_id: u16,
+}
+
+impl InitializeUserRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        require_text(&self.address, "address")
+    }
+}
+
+/// `side` and `leverage` are read wider than they are stored, so out-of-range
+/// values are rejected instead of truncated.
+#[derive(Debug, Deserialize)]
+pub struct OpenPositionRequest {
+    pub owner: String,
+    pub symbol: String,
+    pub side: u64,
+    pub size: u64,
+    pub entry_price: u64,
+    pub leverage: u64,
+    pub max_price: Option<u64>,
+    pub min_price: Option<u64>,
+    #[serde(default)]
+    pub sub_account_id: u16,
+}
+
+impl OpenPositionRequest {
+    /// The program's checks on `open_position`, in its order. Returns side and
+    /// leverage at their stored widths.
+    pub fn validate(&self) -> Result<(u8, u16), ApiError> {
+        require_text(&self.owner, "owner")?;
+        require_text(&self.symbol, "symbol")?;
+        if self.size == 0 {
+            return Err(ApiError::program(ErrorCode::InvalidPositionSize).with("size", self.size));
+        }
+        let leverage = u16::try_from(self.leverage)
+            .ok()
+            .filter(|&leverage| leverage > 0)
+            .ok_or_else(|| ApiError::program(ErrorCode::InvalidLeverageValue).with("leverage", self.leverage))?;
+        if self.entry_price == 0 {
+            return Err(ApiError::program(ErrorCode::InvalidPrice).with("entry_price", self.entry_price));
+        }
+        let side = match self.side {
+            1 => 1,
+            2 => 2,
+            _ => return Err(ApiError::program(ErrorCode::InvalidSide).with("side", self.side)),
+        };
+        Ok((side, leverage))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModifyPositionRequest {
+    pub position_id: String,
+    pub size_delta: Option<i64>,
+    pub margin_delta: Option<i64>,
+}
+
+impl ModifyPositionRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        require_text(&self.position_id, "position_id")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClosePositionRequest {
+    pub position_id: String,
+    pub max_price: Option<u64>,
+    pub min_price: Option<u64>,
+}
+
+impl ClosePositionRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        require_text(&self.position_id, "position_id")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LiquidatePositionRequest {
+    pub position_id: String,
+}
+
+impl LiquidatePositionRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        require_text(&self.position_id, "position_id")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CloseAllRequest {
+    /// Optional slippage bounds per symbol, checked against its cached mark.
+    #[serde(default)]
+    pub bounds: HashMap<String, PriceBounds>,
+    pub sub_account_id: Option<u16>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PriceBounds {
+    pub max_price: Option<u64>,
+    pub min_price: Option<u64>,
+}
+
+/// Body of `POST /prices`: any of the `PriceUpdates` formats, optionally
+/// wrapped in `prices`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum PostPricesRequest {
+    Wrapped { prices: PriceUpdates },
+    Bare(PriceUpdates),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum PriceUpdates {
+    /// `[{"symbol", "price"}, ...]`
+    List(Vec<PriceUpdate>),
+    /// `{"symbol", "price"}`
+    Single(PriceUpdate),
+    /// `{"SYMBOL": price, ...}`
+    Map(BTreeMap<String, u64>),
+}
+
+/// Any `timestamp` sent along is ignored; injected ticks take the server clock.
+#[derive(Debug, Deserialize)]
+pub struct PriceUpdate {
+    pub symbol: String,
+    pub price: u64,
+}
+
+impl PostPricesRequest {
+    /// Checks every update and returns them as ticks stamped `now`.
+    pub fn validate(self, now: i64) -> Result<Vec<PriceTick>, ApiError> {
+        let updates = match self {
+            PostPricesRequest::Wrapped { prices } | PostPricesRequest::Bare(prices) => prices,
+        };
+        let updates: Vec<(String, u64)> = match updates {
+            PriceUpdates::List(list) => list.into_iter().map(|u| (u.symbol, u.price)).collect(),
+            PriceUpdates::Single(update) => vec![(update.symbol, update.price)],
+            PriceUpdates::Map(map) => map.into_iter().collect(),
+        };
+        if updates.is_empty() {
+            return Err(ApiError::invalid_request("No prices given"));
+        }
+        updates
+            .into_iter()
+            .map(|(symbol, price)| {
+                require_text(&symbol, "symbol")?;
+                if price == 0 {
+                    return Err(ApiError::program(ErrorCode::InvalidPrice).with("symbol", &symbol));
+                }
+                Ok(PriceTick { symbol, price, timestamp: now })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReferralCodeRequest {
+    pub referrer: String,
+    pub code: String,
+}
+
+impl ReferralCodeRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        require_text(&self.referrer, "referrer")?;
+        if self.code.is_empty() || self.code.len() > 16 {
+            return Err(ApiError::invalid_request("code must be 1 to 16 bytes"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterReferralRequest {
+    pub referee: String,
+    pub code: String,
+}
+
+impl RegisterReferralRequest {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        require_text(&self.referee, "referee")?;
+        require_text(&self.code, "code")
+    }
+}
+
+/// Optional `from` / `to` unix timestamps.
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl HistoryQuery {
+    pub fn time_range(&self) -> Result<TimeRange, ApiError> {
+        time_range(self.from, self.to)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PnlHistoryQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    /// `hourly`, `daily` or a number of seconds.
+    pub interval: Option<String>,
+}
+
+impl PnlHistoryQuery {
+    pub fn time_range(&self) -> Result<TimeRange, ApiError> {
+        time_range(self.from, self.to)
+    }
+
+    pub fn interval(&self) -> Result<Option<u64>, ApiError> {
+        match &self.interval {
+            None => Ok(None),
+            Some(interval) => parse_interval(interval)
+                .map(Some)
+                .ok_or_else(|| ApiError::invalid_request(format!("Invalid interval: {}", interval))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_request(body: serde_json::Value) -> OpenPositionRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn open_requests_are_checked_like_the_program() {
+        let valid = serde_json::json!({
+            "owner": "alice", "symbol": "SOL-PERP", "side": 2, "size": 1_000, "entry_price": 100, "leverage": 10
+        });
+        assert_eq!(open_request(valid.clone()).validate().unwrap(), (2, 10));
+
+        let code = |field: &str, value: serde_json::Value| {
+            let mut body = valid.clone();
+            body[field] = value;
+            open_request(body).validate().unwrap_err().code()
+        };
+        assert_eq!(code("size", 0.into()), 3004);
+        assert_eq!(code("leverage", 0.into()), 4001);
+        // Would have truncated to 10 as a u16.
+        assert_eq!(code("leverage", 65_546.into()), 4001);
+        assert_eq!(code("entry_price", 0.into()), 4002);
+        // Would have truncated to 1 as a u8.
+        assert_eq!(code("side", 257.into()), 4003);
+        assert_eq!(code("owner", " ".into()), 9001);
+
+        let history = HistoryQuery { from: Some(10), to: Some(5) };
+        assert_eq!(history.time_range().unwrap_err().code(), 9001);
+        let pnl = PnlHistoryQuery { from: None, to: None, interval: Some("weekly".to_string()) };
+        assert!(pnl.interval().is_err());
+    }
+
+    #[test]
+    fn posted_prices_are_typed_and_stamped_with_the_server_clock() {
+        let ticks = |body: serde_json::Value| serde_json::from_value::<PostPricesRequest>(body).unwrap().validate(42);
+        let summary = |body| {
+            ticks(body).unwrap().into_iter().map(|t| (t.symbol, t.price, t.timestamp)).collect::<Vec<_>>()
+        };
+        let sol = || ("SOL-PERP".to_string(), 105, 42);
+
+        assert_eq!(summary(serde_json::json!({"SOL-PERP": 105})), [sol()]);
+        assert_eq!(summary(serde_json::json!({"symbol": "SOL-PERP", "price": 105, "timestamp": i64::MAX})), [sol()]);
+        assert_eq!(
+            summary(serde_json::json!({"prices": [
+                {"symbol": "SOL-PERP", "price": 105, "timestamp": 1},
+                {"symbol": "BTC-PERP", "price": 98},
+            ]})),
+            [sol(), ("BTC-PERP".to_string(), 98, 42)]
+        );
+
+        assert_eq!(ticks(serde_json::json!({"SOL-PERP": 0})).unwrap_err().code(), 4002);
+        assert_eq!(ticks(serde_json::json!([{"symbol": " ", "price": 1}])).unwrap_err().code(), 9001);
+        assert_eq!(ticks(serde_json::json!({"prices": []})).unwrap_err().code(), 9001);
+        for body in [serde_json::json!({"SOL-PERP": -1}), serde_json::json!({"symbol": "SOL-PERP"}), serde_json::json!(5)] {
+            assert!(serde_json::from_value::<PostPricesRequest>(body).is_err());
+        }
+    }
+}
\ No newline at end of file
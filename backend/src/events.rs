@@ -0,0 +1,153 @@
+//! Live updates for `/ws` subscribers. Handlers and background tasks publish an
+//! `Event` on the channel it concerns after each state change; every session
+//! receives all of them and forwards those on channels it subscribed to.
+
+use std::fmt;
+
+use serde_json::json;
+use tokio::sync::broadcast;
+
+use crate::prices::PriceTick;
+use crate::storage::changed_fields;
+use crate::{HistoryAction, Position, User};
+
+// Events a slow session may fall behind by before it misses some.
+const EVENT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// `positions:<owner>`: opens, changes and marks of the owner's positions,
+    /// and the owner's account after each of them.
+    Positions(String),
+    /// `prices:<symbol>`: mark price ticks.
+    Prices(String),
+    /// `liquidations`: every liquidation.
+    Liquidations,
+    /// `metrics`: changed fields of `/metrics`.
+    Metrics,
+}
+
+impl Channel {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let channel = match name.split_once(':') {
+            Some(("positions", owner)) if !owner.is_empty() => Channel::Positions(owner.to_string()),
+            Some(("prices", symbol)) if !symbol.is_empty() => Channel::Prices(symbol.to_string()),
+            None if name == "liquidations" => Channel::Liquidations,
+            None if name == "metrics" => Channel::Metrics,
+            _ => return Err(format!("Unknown channel: {}", name)),
+        };
+        Ok(channel)
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Channel::Positions(owner) => write!(f, "positions:{}", owner),
+            Channel::Prices(symbol) => write!(f, "prices:{}", symbol),
+            Channel::Liquidations => write!(f, "liquidations"),
+            Channel::Metrics => write!(f, "metrics"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub channel: Channel,
+    pub kind: &'static str,
+    pub data: serde_json::Value,
+}
+
+impl Event {
+    /// An opened position in full, or only the fields `action` changed.
+    pub fn position(action: HistoryAction, old: Option<&Position>, new: &Position) -> Self {
+        let changes = match old {
+            None => json!(new),
+            Some(old) => json!(changed_fields(old, new).1),
+        };
+        Event {
+            channel: Channel::Positions(new.owner.clone()),
+            kind: "position",
+            data: json!({"position_id": new.id, "action": action, "changes": changes}),
+        }
+    }
+
+    /// The fields a new mark price changed.
+    pub fn mark(old: &Position, new: &Position) -> Self {
+        Event {
+            channel: Channel::Positions(new.owner.clone()),
+            kind: "mark",
+            data: json!({"position_id": new.id, "changes": changed_fields(old, new).1}),
+        }
+    }
+
+    pub fn account(user: &User) -> Self {
+        Event { channel: Channel::Positions(user.address.clone()), kind: "account", data: json!(user) }
+    }
+
+    pub fn price(tick: &PriceTick) -> Self {
+        Event { channel: Channel::Prices(tick.symbol.clone()), kind: "price", data: json!(tick) }
+    }
+
+    pub fn liquidation(position: &Position, mark_price: Option<u64>, reason: &str) -> Self {
+        Event {
+            channel: Channel::Liquidations,
+            kind: "liquidation",
+            data: json!({
+                "position_id": position.id,
+                "owner": position.owner,
+                "symbol": position.symbol,
+                "side": position.side,
+                "size": position.size,
+                "margin": position.margin,
+                "mark_price": mark_price,
+                "reason": reason
+            }),
+        }
+    }
+
+    pub fn metrics(changes: serde_json::Map<String, serde_json::Value>) -> Self {
+        Event { channel: Channel::Metrics, kind: "metrics", data: serde_json::Value::Object(changes) }
+    }
+
+    /// The message sent to subscribers.
+    pub fn to_message(&self) -> serde_json::Value {
+        json!({"channel": self.channel.to_string(), "type": self.kind, "data": self.data})
+    }
+}
+
+pub struct Events {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self { sender: broadcast::channel(EVENT_CAPACITY).0 }
+    }
+}
+
+impl Events {
+    pub fn publish(&self, event: Event) {
+        // Fails only when nobody is listening.
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_round_trip() {
+        for name in ["positions:alice", "prices:SOL-PERP", "liquidations", "metrics"] {
+            assert_eq!(Channel::parse(name).unwrap().to_string(), name);
+        }
+        assert!(Channel::parse("positions:").is_err());
+        assert!(Channel::parse("prices").is_err());
+        assert!(Channel::parse("trades:SOL-PERP").is_err());
+    }
+}
\ No newline at end of file
@@ -1,11 +1,26 @@
-use actix_web::{web, App, HttpServer, HttpResponse, middleware::Logger};
+mod api;
+mod events;
+mod prices;
+mod storage;
+
+use actix_web::{web, App, HttpRequest, HttpServer, HttpResponse, middleware::Logger};
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
 use actix_files::NamedFile;
 use std::path::PathBuf;
 use chrono::Local;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
 use uuid::Uuid;
+use api::{
+    ApiError, ClosePositionRequest, CloseAllRequest, HistoryQuery, InitializeUserRequest, LiquidatePositionRequest,
+    ModifyPositionRequest, OpenPositionRequest, PnlHistoryQuery, PostPricesRequest, PriceBounds, ReferralCodeRequest,
+    RegisterReferralRequest,
+};
+use events::{Channel, Event, Events};
+use position_management_client::ErrorCode;
+use prices::{PriceCache, PriceSource, PriceTick};
+use storage::{Change, InMemoryRepository, PostgresRepository, Repository, StorageError};
 
 // ===== LEVERAGE TIERS (REQUIREMENT) =====
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,28 +64,157 @@ const LEVERAGE_TIERS: [LeverageTier; 5] = [
     },
 ];
 
-fn get_leverage_tier(leverage: u16, position_size: u64) -> Result<LeverageTier, String> {
+fn get_leverage_tier(leverage: u16, position_size: u64) -> Result<LeverageTier, ApiError> {
     for tier in &LEVERAGE_TIERS {
         if leverage <= tier.max_leverage && position_size <= tier.max_position_size {
             return Ok(tier.clone());
         }
     }
-    Err("Leverage or position size exceeds limits".to_string())
+    Err(ApiError::program(ErrorCode::InvalidLeverageValue).detail("Leverage or position size exceeds limits"))
+}
+
+// ===== SLIPPAGE =====
+// Optional `max_price` / `min_price` bounds on the execution price, as in the program.
+fn check_slippage(price: u64, max_price: Option<u64>, min_price: Option<u64>) -> Result<(), ApiError> {
+    if max_price.is_some_and(|max| price > max) || min_price.is_some_and(|min| price < min) {
+        return Err(ApiError::program(ErrorCode::SlippageExceeded)
+            .with("price", price)
+            .with("max_price", max_price)
+            .with("min_price", min_price));
+    }
+    Ok(())
+}
+
+/// Checks the bounds against the cached mark of `symbol`, the price trades
+/// execute at on-chain. Bounded requests fail without a mark.
+fn cached_mark(prices: &PriceCache, symbol: &str) -> Result<u64, ApiError> {
+    prices
+        .get(symbol)
+        .map(|tick| tick.price)
+        .ok_or_else(|| ApiError::program(ErrorCode::InvalidPrice).detail("No mark price for symbol").with("symbol", symbol))
+}
+
+fn check_mark_slippage(
+    prices: &PriceCache,
+    symbol: &str,
+    max_price: Option<u64>,
+    min_price: Option<u64>,
+) -> Result<(), ApiError> {
+    if max_price.is_none() && min_price.is_none() {
+        return Ok(());
+    }
+    check_slippage(cached_mark(prices, symbol)?, max_price, min_price)
 }
 
 // ===== MARGIN CALCULATIONS =====
-fn calculate_liquidation_price_long(entry_price: u64, leverage: u16, maintenance_rate: f64) -> u64 {
-    let entry = entry_price as f64;
-    let lev = leverage as f64;
-    let liq = entry * (1.0 - 1.0 / lev + maintenance_rate);
-    liq as u64
+fn calculate_liquidation_price_long(entry_price: u64, leverage: u16, maintenance_rate: f64) -> Result<u64, ApiError> {
+    let lev = checked_leverage(leverage, maintenance_rate)?;
+    to_price(entry_price as f64 * (1.0 - 1.0 / lev + maintenance_rate))
+}
+
+fn calculate_liquidation_price_short(entry_price: u64, leverage: u16, maintenance_rate: f64) -> Result<u64, ApiError> {
+    let lev = checked_leverage(leverage, maintenance_rate)?;
+    to_price(entry_price as f64 * (1.0 + 1.0 / lev - maintenance_rate))
 }
 
-fn calculate_liquidation_price_short(entry_price: u64, leverage: u16, maintenance_rate: f64) -> u64 {
-    let entry = entry_price as f64;
+// At 1 / leverage <= maintenance rate the initial margin is already below maintenance,
+// so the position would open past its own liquidation price.
+fn checked_leverage(leverage: u16, maintenance_rate: f64) -> Result<f64, ApiError> {
+    if leverage == 0 {
+        return Err(ApiError::program(ErrorCode::InvalidLeverageValue).detail("Leverage must be at least 1"));
+    }
     let lev = leverage as f64;
-    let liq = entry * (1.0 + 1.0 / lev - maintenance_rate);
-    liq as u64
+    if maintenance_rate * lev >= 1.0 {
+        return Err(ApiError::program(ErrorCode::InvalidLeverageValue)
+            .detail("Leverage leaves no margin above the maintenance rate"));
+    }
+    Ok(lev)
+}
+
+// `as u64` saturates, so out-of-range prices are rejected rather than clamped.
+fn to_price(price: f64) -> Result<u64, ApiError> {
+    if !(0.0..18_446_744_073_709_551_616.0).contains(&price) {
+        return Err(ApiError::program(ErrorCode::CalculationOverflow).detail("Liquidation price out of range"));
+    }
+    Ok(price as u64)
+}
+
+// (Margin + Unrealized PnL) / Position Value, at entry price. None without exposure.
+fn margin_ratio(margin: u64, unrealized_pnl: i64, notional: u64) -> Option<f64> {
+    if notional == 0 {
+        return None;
+    }
+    Some((margin as f64 + unrealized_pnl as f64) / notional as f64)
+}
+
+// Leverage implied by `margin` on `notional`, rounded to the nearest whole x.
+fn effective_leverage(notional: u64, margin: u64) -> Result<u16, ApiError> {
+    if margin == 0 {
+        return Err(ApiError::program(ErrorCode::CannotReduceMargin).detail("Margin must be positive"));
+    }
+    let leverage = (notional as f64 / margin as f64).round().max(1.0);
+    if leverage > u16::MAX as f64 {
+        return Err(ApiError::program(ErrorCode::InvalidLeverageValue).detail("Leverage or position size exceeds limits"));
+    }
+    Ok(leverage as u16)
+}
+
+/// Mirrors the program's `modify_position`: `size_delta` changes the size at
+/// the entry price and `margin_delta` the margin. Leverage, tier, liquidation
+/// price and margin ratio are recomputed for the result.
+fn modify_position_values(position: &Position, size_delta: i64, margin_delta: i64) -> Result<(Position, LeverageTier), ApiError> {
+    if position.status != 1 {
+        return Err(ApiError::program(ErrorCode::PositionAlreadyClosed));
+    }
+    let size = position.size.checked_add_signed(size_delta).ok_or_else(|| {
+        ApiError::program(if size_delta < 0 { ErrorCode::CalculationUnderflow } else { ErrorCode::CalculationOverflow })
+    })?;
+    if size == 0 {
+        return Err(ApiError::program(ErrorCode::InvalidPositionSize).detail("Size must stay positive; close the position instead"));
+    }
+    let margin = position.margin.checked_add_signed(margin_delta).ok_or_else(|| {
+        ApiError::program(if margin_delta < 0 { ErrorCode::CannotReduceMargin } else { ErrorCode::CalculationOverflow })
+    })?;
+
+    let notional = size.saturating_mul(position.entry_price);
+    let leverage = effective_leverage(notional, margin)?;
+    let tier = get_leverage_tier(leverage, size)?;
+    let liquidation_price = if position.side == 1 {
+        calculate_liquidation_price_long(position.entry_price, leverage, tier.maintenance_margin_rate)?
+    } else {
+        calculate_liquidation_price_short(position.entry_price, leverage, tier.maintenance_margin_rate)?
+    };
+
+    let modified = Position {
+        size,
+        margin,
+        leverage,
+        liquidation_price,
+        margin_ratio: margin_ratio(margin, position.unrealized_pnl, notional).unwrap_or(0.0),
+        ..position.clone()
+    };
+    Ok((modified, tier))
+}
+
+// ===== AUTO-DELEVERAGING =====
+const ADL_SCORE_PRECISION: u128 = 1_000_000;
+
+// Mirrors the program's `adl_score`: PnL over margin, scaled by leverage.
+fn adl_score(unrealized_pnl: i64, margin: u64, leverage: u16) -> u128 {
+    if unrealized_pnl <= 0 {
+        return 0;
+    }
+    (unrealized_pnl as u128) * (leverage as u128) * ADL_SCORE_PRECISION / (margin.max(1) as u128)
+}
+
+// ===== REFERRALS =====
+// Mirror the program's `Config.fee_bps` and `Config.referral_share_bps`.
+const TRADING_FEE_BPS: u64 = 10;
+const REFERRAL_SHARE_BPS: u64 = 2_000;
+
+fn referral_rebate(volume: u64) -> u64 {
+    let fee = volume as u128 * TRADING_FEE_BPS as u128 / 10_000;
+    (fee * REFERRAL_SHARE_BPS as u128 / 10_000) as u64
 }
 
 // ===== STRUCTS =====
@@ -78,6 +222,7 @@ fn calculate_liquidation_price_short(entry_price: u64, leverage: u16, maintenanc
 pub struct Position {
     pub id: String,
     pub owner: String,
+    pub sub_account_id: u16,
     pub symbol: String,
     pub side: u8,
     pub size: u64,
@@ -98,14 +243,524 @@ pub struct User {
     pub address: String,
     pub collateral: u64,
     pub locked_collateral: u64,
+    pub sub_accounts: Vec<u16>,
     pub positions: Vec<String>,
     pub total_pnl: i64,
     pub created_at: i64,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReferralCode {
+    pub code: String,
+    pub referrer: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Referral {
+    pub referee: String,
+    pub referrer: String,
+    pub code: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HistoryAction {
+    Opened,
+    Modified,
+    Closed,
+    Liquidated,
+}
+
+impl HistoryAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HistoryAction::Opened => "OPENED",
+            HistoryAction::Modified => "MODIFIED",
+            HistoryAction::Closed => "CLOSED",
+            HistoryAction::Liquidated => "LIQUIDATED",
+        }
+    }
+
+    pub fn parse(action: &str) -> Option<Self> {
+        match action {
+            "OPENED" => Some(HistoryAction::Opened),
+            "MODIFIED" => Some(HistoryAction::Modified),
+            "CLOSED" => Some(HistoryAction::Closed),
+            "LIQUIDATED" => Some(HistoryAction::Liquidated),
+            _ => None,
+        }
+    }
+}
+
+/// One audit-trail entry. `old_values` and `new_values` hold only the fields
+/// that changed; `old_values` is null when the position was opened.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PositionHistory {
+    pub id: String,
+    pub position_id: String,
+    pub owner: String,
+    pub action: HistoryAction,
+    pub old_values: Option<serde_json::Value>,
+    pub new_values: Option<serde_json::Value>,
+    pub reason: Option<String>,
+    pub created_at: i64,
+}
+
+/// PnL at `snapshot_time` for an owner, or for one of its positions when
+/// `position_id` is set.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PnlSnapshot {
+    pub owner: String,
+    pub position_id: Option<String>,
+    pub snapshot_time: i64,
+    pub unrealized_pnl: i64,
+    pub realized_pnl: i64,
+    pub total_pnl: i64,
+    pub margin_ratio: Option<f64>,
+}
+
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct OpenInterest {
+    pub long: u64,
+    pub short: u64,
+}
+
 pub struct AppState {
-    pub positions: Mutex<HashMap<String, Position>>,
-    pub users: Mutex<HashMap<String, User>>,
+    pub repository: Box<dyn Repository>,
+    /// Latest mark price per symbol.
+    pub prices: PriceCache,
+    /// Live updates for `/ws` subscribers.
+    pub events: Events,
+}
+
+// ===== MARK PRICES =====
+const PRICE_SOURCE_RETRY: Duration = Duration::from_secs(5);
+
+fn mark_to_market(position: &Position, mark_price: u64) -> Position {
+    let unrealized_pnl = unrealized_pnl_at(position, mark_price);
+    let notional = position.size.saturating_mul(position.entry_price);
+    Position {
+        unrealized_pnl,
+        margin_ratio: margin_ratio(position.margin, unrealized_pnl, notional).unwrap_or(0.0),
+        ..position.clone()
+    }
+}
+
+/// Caches `ticks` and marks the open positions of their symbols to market.
+/// Returns the ticks that were newer than the cached ones.
+async fn apply_prices(data: &AppState, ticks: Vec<PriceTick>) -> Result<Vec<PriceTick>, StorageError> {
+    let applied = data.prices.update(ticks);
+    if applied.is_empty() {
+        return Ok(applied);
+    }
+    for tick in &applied {
+        data.events.publish(Event::price(tick));
+    }
+
+    let marks: HashMap<&str, u64> = applied.iter().map(|t| (t.symbol.as_str(), t.price)).collect();
+    let (open, marked): (Vec<Position>, Vec<Position>) = data
+        .repository
+        .list_positions()
+        .await?
+        .into_iter()
+        .filter(|p| p.status == 1)
+        .filter_map(|p| marks.get(p.symbol.as_str()).map(|&price| (mark_to_market(&p, price), p)))
+        .map(|(marked, p)| (p, marked))
+        .unzip();
+    data.repository.mark_positions(&marked).await?;
+
+    for (old, new) in open.iter().zip(&marked) {
+        if old.unrealized_pnl != new.unrealized_pnl || old.margin_ratio != new.margin_ratio {
+            data.events.publish(Event::mark(old, new));
+        }
+    }
+    Ok(applied)
+}
+
+async fn run_price_source(data: web::Data<AppState>, mut source: Box<dyn PriceSource>) {
+    loop {
+        match source.next_prices().await {
+            Ok(Some(ticks)) => {
+                if let Err(e) = apply_prices(&data, ticks).await {
+                    log::error!("Applying prices from {} failed: {}", source.name(), e);
+                }
+            }
+            Ok(None) => {
+                log::info!("Price source {} exhausted", source.name());
+                return;
+            }
+            Err(e) => {
+                log::warn!("Price source {} failed: {}", source.name(), e);
+                tokio::time::sleep(PRICE_SOURCE_RETRY).await;
+            }
+        }
+    }
+}
+
+// ===== LIQUIDATION =====
+const DEFAULT_LIQUIDATION_INTERVAL_SECS: u64 = 5;
+
+fn unrealized_pnl_at(position: &Position, mark_price: u64) -> i64 {
+    let diff = mark_price as i128 - position.entry_price as i128;
+    let pnl = if position.side == 1 { diff } else { -diff } * position.size as i128;
+    pnl.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Why an open position is liquidatable at `mark_price`, if it is: the mark has
+/// crossed `liquidation_price`, or the margin ratio is below the tier's
+/// maintenance rate.
+fn liquidation_reason(position: &Position, mark_price: u64) -> Option<&'static str> {
+    if position.status != 1 {
+        return None;
+    }
+    let crossed = if position.side == 1 {
+        mark_price <= position.liquidation_price
+    } else {
+        mark_price >= position.liquidation_price
+    };
+    if crossed {
+        return Some("Mark price crossed liquidation price");
+    }
+
+    let tier = get_leverage_tier(position.leverage, position.size).ok()?;
+    let notional = position.size.saturating_mul(position.entry_price);
+    let ratio = margin_ratio(position.margin, unrealized_pnl_at(position, mark_price), notional)?;
+    (ratio < tier.maintenance_margin_rate).then_some("Margin ratio below maintenance")
+}
+
+/// The position as liquidated: like the program, the whole margin is lost.
+fn liquidated(position: &Position, now: i64) -> Position {
+    let loss = -i64::try_from(position.margin).unwrap_or(i64::MAX);
+    Position {
+        status: 3,
+        unrealized_pnl: loss,
+        realized_pnl: loss,
+        margin_ratio: 0.0,
+        closed_at: now,
+        ..position.clone()
+    }
+}
+
+/// Liquidates `position` at `mark_price` and releases its margin. Returns
+/// false if it was closed or changed since it was read.
+async fn liquidate(data: &AppState, position: &Position, mark_price: u64, reason: &str) -> Result<bool, StorageError> {
+    let now = Local::now().timestamp();
+    let change = Change { action: HistoryAction::Liquidated, reason: Some(reason), at: now };
+    let settled_position = liquidated(position, now);
+    if !data.repository.settle_positions(&position.owner, std::slice::from_ref(&settled_position), &change).await? {
+        return Ok(false);
+    }
+
+    data.events.publish(Event::liquidation(position, Some(mark_price), reason));
+    publish_position_change(data, HistoryAction::Liquidated, Some(position), &settled_position).await;
+    Ok(true)
+}
+
+/// Liquidates every open position that is liquidatable at its symbol's mark.
+async fn run_liquidation_scan(data: &AppState) -> Result<usize, StorageError> {
+    let marks = data.prices.prices();
+    let mut count = 0;
+    for position in data.repository.list_positions().await? {
+        let Some(&mark_price) = marks.get(&position.symbol) else {
+            continue;
+        };
+        if let Some(reason) = liquidation_reason(&position, mark_price) {
+            if liquidate(data, &position, mark_price, reason).await? {
+                log::info!("Liquidated {} at {}: {}", position.id, mark_price, reason);
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+async fn run_liquidations(data: web::Data<AppState>, period: u64) {
+    let mut ticks = tokio::time::interval(Duration::from_secs(period));
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        ticks.tick().await;
+        if let Err(e) = run_liquidation_scan(&data).await {
+            log::error!("Liquidation scan failed: {}", e);
+        }
+    }
+}
+
+// ===== PNL SNAPSHOTS =====
+const DEFAULT_SNAPSHOT_INTERVAL: &str = "hourly";
+
+/// `hourly`, `daily` or a number of seconds.
+fn parse_interval(interval: &str) -> Option<u64> {
+    match interval {
+        "hourly" => Some(3_600),
+        "daily" => Some(86_400),
+        secs => secs.parse::<u32>().ok().filter(|&s| s > 0).map(u64::from),
+    }
+}
+
+#[derive(Default)]
+struct OwnerPnl {
+    unrealized_pnl: i64,
+    realized_pnl: i64,
+    margin: u64,
+    notional: u64,
+}
+
+/// One snapshot per open position, and one per registered user or owner of a
+/// position. Realized PnL counts positions that are no longer open.
+fn pnl_snapshots(users: &[User], positions: &[Position], now: i64) -> Vec<PnlSnapshot> {
+    let mut owners: BTreeMap<&str, OwnerPnl> = users.iter().map(|u| (u.address.as_str(), OwnerPnl::default())).collect();
+    let mut snapshots = Vec::new();
+
+    for p in positions {
+        let owner = owners.entry(p.owner.as_str()).or_default();
+        if p.status != 1 {
+            owner.realized_pnl = owner.realized_pnl.saturating_add(p.realized_pnl);
+            continue;
+        }
+        let notional = p.size.saturating_mul(p.entry_price);
+        owner.unrealized_pnl = owner.unrealized_pnl.saturating_add(p.unrealized_pnl);
+        owner.margin = owner.margin.saturating_add(p.margin);
+        owner.notional = owner.notional.saturating_add(notional);
+
+        snapshots.push(PnlSnapshot {
+            owner: p.owner.clone(),
+            position_id: Some(p.id.clone()),
+            snapshot_time: now,
+            unrealized_pnl: p.unrealized_pnl,
+            realized_pnl: p.realized_pnl,
+            total_pnl: p.unrealized_pnl.saturating_add(p.realized_pnl),
+            margin_ratio: margin_ratio(p.margin, p.unrealized_pnl, notional),
+        });
+    }
+
+    snapshots.extend(owners.into_iter().map(|(owner, pnl)| PnlSnapshot {
+        owner: owner.to_string(),
+        position_id: None,
+        snapshot_time: now,
+        unrealized_pnl: pnl.unrealized_pnl,
+        realized_pnl: pnl.realized_pnl,
+        total_pnl: pnl.unrealized_pnl.saturating_add(pnl.realized_pnl),
+        margin_ratio: margin_ratio(pnl.margin, pnl.unrealized_pnl, pnl.notional),
+    }));
+    snapshots
+}
+
+/// Keeps the last snapshot in each `interval`-second bucket of an oldest-first series.
+fn downsample(snapshots: Vec<PnlSnapshot>, interval: u64) -> Vec<PnlSnapshot> {
+    let bucket = |s: &PnlSnapshot| s.snapshot_time.div_euclid(interval as i64);
+    let mut series: Vec<PnlSnapshot> = Vec::new();
+    for snapshot in snapshots {
+        match series.last_mut() {
+            Some(last) if bucket(last) == bucket(&snapshot) => *last = snapshot,
+            _ => series.push(snapshot),
+        }
+    }
+    series
+}
+
+async fn take_pnl_snapshots(data: &AppState) -> Result<usize, StorageError> {
+    let users = data.repository.list_users().await?;
+    let positions = data.repository.list_positions().await?;
+    let snapshots = pnl_snapshots(&users, &positions, Local::now().timestamp());
+    data.repository.insert_pnl_snapshots(&snapshots).await?;
+    Ok(snapshots.len())
+}
+
+/// Snapshots every `period` seconds, starting one period after startup.
+async fn run_pnl_snapshots(data: web::Data<AppState>, period: u64) {
+    let period = Duration::from_secs(period);
+    let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        ticks.tick().await;
+        match take_pnl_snapshots(&data).await {
+            Ok(count) => log::info!("Recorded {} PnL snapshots", count),
+            Err(e) => log::error!("PnL snapshot failed: {}", e),
+        }
+    }
+}
+
+// ===== LIVE UPDATES =====
+async fn publish_account(data: &AppState, owner: &str) {
+    match data.repository.get_user(owner).await {
+        Ok(Some(user)) => data.events.publish(Event::account(&user)),
+        Ok(None) => {}
+        Err(e) => log::error!("Reading account {} failed: {}", owner, e),
+    }
+}
+
+/// Publishes the change from `old` to `new`, then the owner's account.
+async fn publish_position_change(data: &AppState, action: HistoryAction, old: Option<&Position>, new: &Position) {
+    data.events.publish(Event::position(action, old, new));
+    publish_account(data, &new.owner).await;
+}
+
+/// The body of `/metrics`, without its timestamp.
+async fn system_metrics(data: &AppState) -> Result<serde_json::Map<String, serde_json::Value>, StorageError> {
+    let positions = data.repository.list_positions().await?;
+    let users = data.repository.list_users().await?;
+
+    let total_volume: u64 = positions
+        .iter()
+        .map(|p| p.size.saturating_mul(p.entry_price))
+        .sum();
+
+    let total_pnl: i64 = positions
+        .iter()
+        .map(|p| p.unrealized_pnl)
+        .sum();
+
+    let mut open_interest: HashMap<String, OpenInterest> = HashMap::new();
+    for p in positions.iter().filter(|p| p.status == 1) {
+        let oi = open_interest.entry(p.symbol.clone()).or_default();
+        if p.side == 1 {
+            oi.long = oi.long.saturating_add(p.size);
+        } else {
+            oi.short = oi.short.saturating_add(p.size);
+        }
+    }
+
+    let serde_json::Value::Object(body) = serde_json::json!({
+        "user_count": users.len(),
+        "position_count": positions.len(),
+        "open_positions": positions.iter().filter(|p| p.status == 1).count(),
+        "closed_positions": positions.iter().filter(|p| p.status == 2).count(),
+        "liquidated_positions": positions.iter().filter(|p| p.status == 3).count(),
+        "total_volume": total_volume,
+        "total_pnl": total_pnl,
+        "open_interest": open_interest,
+        "leverage_tiers": LEVERAGE_TIERS
+    }) else {
+        unreachable!("metrics are an object")
+    };
+    Ok(body)
+}
+
+/// After each burst of state changes, publishes the `/metrics` fields that
+/// changed since the last update.
+async fn run_metrics_updates(data: web::Data<AppState>) {
+    let mut events = data.events.subscribe();
+    let mut last = system_metrics(&data).await.unwrap_or_default();
+    loop {
+        match events.recv().await {
+            Ok(event) if event.channel == Channel::Metrics => continue,
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => return,
+        }
+        while let Ok(_) | Err(TryRecvError::Lagged(_)) = events.try_recv() {}
+
+        let current = match system_metrics(&data).await {
+            Ok(current) => current,
+            Err(e) => {
+                log::error!("Computing metrics failed: {}", e);
+                continue;
+            }
+        };
+        let changes: serde_json::Map<String, serde_json::Value> =
+            current.iter().filter(|(k, v)| last.get(*k) != Some(*v)).map(|(k, v)| (k.clone(), v.clone())).collect();
+        if !changes.is_empty() {
+            data.events.publish(Event::metrics(changes));
+        }
+        last = current;
+    }
+}
+
+/// The current state of `channel`, sent on subscribing so later deltas have
+/// something to apply to. Liquidation alerts have none.
+async fn channel_snapshot(data: &AppState, channel: &Channel) -> Result<Option<serde_json::Value>, StorageError> {
+    let snapshot = match channel {
+        Channel::Positions(owner) => serde_json::json!({
+            "account": data.repository.get_user(owner).await?,
+            "positions": data.repository.user_positions(owner).await?
+        }),
+        Channel::Prices(symbol) => serde_json::json!(data.prices.get(symbol)),
+        Channel::Liquidations => return Ok(None),
+        Channel::Metrics => serde_json::Value::Object(system_metrics(data).await?),
+    };
+    Ok(Some(serde_json::json!({"channel": channel.to_string(), "type": "snapshot", "data": snapshot})))
+}
+
+/// Handles `{"action": "subscribe" | "unsubscribe", "channels": [...]}` from a
+/// client and returns the replies.
+async fn handle_ws_request(data: &AppState, subscriptions: &mut HashSet<Channel>, text: &str) -> Vec<serde_json::Value> {
+    let error = |e: &str| vec![serde_json::json!({"type": "error", "error": e})];
+
+    let Ok(request) = serde_json::from_str::<serde_json::Value>(text) else {
+        return error("Invalid JSON");
+    };
+    let channels: Vec<Channel> = match request.get("channels").and_then(|v| v.as_array()) {
+        Some(names) => match names.iter().map(|n| Channel::parse(n.as_str().unwrap_or_default())).collect() {
+            Ok(channels) => channels,
+            Err(e) => return error(&e),
+        },
+        None => return error("Missing channels"),
+    };
+    let names: Vec<String> = channels.iter().map(Channel::to_string).collect();
+
+    match request.get("action").and_then(|v| v.as_str()) {
+        Some("subscribe") => {
+            let mut replies = vec![serde_json::json!({"type": "subscribed", "channels": names})];
+            for channel in channels {
+                if !subscriptions.insert(channel.clone()) {
+                    continue;
+                }
+                match channel_snapshot(data, &channel).await {
+                    Ok(snapshot) => replies.extend(snapshot),
+                    Err(e) => {
+                        log::error!("Snapshot of {} failed: {}", channel, e);
+                        replies.extend(error("Storage error"));
+                    }
+                }
+            }
+            replies
+        }
+        Some("unsubscribe") => {
+            for channel in &channels {
+                subscriptions.remove(channel);
+            }
+            vec![serde_json::json!({"type": "unsubscribed", "channels": names})]
+        }
+        _ => error("Unknown action"),
+    }
+}
+
+async fn run_ws_session(data: web::Data<AppState>, mut session: actix_ws::Session, mut stream: actix_ws::MessageStream) {
+    let mut events = data.events.subscribe();
+    let mut subscriptions: HashSet<Channel> = HashSet::new();
+    loop {
+        let replies = tokio::select! {
+            message = stream.recv() => match message {
+                Some(Ok(actix_ws::Message::Text(text))) => handle_ws_request(&data, &mut subscriptions, &text).await,
+                Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                    if session.pong(&bytes).await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                Some(Ok(actix_ws::Message::Close(reason))) => {
+                    let _ = session.close(reason).await;
+                    return;
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(_)) | None => break,
+            },
+            event = events.recv() => match event {
+                Ok(event) if subscriptions.contains(&event.channel) => vec![event.to_message()],
+                Ok(_) => continue,
+                // Deltas were lost; the client should subscribe again for fresh snapshots.
+                Err(RecvError::Lagged(missed)) => vec![serde_json::json!({"type": "lagged", "missed": missed})],
+                Err(RecvError::Closed) => break,
+            },
+        };
+        for reply in replies {
+            if session.text(reply.to_string()).await.is_err() {
+                return;
+            }
+        }
+    }
+    let _ = session.close(None).await;
 }
 
 // ===== HANDLERS =====
@@ -133,233 +788,376 @@ async fn index() -> actix_web::Result<NamedFile> {
 #[actix_web::post("/user/initialize")]
 async fn initialize_user(
     data: web::Data<AppState>,
-    req: web::Json<serde_json::Value>,
-) -> HttpResponse {
-    let address = match req.get("address").and_then(|v| v.as_str()) {
-        Some(addr) => addr.to_string(),
-        None => return HttpResponse::BadRequest().json(serde_json::json!({"error": "Missing address"})),
-    };
-
-    let user = User {
-        address: address.clone(),
-        collateral: 0,
-        locked_collateral: 0,
-        positions: vec![],
-        total_pnl: 0,
-        created_at: Local::now().timestamp(),
-    };
+    req: web::Json<InitializeUserRequest>,
+) -> Result<HttpResponse, ApiError> {
+    req.validate()?;
+    let InitializeUserRequest { address, sub_account_id } = req.into_inner();
 
-    data.users.lock().unwrap().insert(address.clone(), user);
+    if !data.repository.initialize_user(&address, sub_account_id, Local::now().timestamp()).await? {
+        return Err(ApiError::conflict("Sub-account already initialized").with("sub_account_id", sub_account_id));
+    }
+    publish_account(&data, &address).await;
 
-    HttpResponse::Ok().json(serde_json::json!({
+    Ok(HttpResponse::Ok().json(serde_json::json!({
         "success": true,
         "message": "User initialized",
         "address": address,
+        "sub_account_id": sub_account_id,
         "timestamp": Local::now().to_rfc3339()
-    }))
+    })))
 }
 
 #[actix_web::post("/position/open")]
 async fn open_position(
     data: web::Data<AppState>,
-    req: web::Json<serde_json::Value>,
-) -> HttpResponse {
-    let owner = match req.get("owner").and_then(|v| v.as_str()) {
-        Some(o) => o.to_string(),
-        None => return HttpResponse::BadRequest().json(serde_json::json!({"error": "Missing owner"})),
-    };
-
-    let symbol = match req.get("symbol").and_then(|v| v.as_str()) {
-        Some(s) => s.to_string(),
-        None => return HttpResponse::BadRequest().json(serde_json::json!({"error": "Missing symbol"})),
-    };
+    req: web::Json<OpenPositionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let (side, leverage) = req.validate()?;
+    let OpenPositionRequest { owner, symbol, size, entry_price, max_price, min_price, sub_account_id, .. } = req.into_inner();
+    check_mark_slippage(&data.prices, &symbol, max_price, min_price)?;
 
-    let side = match req.get("side").and_then(|v| v.as_u64()) {
-        Some(s) => s as u8,
-        None => return HttpResponse::BadRequest().json(serde_json::json!({"error": "Missing side"})),
-    };
+    let user = data.repository.get_user(&owner).await?.ok_or_else(|| ApiError::not_found("User not found"))?;
+    if !user.sub_accounts.contains(&sub_account_id) {
+        return Err(ApiError::program(ErrorCode::InvalidSubAccount).with("sub_account_id", sub_account_id));
+    }
 
-    let size = match req.get("size").and_then(|v| v.as_u64()) {
-        Some(s) => s,
-        None => return HttpResponse::BadRequest().json(serde_json::json!({"error": "Missing size"})),
+    // Validate leverage tier
+    let tier = get_leverage_tier(leverage, size)
+        .map_err(|e| e.with("leverage", leverage).with("position_size", size).with("available_tiers", LEVERAGE_TIERS))?;
+    let margin = ((entry_price as f64 * size as f64) / leverage as f64) as u64;
+    let liquidation_price = if side == 1 {
+        calculate_liquidation_price_long(entry_price, leverage, tier.maintenance_margin_rate)?
+    } else {
+        calculate_liquidation_price_short(entry_price, leverage, tier.maintenance_margin_rate)?
     };
 
-    let entry_price = match req.get("entry_price").and_then(|v| v.as_u64()) {
-        Some(p) => p,
-        None => return HttpResponse::BadRequest().json(serde_json::json!({"error": "Missing entry_price"})),
-    };
+    let position_id = format!("{}-{}", owner, Uuid::new_v4());
 
-    let leverage = match req.get("leverage").and_then(|v| v.as_u64()) {
-        Some(l) => l as u16,
-        None => return HttpResponse::BadRequest().json(serde_json::json!({"error": "Missing leverage"})),
+    let position = Position {
+        id: position_id.clone(),
+        owner,
+        sub_account_id,
+        symbol,
+        side,
+        size,
+        entry_price,
+        leverage,
+        status: 1,
+        margin,
+        unrealized_pnl: 0,
+        realized_pnl: 0,
+        liquidation_price,
+        margin_ratio: 1.0,
+        opened_at: Local::now().timestamp(),
+        closed_at: 0,
     };
 
-    // Validate leverage tier
-    match get_leverage_tier(leverage, size) {
-        Ok(tier) => {
-            let margin = ((entry_price as f64 * size as f64) / leverage as f64) as u64;
-            let _position_value = entry_price.saturating_mul(size);
-            let liquidation_price = if side == 1 {
-                calculate_liquidation_price_long(entry_price, leverage, tier.maintenance_margin_rate)
-            } else {
-                calculate_liquidation_price_short(entry_price, leverage, tier.maintenance_margin_rate)
-            };
-
-            let position_id = format!("{}-{}", owner, Uuid::new_v4());
-
-            let position = Position {
-                id: position_id.clone(),
-                owner: owner.clone(),
-                symbol,
-                side,
-                size,
-                entry_price,
-                leverage,
-                status: 1,
-                margin,
-                unrealized_pnl: 0,
-                realized_pnl: 0,
-                liquidation_price,
-                margin_ratio: 1.0,
-                opened_at: Local::now().timestamp(),
-                closed_at: 0,
-            };
-
-            data.positions.lock().unwrap().insert(position_id.clone(), position.clone());
-
-            if let Some(user) = data.users.lock().unwrap().get_mut(&owner) {
-                user.positions.push(position_id.clone());
-                user.locked_collateral = user.locked_collateral.saturating_add(margin);
-            }
-
-            HttpResponse::Ok().json(serde_json::json!({
-                "success": true,
-                "position_id": position_id,
-                "position": position,
-                "tier": tier,
-                "timestamp": Local::now().to_rfc3339()
-            }))
-        }
-        Err(e) => {
-            HttpResponse::BadRequest().json(serde_json::json!({
-                "error": e,
-                "leverage": leverage,
-                "position_size": size,
-                "available_tiers": LEVERAGE_TIERS
-            }))
-        }
+    if !data.repository.open_position(&position).await? {
+        return Err(ApiError::not_found("User not found"));
     }
+    publish_position_change(&data, HistoryAction::Opened, None, &position).await;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "position_id": position_id,
+        "position": position,
+        "tier": tier,
+        "timestamp": Local::now().to_rfc3339()
+    })))
 }
 
 #[actix_web::get("/position/{id}")]
 async fn get_position(
     data: web::Data<AppState>,
     id: web::Path<String>,
-) -> HttpResponse {
+) -> Result<HttpResponse, ApiError> {
+    match data.repository.get_position(&id).await? {
+        Some(position) => Ok(HttpResponse::Ok().json(position)),
+        None => Err(ApiError::program(ErrorCode::PositionNotFound)),
+    }
+}
+
+#[actix_web::get("/position/{id}/history")]
+async fn position_history(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    query: web::Query<HistoryQuery>,
+) -> Result<HttpResponse, ApiError> {
     let position_id = id.into_inner();
+    let range = query.time_range()?;
 
-    match data.positions.lock().unwrap().get(&position_id) {
-        Some(position) => HttpResponse::Ok().json(position),
-        None => HttpResponse::NotFound().json(serde_json::json!({"error": "Position not found"})),
+    if data.repository.get_position(&position_id).await?.is_none() {
+        return Err(ApiError::program(ErrorCode::PositionNotFound));
     }
+
+    let history = data.repository.position_history(&position_id, range).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "position_id": position_id,
+        "total": history.len(),
+        "history": history
+    })))
 }
 
 #[actix_web::get("/user/{address}")]
 async fn get_user(
     data: web::Data<AppState>,
     address: web::Path<String>,
-) -> HttpResponse {
-    let user_address = address.into_inner();
+) -> Result<HttpResponse, ApiError> {
+    match data.repository.get_user(&address).await? {
+        Some(user) => Ok(HttpResponse::Ok().json(user)),
+        None => Err(ApiError::not_found("User not found")),
+    }
+}
+
+#[actix_web::post("/position/modify")]
+async fn modify_position(
+    data: web::Data<AppState>,
+    req: web::Json<ModifyPositionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    req.validate()?;
+    let size_delta = req.size_delta.unwrap_or(0);
+    let margin_delta = req.margin_delta.unwrap_or(0);
+
+    let position = data
+        .repository
+        .get_position(&req.position_id)
+        .await?
+        .ok_or(ApiError::program(ErrorCode::PositionNotFound))?;
 
-    match data.users.lock().unwrap().get(&user_address) {
-        Some(user) => HttpResponse::Ok().json(user),
-        None => HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"})),
+    let (modified, tier) = modify_position_values(&position, size_delta, margin_delta).map_err(|e| {
+        e.with("size_delta", size_delta).with("margin_delta", margin_delta).with("available_tiers", LEVERAGE_TIERS)
+    })?;
+
+    let change = Change { action: HistoryAction::Modified, reason: Some("Modified by owner"), at: Local::now().timestamp() };
+    if !data.repository.modify_position(&position, &modified, &change).await? {
+        return Err(ApiError::conflict("Position changed concurrently; retry"));
     }
+    publish_position_change(&data, HistoryAction::Modified, Some(&position), &modified).await;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "position_id": req.position_id,
+        "position": modified,
+        "tier": tier,
+        "timestamp": Local::now().to_rfc3339()
+    })))
+}
+
+/// Closes one position at the cached mark of its symbol, settling its margin
+/// and PnL into the owner's account.
+async fn close_one(data: &AppState, req: &ClosePositionRequest) -> Result<Position, ApiError> {
+    let open = data
+        .repository
+        .get_position(&req.position_id)
+        .await?
+        .ok_or(ApiError::program(ErrorCode::PositionNotFound))?;
+    if open.status != 1 {
+        return Err(ApiError::program(ErrorCode::PositionAlreadyClosed));
+    }
+    let exit_price = cached_mark(&data.prices, &open.symbol)?;
+    check_slippage(exit_price, req.max_price, req.min_price)?;
+
+    let pnl = unrealized_pnl_at(&open, exit_price);
+    let position = Position {
+        status: 2,
+        unrealized_pnl: pnl,
+        realized_pnl: pnl,
+        closed_at: Local::now().timestamp(),
+        ..open.clone()
+    };
+
+    let change = Change { action: HistoryAction::Closed, reason: Some("Closed by owner"), at: position.closed_at };
+    if !data.repository.settle_positions(&open.owner, std::slice::from_ref(&position), &change).await? {
+        let still_open = data.repository.get_position(&open.id).await?.is_some_and(|p| p.status == 1);
+        return Err(if still_open {
+            ApiError::conflict("Position changed concurrently; retry")
+        } else {
+            ApiError::program(ErrorCode::PositionAlreadyClosed)
+        });
+    }
+    publish_position_change(data, HistoryAction::Closed, Some(&open), &position).await;
+    Ok(position)
 }
 
 #[actix_web::post("/position/close")]
 async fn close_position(
     data: web::Data<AppState>,
-    req: web::Json<serde_json::Value>,
-) -> HttpResponse {
-    let position_id = match req.get("position_id").and_then(|v| v.as_str()) {
-        Some(id) => id.to_string(),
-        None => return HttpResponse::BadRequest().json(serde_json::json!({"error": "Missing position_id"})),
-    };
+    req: web::Json<ClosePositionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    req.validate()?;
+    let position = close_one(&data, &req).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "position_id": req.position_id,
+        "realized_pnl": position.realized_pnl,
+        "timestamp": Local::now().to_rfc3339()
+    })))
+}
+
+#[actix_web::post("/position/liquidate")]
+async fn liquidate_position(
+    data: web::Data<AppState>,
+    req: web::Json<LiquidatePositionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    req.validate()?;
 
-    let exit_price = match req.get("exit_price").and_then(|v| v.as_u64()) {
-        Some(p) => p,
-        None => return HttpResponse::BadRequest().json(serde_json::json!({"error": "Missing exit_price"})),
+    let position = data
+        .repository
+        .get_position(&req.position_id)
+        .await?
+        .ok_or(ApiError::program(ErrorCode::PositionNotFound))?;
+    if position.status != 1 {
+        return Err(ApiError::program(ErrorCode::PositionAlreadyClosed));
+    }
+
+    let mark_price = cached_mark(&data.prices, &position.symbol)?;
+
+    let Some(reason) = liquidation_reason(&position, mark_price) else {
+        return Err(ApiError::program(ErrorCode::PositionNotLiquidatable)
+            .with("mark_price", mark_price)
+            .with("liquidation_price", position.liquidation_price));
     };
 
-    let mut positions = data.positions.lock().unwrap();
-
-    match positions.get_mut(&position_id) {
-        Some(position) => {
-            let is_long = position.side == 1;
-            let pnl = if is_long {
-                ((exit_price as i64) - (position.entry_price as i64)) * (position.size as i64)
-            } else {
-                ((position.entry_price as i64) - (exit_price as i64)) * (position.size as i64)
-            };
-
-            position.status = 2;
-            position.unrealized_pnl = pnl;
-            position.realized_pnl = pnl;
-            position.closed_at = Local::now().timestamp();
-
-            HttpResponse::Ok().json(serde_json::json!({
-                "success": true,
-                "position_id": position_id,
-                "realized_pnl": pnl,
-                "timestamp": Local::now().to_rfc3339()
-            }))
+    if !liquidate(&data, &position, mark_price, &format!("{} (operator)", reason)).await? {
+        return Err(ApiError::conflict("Position changed concurrently; retry"));
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "position_id": req.position_id,
+        "mark_price": mark_price,
+        "reason": reason,
+        "released_margin": position.margin,
+        "timestamp": Local::now().to_rfc3339()
+    })))
+}
+
+/// Closes every open position of `owner` (optionally one sub-account) at the
+/// cached mark of its symbol, all or nothing like the on-chain instruction.
+/// Returns each closed position with its exit price.
+async fn close_all(
+    data: &AppState,
+    owner: &str,
+    sub_account_id: Option<u16>,
+    bounds: &HashMap<String, PriceBounds>,
+) -> Result<Vec<(Position, u64)>, ApiError> {
+    let open: Vec<Position> = data
+        .repository
+        .user_positions(owner)
+        .await?
+        .into_iter()
+        .filter(|p| p.status == 1)
+        .filter(|p| sub_account_id.is_none_or(|id| p.sub_account_id == id))
+        .collect();
+
+    let marks = data.prices.prices();
+    let mut missing: Vec<String> = open
+        .iter()
+        .filter(|p| !marks.contains_key(&p.symbol))
+        .map(|p| p.symbol.clone())
+        .collect();
+    if !missing.is_empty() {
+        missing.sort();
+        missing.dedup();
+        return Err(ApiError::program(ErrorCode::InvalidPrice).detail("No mark price for symbols").with("symbols", missing));
+    }
+
+    let now = Local::now().timestamp();
+    let mut targets = Vec::with_capacity(open.len());
+    for position in &open {
+        let exit_price = marks[&position.symbol];
+        if let Some(bounds) = bounds.get(&position.symbol) {
+            check_slippage(exit_price, bounds.max_price, bounds.min_price).map_err(|e| e.with("symbol", &position.symbol))?;
         }
-        None => HttpResponse::NotFound().json(serde_json::json!({"error": "Position not found"})),
+        let pnl = unrealized_pnl_at(position, exit_price);
+        targets.push(Position {
+            status: 2,
+            unrealized_pnl: pnl,
+            realized_pnl: pnl,
+            closed_at: now,
+            ..position.clone()
+        });
+    }
+
+    let change = Change { action: HistoryAction::Closed, reason: Some("Closed by close-all"), at: now };
+    if !data.repository.settle_positions(owner, &targets, &change).await? {
+        return Err(ApiError::conflict("Positions changed concurrently; retry"));
+    }
+    for (old, new) in open.iter().zip(&targets) {
+        data.events.publish(Event::position(HistoryAction::Closed, Some(old), new));
     }
+    publish_account(data, owner).await;
+
+    Ok(targets
+        .into_iter()
+        .map(|position| {
+            let exit_price = marks[&position.symbol];
+            (position, exit_price)
+        })
+        .collect())
 }
 
-#[actix_web::get("/positions")]
-async fn list_positions(data: web::Data<AppState>) -> HttpResponse {
-    let positions: Vec<Position> = data.positions
-        .lock()
-        .unwrap()
-        .values()
-        .cloned()
+#[actix_web::post("/user/{address}/close-all")]
+async fn close_all_positions(
+    data: web::Data<AppState>,
+    address: web::Path<String>,
+    req: web::Json<CloseAllRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let addr = address.into_inner();
+    let CloseAllRequest { bounds, sub_account_id } = req.into_inner();
+
+    let closed = close_all(&data, &addr, sub_account_id, &bounds).await?;
+    let total_realized_pnl = closed.iter().fold(0i64, |total, (p, _)| total.saturating_add(p.realized_pnl));
+    let closed: Vec<serde_json::Value> = closed
+        .iter()
+        .map(|(position, exit_price)| {
+            serde_json::json!({
+                "position_id": position.id,
+                "symbol": position.symbol,
+                "exit_price": exit_price,
+                "realized_pnl": position.realized_pnl
+            })
+        })
         .collect();
 
-    HttpResponse::Ok().json(serde_json::json!({
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "address": addr,
+        "closed_count": closed.len(),
+        "closed": closed,
+        "total_realized_pnl": total_realized_pnl,
+        "timestamp": Local::now().to_rfc3339()
+    })))
+}
+
+#[actix_web::get("/positions")]
+async fn list_positions(data: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let positions = data.repository.list_positions().await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
         "total": positions.len(),
         "open_positions": positions.iter().filter(|p| p.status == 1).count(),
         "closed_positions": positions.iter().filter(|p| p.status == 2).count(),
+        "liquidated_positions": positions.iter().filter(|p| p.status == 3).count(),
         "positions": positions
-    }))
+    })))
 }
 
 #[actix_web::get("/users")]
-async fn list_users(data: web::Data<AppState>) -> HttpResponse {
-    let users: Vec<User> = data.users
-        .lock()
-        .unwrap()
-        .values()
-        .cloned()
-        .collect();
+async fn list_users(data: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let users = data.repository.list_users().await?;
 
-    HttpResponse::Ok().json(serde_json::json!({
+    Ok(HttpResponse::Ok().json(serde_json::json!({
         "total": users.len(),
         "users": users
-    }))
+    })))
 }
 
 #[actix_web::get("/user/{address}/pnl")]
-async fn user_pnl(data: web::Data<AppState>, address: web::Path<String>) -> HttpResponse {
+async fn user_pnl(data: web::Data<AppState>, address: web::Path<String>) -> Result<HttpResponse, ApiError> {
     let addr = address.into_inner();
-    let positions = data.positions.lock().unwrap();
-    
-    let user_positions: Vec<&Position> = positions
-        .values()
-        .filter(|p| p.owner == addr)
-        .collect();
+    let user_positions = data.repository.user_positions(&addr).await?;
 
     let total_unrealized_pnl: i64 = user_positions
         .iter()
@@ -369,64 +1167,350 @@ async fn user_pnl(data: web::Data<AppState>, address: web::Path<String>) -> Http
 
     let total_realized_pnl: i64 = user_positions
         .iter()
-        .filter(|p| p.status == 2)
+        .filter(|p| p.status != 1)
         .map(|p| p.realized_pnl)
         .sum();
 
-    HttpResponse::Ok().json(serde_json::json!({
+    Ok(HttpResponse::Ok().json(serde_json::json!({
         "address": addr,
         "open_positions": user_positions.iter().filter(|p| p.status == 1).count(),
         "closed_positions": user_positions.iter().filter(|p| p.status == 2).count(),
+        "liquidated_positions": user_positions.iter().filter(|p| p.status == 3).count(),
         "total_unrealized_pnl": total_unrealized_pnl,
         "total_realized_pnl": total_realized_pnl,
         "total_pnl": total_unrealized_pnl + total_realized_pnl
-    }))
+    })))
 }
 
-#[actix_web::get("/metrics")]
-async fn metrics(data: web::Data<AppState>) -> HttpResponse {
-    let positions = data.positions.lock().unwrap();
-    let users = data.users.lock().unwrap();
-    
-    let total_volume: u64 = positions
-        .values()
-        .map(|p| p.size.saturating_mul(p.entry_price))
-        .sum();
+#[actix_web::get("/user/{address}/pnl/history")]
+async fn user_pnl_history(
+    data: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<PnlHistoryQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let addr = address.into_inner();
+    let range = query.time_range()?;
+    let interval = query.interval()?;
 
-    let total_pnl: i64 = positions
-        .values()
-        .map(|p| p.unrealized_pnl)
-        .sum();
+    let snapshots = data.repository.pnl_snapshots(&addr, range).await?;
+    let series: Vec<serde_json::Value> = match interval {
+        Some(secs) => downsample(snapshots, secs),
+        None => snapshots,
+    }
+    .into_iter()
+    .map(|s| {
+        serde_json::json!({
+            "timestamp": s.snapshot_time,
+            "unrealized_pnl": s.unrealized_pnl,
+            "realized_pnl": s.realized_pnl,
+            "total_pnl": s.total_pnl,
+            "margin_ratio": s.margin_ratio
+        })
+    })
+    .collect();
 
-    HttpResponse::Ok().json(serde_json::json!({
-        "user_count": users.len(),
-        "position_count": positions.len(),
-        "open_positions": positions.values().filter(|p| p.status == 1).count(),
-        "closed_positions": positions.values().filter(|p| p.status == 2).count(),
-        "total_volume": total_volume,
-        "total_pnl": total_pnl,
-        "leverage_tiers": LEVERAGE_TIERS,
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "address": addr,
+        "interval": interval,
+        "total": series.len(),
+        "series": series
+    })))
+}
+
+#[actix_web::get("/user/{address}/history")]
+async fn user_history(
+    data: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<HistoryQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let addr = address.into_inner();
+    let range = query.time_range()?;
+
+    let history = data.repository.user_history(&addr, range).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "address": addr,
+        "total": history.len(),
+        "history": history
+    })))
+}
+
+#[actix_web::get("/user/{address}/adl")]
+async fn user_adl_queue(data: web::Data<AppState>, address: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let addr = address.into_inner();
+    let open: Vec<Position> = data.repository.list_positions().await?.into_iter().filter(|p| p.status == 1).collect();
+
+    let queue: Vec<serde_json::Value> = open
+        .iter()
+        .filter(|p| p.owner == addr)
+        .map(|p| {
+            let score = adl_score(p.unrealized_pnl, p.margin, p.leverage);
+            let peers: Vec<&Position> = open
+                .iter()
+                .filter(|o| o.symbol == p.symbol && o.side == p.side)
+                .collect();
+            let ahead = peers
+                .iter()
+                .filter(|o| {
+                    let other = adl_score(o.unrealized_pnl, o.margin, o.leverage);
+                    other > score || (other == score && o.id < p.id)
+                })
+                .count();
+
+            serde_json::json!({
+                "position_id": p.id,
+                "symbol": p.symbol,
+                "side": p.side,
+                "adl_score": score,
+                "queue_position": ahead + 1,
+                "queue_size": peers.len()
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "address": addr,
+        "positions": queue
+    })))
+}
+
+#[actix_web::post("/referral/code")]
+async fn register_referral_code(
+    data: web::Data<AppState>,
+    req: web::Json<ReferralCodeRequest>,
+) -> Result<HttpResponse, ApiError> {
+    req.validate()?;
+    let ReferralCodeRequest { referrer, code } = req.into_inner();
+
+    let referral_code = ReferralCode {
+        code,
+        referrer,
+        created_at: Local::now().timestamp(),
+    };
+    if !data.repository.insert_referral_code(&referral_code).await? {
+        return Err(ApiError::conflict("Referral code already registered"));
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "referral_code": referral_code,
         "timestamp": Local::now().to_rfc3339()
+    })))
+}
+
+#[actix_web::post("/referral/register")]
+async fn register_referral(
+    data: web::Data<AppState>,
+    req: web::Json<RegisterReferralRequest>,
+) -> Result<HttpResponse, ApiError> {
+    req.validate()?;
+    let RegisterReferralRequest { referee, code } = req.into_inner();
+
+    let referrer = data
+        .repository
+        .get_referral_code(&code)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Referral code not found"))?
+        .referrer;
+
+    if referrer == referee {
+        return Err(ApiError::program(ErrorCode::SelfReferral));
+    }
+
+    let referral = Referral {
+        referee,
+        referrer,
+        code,
+        created_at: Local::now().timestamp(),
+    };
+    if !data.repository.insert_referral(&referral).await? {
+        return Err(ApiError::conflict("Referee already registered"));
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "referral": referral,
+        "timestamp": Local::now().to_rfc3339()
+    })))
+}
+
+// Volume and rebates for each referee of `referrer`, counting positions opened after registration.
+async fn referee_stats(data: &AppState, referrer: &str) -> Result<Vec<serde_json::Value>, StorageError> {
+    let mut stats = Vec::new();
+    for r in data.repository.referrals(referrer).await? {
+        let volume: u64 = data
+            .repository
+            .user_positions(&r.referee)
+            .await?
+            .iter()
+            .filter(|p| p.opened_at >= r.created_at)
+            .map(|p| p.size.saturating_mul(p.entry_price))
+            .fold(0, u64::saturating_add);
+
+        stats.push(serde_json::json!({
+            "address": r.referee,
+            "code": r.code,
+            "volume": volume,
+            "rebates": referral_rebate(volume),
+            "registered_at": r.created_at
+        }));
+    }
+    Ok(stats)
+}
+
+#[actix_web::get("/user/{address}/referees")]
+async fn user_referees(data: web::Data<AppState>, address: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let addr = address.into_inner();
+    let referees = referee_stats(&data, &addr).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "address": addr,
+        "total": referees.len(),
+        "referees": referees
+    })))
+}
+
+#[actix_web::get("/user/{address}/rebates")]
+async fn user_rebates(data: web::Data<AppState>, address: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let addr = address.into_inner();
+    let referees = referee_stats(&data, &addr).await?;
+
+    let total_volume = referees
+        .iter()
+        .filter_map(|r| r["volume"].as_u64())
+        .fold(0, u64::saturating_add);
+    let rebates_earned = referees
+        .iter()
+        .filter_map(|r| r["rebates"].as_u64())
+        .fold(0, u64::saturating_add);
+
+    let codes: Vec<String> = data.repository.referral_codes(&addr).await?.into_iter().map(|c| c.code).collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "address": addr,
+        "codes": codes,
+        "referee_count": referees.len(),
+        "referee_volume": total_volume,
+        "rebates_earned": rebates_earned,
+        "fee_bps": TRADING_FEE_BPS,
+        "referral_share_bps": REFERRAL_SHARE_BPS
+    })))
+}
+
+#[actix_web::get("/prices")]
+async fn get_prices(data: web::Data<AppState>) -> HttpResponse {
+    let prices = data.prices.all();
+    HttpResponse::Ok().json(serde_json::json!({
+        "total": prices.len(),
+        "prices": prices
     }))
 }
 
+/// Token `POST /prices` requires as `Authorization: Bearer <token>`, from
+/// `PRICE_ADMIN_TOKEN`. Without one the endpoint is disabled.
+struct PriceAdminToken(Option<String>);
+
+fn check_price_admin(token: &PriceAdminToken, req: &HttpRequest) -> Result<(), ApiError> {
+    let Some(expected) = &token.0 else {
+        return Err(ApiError::unauthorized("Price injection is disabled; set PRICE_ADMIN_TOKEN"));
+    };
+    let given = req
+        .headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // Compares every byte, so the time taken does not reveal a matching prefix.
+    let matches = given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0;
+    if !matches {
+        return Err(ApiError::unauthorized("Missing or invalid admin token"));
+    }
+    Ok(())
+}
+
+#[actix_web::post("/prices")]
+async fn post_prices(
+    data: web::Data<AppState>,
+    admin: web::Data<PriceAdminToken>,
+    http: HttpRequest,
+    req: web::Json<PostPricesRequest>,
+) -> Result<HttpResponse, ApiError> {
+    check_price_admin(&admin, &http)?;
+    let ticks = req.into_inner().validate(Local::now().timestamp())?;
+    let received = ticks.len();
+
+    let applied = apply_prices(&data, ticks).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "applied": applied,
+        "ignored": received - applied.len(),
+        "timestamp": Local::now().to_rfc3339()
+    })))
+}
+
+#[actix_web::get("/metrics")]
+async fn metrics(data: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let mut body = system_metrics(&data).await?;
+    body.insert("timestamp".to_string(), serde_json::json!(Local::now().to_rfc3339()));
+    Ok(HttpResponse::Ok().json(body))
+}
+
+#[actix_web::get("/ws")]
+async fn ws(data: web::Data<AppState>, req: HttpRequest, body: web::Payload) -> actix_web::Result<HttpResponse> {
+    let (response, session, stream) = actix_ws::handle(&req, body)?;
+    actix_web::rt::spawn(run_ws_session(data, session, stream));
+    Ok(response)
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    dotenv::dotenv().ok();
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
-    let app_state = web::Data::new(AppState {
-        positions: Mutex::new(HashMap::new()),
-        users: Mutex::new(HashMap::new()),
-    });
+    let repository: Box<dyn Repository> = match std::env::var("DATABASE_URL") {
+        Ok(url) => Box::new(PostgresRepository::connect(&url).await.map_err(std::io::Error::other)?),
+        Err(_) => {
+            log::warn!("DATABASE_URL not set; state is kept in memory and lost on restart");
+            Box::new(InMemoryRepository::default())
+        }
+    };
+    let app_state = web::Data::new(AppState { repository, prices: PriceCache::default(), events: Events::default() });
+    let price_admin = web::Data::new(PriceAdminToken(std::env::var("PRICE_ADMIN_TOKEN").ok().filter(|t| !t.is_empty())));
+    if price_admin.0.is_none() {
+        log::info!("PRICE_ADMIN_TOKEN not set; POST /prices is disabled");
+    }
+    tokio::spawn(run_metrics_updates(app_state.clone()));
+
+    for source in prices::from_env().map_err(std::io::Error::other)? {
+        log::info!("Reading prices from {}", source.name());
+        tokio::spawn(run_price_source(app_state.clone(), source));
+    }
+
+    let snapshot_interval = std::env::var("PNL_SNAPSHOT_INTERVAL").unwrap_or_else(|_| DEFAULT_SNAPSHOT_INTERVAL.to_string());
+    let snapshot_period = parse_interval(&snapshot_interval)
+        .ok_or_else(|| std::io::Error::other(format!("Invalid PNL_SNAPSHOT_INTERVAL: {}", snapshot_interval)))?;
+    tokio::spawn(run_pnl_snapshots(app_state.clone(), snapshot_period));
+
+    let liquidation_period = match std::env::var("LIQUIDATION_INTERVAL_SECS") {
+        Err(_) => DEFAULT_LIQUIDATION_INTERVAL_SECS,
+        Ok(v) => v.parse::<u64>().ok().filter(|&secs| secs > 0).ok_or_else(|| {
+            std::io::Error::other(format!("Invalid LIQUIDATION_INTERVAL_SECS: {}", v))
+        })?,
+    };
+    tokio::spawn(run_liquidations(app_state.clone(), liquidation_period));
 
     println!("🚀 Starting Position Management Backend v2.0");
     println!("📊 Dashboard: http://127.0.0.1:8080");
     println!("📈 Metrics: http://127.0.0.1:8080/metrics");
-    println!("✅ Features: Leverage tiers, PostgreSQL-ready, Margin calculations");
+    println!("✅ Features: Leverage tiers, PostgreSQL storage, Margin calculations");
 
     HttpServer::new(move || {
         App::new()
             .app_data(app_state.clone())
+            .app_data(price_admin.clone())
+            // Malformed bodies and query strings answer like any other ApiError.
+            .app_data(web::JsonConfig::default().error_handler(|e, _| ApiError::invalid_request(e.to_string()).into()))
+            .app_data(web::QueryConfig::default().error_handler(|e, _| ApiError::invalid_request(e.to_string()).into()))
             .wrap(Logger::default())
             .route("/", web::get().to(index))
             .service(actix_files::Files::new("/static", "./static"))
@@ -434,14 +1518,415 @@ async fn main() -> std::io::Result<()> {
             .service(initialize_user)
             .service(open_position)
             .service(get_position)
+            .service(position_history)
             .service(get_user)
+            .service(modify_position)
             .service(close_position)
+            .service(liquidate_position)
+            .service(close_all_positions)
             .service(list_positions)
             .service(list_users)
             .service(user_pnl)
+            .service(user_pnl_history)
+            .service(user_history)
+            .service(user_adl_queue)
+            .service(register_referral_code)
+            .service(register_referral)
+            .service(user_referees)
+            .service(user_rebates)
+            .service(get_prices)
+            .service(post_prices)
             .service(metrics)
+            .service(ws)
     })
     .bind("127.0.0.1:8080")?
     .run()
     .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::TimeRange;
+    use proptest::prelude::*;
+
+    /// Both truncations plus `f64` rounding, which grows with the price.
+    fn tolerance(entry_price: u64) -> f64 {
+        2.0 + entry_price as f64 * f64::EPSILON * 8.0
+    }
+
+    fn leverage_and_tier() -> impl Strategy<Value = (u16, LeverageTier)> {
+        (1u16..=1000, 0u64..=100_000).prop_filter_map("no tier", |(leverage, size)| {
+            get_leverage_tier(leverage, size)
+                .ok()
+                .filter(|tier| tier.maintenance_margin_rate * (leverage as f64) < 1.0)
+                .map(|tier| (leverage, tier))
+        })
+    }
+
+    /// In-memory state with no cached prices.
+    fn test_state() -> AppState {
+        AppState {
+            repository: Box::new(InMemoryRepository::default()),
+            prices: PriceCache::default(),
+            events: Events::default(),
+        }
+    }
+
+    /// An open 10x SOL-PERP long of 1,000 at 100, also used by the storage tests.
+    pub(crate) fn long_position(id: &str, owner: &str) -> Position {
+        Position {
+            id: id.to_string(),
+            owner: owner.to_string(),
+            sub_account_id: 0,
+            symbol: "SOL-PERP".to_string(),
+            side: 1,
+            size: 1_000,
+            entry_price: 100,
+            leverage: 10,
+            status: 1,
+            margin: 10_000,
+            unrealized_pnl: 0,
+            realized_pnl: 0,
+            liquidation_price: 92,
+            margin_ratio: 0.1,
+            opened_at: 0,
+            closed_at: 0,
+        }
+    }
+
+    fn snapshot(snapshot_time: i64, total_pnl: i64) -> PnlSnapshot {
+        PnlSnapshot {
+            owner: "alice".to_string(),
+            position_id: None,
+            snapshot_time,
+            unrealized_pnl: total_pnl,
+            realized_pnl: 0,
+            total_pnl,
+            margin_ratio: None,
+        }
+    }
+
+    #[test]
+    fn leverage_must_leave_margin_above_maintenance() {
+        let tier = get_leverage_tier(500, 1_000).unwrap();
+        assert_eq!(tier.maintenance_margin_rate, 0.0025);
+        assert!(calculate_liquidation_price_long(100_000, 399, tier.maintenance_margin_rate).unwrap() < 100_000);
+        for leverage in [400, 500] {
+            for result in [
+                calculate_liquidation_price_long(100_000, leverage, tier.maintenance_margin_rate),
+                calculate_liquidation_price_short(100_000, leverage, tier.maintenance_margin_rate),
+            ] {
+                assert_eq!(result.unwrap_err().code(), ErrorCode::InvalidLeverageValue as u32);
+            }
+        }
+    }
+
+    #[test]
+    fn pnl_snapshots_aggregate_open_and_closed_positions() {
+        let position = |id: &str, status: u8, unrealized_pnl: i64, realized_pnl: i64| Position {
+            status,
+            unrealized_pnl,
+            realized_pnl,
+            ..long_position(id, "alice")
+        };
+        let idle = User {
+            address: "idle".to_string(),
+            collateral: 0,
+            locked_collateral: 0,
+            sub_accounts: vec![0],
+            positions: vec![],
+            total_pnl: 0,
+            created_at: 0,
+        };
+        let positions = [
+            position("a", 1, 5_000, 0),
+            position("b", 1, -2_000, 0),
+            position("c", 2, 0, 7_000),
+        ];
+
+        let snapshots = pnl_snapshots(&[idle], &positions, 42);
+        assert_eq!(snapshots.len(), 4);
+        assert!(snapshots.iter().all(|s| s.snapshot_time == 42));
+
+        let a = snapshots.iter().find(|s| s.position_id.as_deref() == Some("a")).unwrap();
+        assert_eq!((a.unrealized_pnl, a.total_pnl, a.margin_ratio), (5_000, 5_000, Some(0.15)));
+
+        let alice = snapshots.iter().find(|s| s.owner == "alice" && s.position_id.is_none()).unwrap();
+        assert_eq!((alice.unrealized_pnl, alice.realized_pnl, alice.total_pnl), (3_000, 7_000, 10_000));
+        assert_eq!(alice.margin_ratio, Some(0.115));
+
+        let idle = snapshots.iter().find(|s| s.owner == "idle").unwrap();
+        assert_eq!((idle.total_pnl, idle.margin_ratio), (0, None));
+    }
+
+    #[test]
+    fn modify_position_values_mirror_the_program() {
+        let position = long_position("p", "alice");
+
+        let (grown, tier) = modify_position_values(&position, 1_000, 0).unwrap();
+        assert_eq!((grown.size, grown.margin, grown.leverage), (2_000, 10_000, 20));
+        assert_eq!(tier.max_leverage, 20);
+        assert_eq!(grown.liquidation_price, 97);
+        assert_eq!(grown.margin_ratio, 0.05);
+
+        let (topped_up, _) = modify_position_values(&position, 0, 10_000).unwrap();
+        assert_eq!((topped_up.leverage, topped_up.liquidation_price, topped_up.margin_ratio), (5, 82, 0.2));
+
+        // The 1000x tier allows up to 5k size, but its 0.1% maintenance rate caps leverage below 1000x,
+        // and 100x already caps size at 50k.
+        assert_eq!(modify_position_values(&position, 0, -9_899).unwrap().0.leverage, 990);
+        assert_eq!(
+            modify_position_values(&position, 0, -9_900).unwrap_err().code(),
+            ErrorCode::InvalidLeverageValue as u32
+        );
+        let large = Position { size: 60_000, margin: 6_000_000, ..position.clone() };
+        assert!(modify_position_values(&large, 0, -5_880_000).is_ok());
+        assert!(modify_position_values(&large, 0, -5_940_000).is_err());
+
+        assert!(modify_position_values(&position, -1_000, 0).is_err());
+        assert!(modify_position_values(&position, i64::MIN, 0).is_err());
+        assert_eq!(modify_position_values(&position, 0, -10_001).unwrap_err().code(), ErrorCode::CannotReduceMargin as u32);
+        assert_eq!(modify_position_values(&position, 0, -10_000).unwrap_err().code(), ErrorCode::CannotReduceMargin as u32);
+        let closed = Position { status: 2, ..position };
+        assert_eq!(modify_position_values(&closed, 1, 0).unwrap_err().code(), ErrorCode::PositionAlreadyClosed as u32);
+    }
+
+    #[test]
+    fn liquidation_reason_checks_price_and_margin_ratio() {
+        let long = long_position("p", "alice");
+        assert_eq!(liquidation_reason(&long, 93), None);
+        assert_eq!(liquidation_reason(&long, 92), Some("Mark price crossed liquidation price"));
+
+        // A stale liquidation price still liquidates once the ratio is below 2.5%.
+        let stale = Position { liquidation_price: 50, ..long.clone() };
+        assert_eq!(liquidation_reason(&stale, 93), None);
+        assert_eq!(liquidation_reason(&stale, 92), Some("Margin ratio below maintenance"));
+
+        let short = Position { side: 2, liquidation_price: 107, ..long.clone() };
+        assert_eq!(liquidation_reason(&short, 106), None);
+        assert_eq!(liquidation_reason(&short, 107), Some("Mark price crossed liquidation price"));
+
+        let closed = Position { status: 2, ..long.clone() };
+        assert_eq!(liquidation_reason(&closed, 1), None);
+
+        let gone = liquidated(&long, 9);
+        assert_eq!((gone.status, gone.realized_pnl, gone.closed_at), (3, -10_000, 9));
+    }
+
+    #[test]
+    fn slippage_is_checked_against_the_cached_mark() {
+        let prices = PriceCache::default();
+        let tick = |price, timestamp| PriceTick { symbol: "SOL-PERP".to_string(), price, timestamp };
+        assert!(check_mark_slippage(&prices, "SOL-PERP", None, None).is_ok());
+        assert_eq!(check_mark_slippage(&prices, "SOL-PERP", Some(100), None).unwrap_err().code(), 4002);
+
+        prices.update(vec![tick(100, 1)]);
+        assert!(check_mark_slippage(&prices, "SOL-PERP", Some(100), Some(100)).is_ok());
+
+        // Moving the mark past a bound fails the request.
+        prices.update(vec![tick(101, 2)]);
+        assert_eq!(check_mark_slippage(&prices, "SOL-PERP", Some(100), None).unwrap_err().code(), 4005);
+        assert!(check_mark_slippage(&prices, "SOL-PERP", None, Some(100)).is_ok());
+        prices.update(vec![tick(99, 3)]);
+        assert_eq!(check_mark_slippage(&prices, "SOL-PERP", None, Some(100)).unwrap_err().code(), 4005);
+    }
+
+    #[tokio::test]
+    async fn liquidation_scan_settles_liquidatable_positions() {
+        let data = test_state();
+        data.repository.initialize_user("alice", 0, 0).await.unwrap();
+        let safe = Position { side: 2, liquidation_price: 107, ..long_position("safe", "alice") };
+        data.repository.open_position(&long_position("risky", "alice")).await.unwrap();
+        data.repository.open_position(&safe).await.unwrap();
+
+        // Without a mark nothing is checked.
+        assert_eq!(run_liquidation_scan(&data).await.unwrap(), 0);
+
+        data.prices.update(vec![PriceTick { symbol: "SOL-PERP".to_string(), price: 91, timestamp: 0 }]);
+        assert_eq!(run_liquidation_scan(&data).await.unwrap(), 1);
+        assert_eq!(run_liquidation_scan(&data).await.unwrap(), 0);
+
+        let risky = data.repository.get_position("risky").await.unwrap().unwrap();
+        assert_eq!((risky.status, risky.realized_pnl), (3, -10_000));
+        let user = data.repository.get_user("alice").await.unwrap().unwrap();
+        assert_eq!((user.locked_collateral, user.total_pnl), (10_000, -10_000));
+
+        let history = data.repository.position_history("risky", TimeRange::default()).await.unwrap();
+        assert_eq!(history.last().unwrap().action, HistoryAction::Liquidated);
+        assert_eq!(history.last().unwrap().reason.as_deref(), Some("Mark price crossed liquidation price"));
+    }
+
+    #[tokio::test]
+    async fn close_settles_margin_and_pnl_once() {
+        let data = test_state();
+        data.repository.initialize_user("alice", 0, 0).await.unwrap();
+        data.repository.open_position(&long_position("sol", "alice")).await.unwrap();
+        data.prices.update(vec![PriceTick { symbol: "SOL-PERP".to_string(), price: 105, timestamp: 1 }]);
+        let req = ClosePositionRequest { position_id: "sol".to_string(), max_price: None, min_price: Some(106) };
+        assert_eq!(close_one(&data, &req).await.unwrap_err().code(), ErrorCode::SlippageExceeded as u32);
+
+        let req = ClosePositionRequest { min_price: Some(105), ..req };
+        let closed = close_one(&data, &req).await.unwrap();
+        assert_eq!((closed.status, closed.realized_pnl), (2, 5_000));
+        let user = data.repository.get_user("alice").await.unwrap().unwrap();
+        assert_eq!((user.locked_collateral, user.total_pnl), (0, 5_000));
+
+        assert_eq!(close_one(&data, &req).await.unwrap_err().code(), ErrorCode::PositionAlreadyClosed as u32);
+        let user = data.repository.get_user("alice").await.unwrap().unwrap();
+        assert_eq!((user.locked_collateral, user.total_pnl), (0, 5_000));
+    }
+
+    #[tokio::test]
+    async fn close_all_uses_cached_marks_for_every_symbol() {
+        let data = test_state();
+        data.repository.initialize_user("alice", 0, 0).await.unwrap();
+        data.repository.open_position(&long_position("sol", "alice")).await.unwrap();
+        data.repository.open_position(&Position { symbol: "BTC-PERP".to_string(), ..long_position("btc", "alice") }).await.unwrap();
+        let tick = |symbol: &str, price| PriceTick { symbol: symbol.to_string(), price, timestamp: 1 };
+        let no_bounds = HashMap::new();
+
+        // All or nothing: one symbol without a mark keeps every position open.
+        data.prices.update(vec![tick("SOL-PERP", 105)]);
+        let err = close_all(&data, "alice", None, &no_bounds).await.unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidPrice as u32);
+        assert_eq!(data.repository.get_position("sol").await.unwrap().unwrap().status, 1);
+
+        data.prices.update(vec![tick("BTC-PERP", 98)]);
+        let bounds = HashMap::from([("SOL-PERP".to_string(), PriceBounds { max_price: Some(104), min_price: None })]);
+        let err = close_all(&data, "alice", None, &bounds).await.unwrap_err();
+        assert_eq!(err.code(), ErrorCode::SlippageExceeded as u32);
+        assert_eq!(data.repository.get_position("btc").await.unwrap().unwrap().status, 1);
+
+        let mut closed = close_all(&data, "alice", None, &no_bounds).await.unwrap();
+        closed.sort_by(|a, b| a.0.id.cmp(&b.0.id));
+        let summary: Vec<_> = closed.iter().map(|(p, exit)| (p.id.as_str(), p.status, p.realized_pnl, *exit)).collect();
+        assert_eq!(summary, [("btc", 2, -2_000, 98), ("sol", 2, 5_000, 105)]);
+        let user = data.repository.get_user("alice").await.unwrap().unwrap();
+        assert_eq!((user.locked_collateral, user.total_pnl), (0, 3_000));
+        assert!(close_all(&data, "alice", None, &no_bounds).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn price_injection_requires_the_admin_token() {
+        let request = |header: Option<&str>| {
+            let mut req = actix_web::test::TestRequest::post().uri("/prices");
+            if let Some(value) = header {
+                req = req.insert_header((actix_web::http::header::AUTHORIZATION, value));
+            }
+            req.to_http_request()
+        };
+        let code = |result: Result<(), ApiError>| result.unwrap_err().code();
+        let unauthorized = api::ApiErrorCode::Unauthorized as u32;
+
+        let disabled = PriceAdminToken(None);
+        assert_eq!(code(check_price_admin(&disabled, &request(Some("Bearer secret")))), unauthorized);
+
+        let token = PriceAdminToken(Some("secret".to_string()));
+        assert!(check_price_admin(&token, &request(Some("Bearer secret"))).is_ok());
+        for header in [None, Some("secret"), Some("Bearer secre"), Some("Bearer secret2")] {
+            assert_eq!(code(check_price_admin(&token, &request(header))), unauthorized);
+        }
+    }
+
+    #[tokio::test]
+    async fn apply_prices_marks_open_positions_to_market() {
+        let data = test_state();
+        data.repository.initialize_user("alice", 0, 0).await.unwrap();
+        data.repository.open_position(&long_position("sol", "alice")).await.unwrap();
+        data.repository.open_position(&Position { symbol: "BTC-PERP".to_string(), ..long_position("btc", "alice") }).await.unwrap();
+
+        let tick = |price, timestamp| PriceTick { symbol: "SOL-PERP".to_string(), price, timestamp };
+        assert_eq!(apply_prices(&data, vec![tick(105, 10)]).await.unwrap().len(), 1);
+        let sol = data.repository.get_position("sol").await.unwrap().unwrap();
+        assert_eq!(sol.unrealized_pnl, 5_000);
+        assert_eq!(sol.margin_ratio, 0.15);
+        assert_eq!(data.repository.get_position("btc").await.unwrap().unwrap().unrealized_pnl, 0);
+
+        // Stale ticks are ignored and leave positions as they are.
+        assert!(apply_prices(&data, vec![tick(95, 9)]).await.unwrap().is_empty());
+        assert_eq!(data.prices.get("SOL-PERP").unwrap().price, 105);
+        assert_eq!(data.repository.get_position("sol").await.unwrap().unwrap().unrealized_pnl, 5_000);
+    }
+
+    #[tokio::test]
+    async fn subscribers_get_snapshots_then_deltas() {
+        let data = test_state();
+        data.repository.initialize_user("alice", 0, 0).await.unwrap();
+        data.repository.open_position(&long_position("sol", "alice")).await.unwrap();
+
+        let mut subscriptions = HashSet::new();
+        let request = r#"{"action": "subscribe", "channels": ["positions:alice", "prices:SOL-PERP", "liquidations"]}"#;
+        let replies = handle_ws_request(&data, &mut subscriptions, request).await;
+        // No snapshot for liquidations.
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[0]["channels"][2], "liquidations");
+        assert_eq!(replies[1]["data"]["positions"][0]["id"], "sol");
+        assert_eq!(replies[2]["data"], serde_json::Value::Null);
+        assert_eq!(handle_ws_request(&data, &mut subscriptions, r#"{"action": "subscribe", "channels": ["trades"]}"#).await[0]["type"], "error");
+
+        let mut events = data.events.subscribe();
+        let tick = |price| PriceTick { symbol: "SOL-PERP".to_string(), price, timestamp: 0 };
+        apply_prices(&data, vec![tick(105)]).await.unwrap();
+        assert_eq!(events.recv().await.unwrap(), Event::price(&tick(105)));
+        let mark = events.recv().await.unwrap();
+        assert_eq!(mark.kind, "mark");
+        assert_eq!(mark.data["changes"], serde_json::json!({"unrealized_pnl": 5_000, "margin_ratio": 0.15}));
+
+        apply_prices(&data, vec![tick(91)]).await.unwrap();
+        assert_eq!(run_liquidation_scan(&data).await.unwrap(), 1);
+        let kinds: Vec<&str> = std::iter::from_fn(|| events.try_recv().ok()).map(|e| e.kind).collect();
+        assert_eq!(kinds, vec!["price", "mark", "liquidation", "position", "account"]);
+    }
+
+    #[test]
+    fn downsample_keeps_the_last_snapshot_per_bucket() {
+        let series = downsample(vec![snapshot(0, 1), snapshot(3_599, 2), snapshot(3_600, 3), snapshot(9_000, 4)], 3_600);
+        let points: Vec<(i64, i64)> = series.iter().map(|s| (s.snapshot_time, s.total_pnl)).collect();
+        assert_eq!(points, vec![(3_599, 2), (3_600, 3), (9_000, 4)]);
+
+        assert_eq!(parse_interval("hourly"), Some(3_600));
+        assert_eq!(parse_interval("daily"), Some(86_400));
+        assert_eq!(parse_interval("900"), Some(900));
+        assert_eq!(parse_interval("0"), None);
+        assert_eq!(parse_interval("weekly"), None);
+    }
+
+    proptest! {
+        #[test]
+        fn liquidation_prices_bracket_the_entry(entry_price in 1u64..=u64::MAX / 4, (leverage, tier) in leverage_and_tier()) {
+            let rate = tier.maintenance_margin_rate;
+            let long = calculate_liquidation_price_long(entry_price, leverage, rate).unwrap();
+            let short = calculate_liquidation_price_short(entry_price, leverage, rate).unwrap();
+            prop_assert!(long <= entry_price, "long {} above entry {}", long, entry_price);
+            prop_assert!(short >= entry_price, "short {} below entry {}", short, entry_price);
+        }
+
+        #[test]
+        fn liquidation_prices_are_symmetric(entry_price in 1u64..=u64::MAX / 4, (leverage, tier) in leverage_and_tier()) {
+            let rate = tier.maintenance_margin_rate;
+            let long = calculate_liquidation_price_long(entry_price, leverage, rate).unwrap();
+            let short = calculate_liquidation_price_short(entry_price, leverage, rate).unwrap();
+            let distance_below = entry_price as f64 - long as f64;
+            let distance_above = short as f64 - entry_price as f64;
+            prop_assert!((distance_below - distance_above).abs() <= tolerance(entry_price));
+        }
+
+        #[test]
+        fn liquidation_prices_never_saturate(entry_price in any::<u64>(), leverage in any::<u16>(), rate in 0.0f64..1.0) {
+            for (side, result) in [
+                (1, calculate_liquidation_price_long(entry_price, leverage, rate)),
+                (2, calculate_liquidation_price_short(entry_price, leverage, rate)),
+            ] {
+                let exact = if side == 1 { 1.0 - 1.0 / leverage as f64 + rate } else { 1.0 + 1.0 / leverage as f64 - rate };
+                let exact = entry_price as f64 * exact;
+                match result {
+                    Ok(price) => prop_assert!((price as f64 - exact).abs() <= tolerance(entry_price)),
+                    Err(_) => prop_assert!(
+                        leverage == 0 || rate * leverage as f64 >= 1.0 || !(0.0..u64::MAX as f64).contains(&exact)
+                    ),
+                }
+            }
+        }
+    }
 }
\ No newline at end of file
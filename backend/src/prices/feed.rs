@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Local;
+use futures_util::StreamExt;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use super::{parse_prices, PriceSource, PriceTick};
+
+enum Transport {
+    /// GETs the URL every `interval`, the first time immediately.
+    Http { interval: Duration, polled: bool },
+    /// Reads each text message; reconnects on the next call after a failure.
+    WebSocket { stream: Option<Box<WebSocketStream<MaybeTlsStream<TcpStream>>>> },
+}
+
+/// A local price service, polled over `http(s)://` or streamed over `ws(s)://`.
+/// Responses and messages use the formats `parse_prices` accepts.
+pub struct FeedPriceSource {
+    url: String,
+    name: String,
+    transport: Transport,
+}
+
+impl FeedPriceSource {
+    pub fn new(url: String, interval: Duration) -> Result<Self, String> {
+        let transport = match url.split_once("://").map(|(scheme, _)| scheme) {
+            Some("http" | "https") => Transport::Http { interval, polled: false },
+            Some("ws" | "wss") => Transport::WebSocket { stream: None },
+            _ => return Err(format!("Unsupported price feed URL: {}", url)),
+        };
+        Ok(Self { name: format!("feed:{}", url), url, transport })
+    }
+}
+
+#[async_trait]
+impl PriceSource for FeedPriceSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn next_prices(&mut self) -> Result<Option<Vec<PriceTick>>, String> {
+        match &mut self.transport {
+            Transport::Http { interval, polled } => {
+                if *polled {
+                    tokio::time::sleep(*interval).await;
+                }
+                *polled = true;
+
+                let url = self.url.clone();
+                let body: serde_json::Value = tokio::task::spawn_blocking(move || {
+                    ureq::get(&url).call().map_err(|e| e.to_string())?.into_json().map_err(|e| e.to_string())
+                })
+                .await
+                .map_err(|e| e.to_string())??;
+                parse_prices(&body, Local::now().timestamp()).map(Some)
+            }
+            Transport::WebSocket { stream } => {
+                if stream.is_none() {
+                    let (connected, _) = tokio_tungstenite::connect_async(self.url.as_str())
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    *stream = Some(Box::new(connected));
+                }
+                let connection = stream.as_mut().expect("connected above");
+                loop {
+                    match connection.next().await {
+                        Some(Ok(Message::Text(text))) => {
+                            let value: serde_json::Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+                            return parse_prices(&value, Local::now().timestamp()).map(Some);
+                        }
+                        Some(Ok(Message::Close(_))) | None => {
+                            *stream = None;
+                            return Err("connection closed".to_string());
+                        }
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => {
+                            *stream = None;
+                            return Err(e.to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Local;
+
+use super::{parse_prices, PriceSource, PriceTick};
+
+// Stands in for a missing timestamp while parsing.
+const UNTIMED: i64 = i64::MIN;
+
+/// Replays a file with one price update per line, in any format `parse_prices`
+/// accepts. Consecutive lines with the same timestamp form one batch, batches
+/// are spaced by the difference between their timestamps, and ticks are
+/// re-stamped with the time they are replayed. Lines without a timestamp are
+/// applied without waiting, so a file of them is a static price list.
+pub struct FilePriceSource {
+    name: String,
+    batches: VecDeque<(Option<i64>, Vec<PriceTick>)>,
+    last_timestamp: Option<i64>,
+}
+
+impl FilePriceSource {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(path, &contents)
+    }
+
+    fn parse(path: &str, contents: &str) -> Result<Self, String> {
+        let mut batches: VecDeque<(Option<i64>, Vec<PriceTick>)> = VecDeque::new();
+        for (index, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let value: serde_json::Value =
+                serde_json::from_str(line).map_err(|e| format!("{}:{}: {}", path, index + 1, e))?;
+            for tick in parse_prices(&value, UNTIMED).map_err(|e| format!("{}:{}: {}", path, index + 1, e))? {
+                let timestamp = Some(tick.timestamp).filter(|&t| t != UNTIMED);
+                match batches.back_mut() {
+                    Some((last, ticks)) if *last == timestamp => ticks.push(tick),
+                    _ => batches.push_back((timestamp, vec![tick])),
+                }
+            }
+        }
+        Ok(Self { name: format!("file:{}", path), batches, last_timestamp: None })
+    }
+}
+
+#[async_trait]
+impl PriceSource for FilePriceSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn next_prices(&mut self) -> Result<Option<Vec<PriceTick>>, String> {
+        let Some((timestamp, ticks)) = self.batches.pop_front() else {
+            return Ok(None);
+        };
+        if let Some(timestamp) = timestamp {
+            if let Some(last) = self.last_timestamp {
+                let wait = timestamp.saturating_sub(last).max(0) as u64;
+                tokio::time::sleep(Duration::from_secs(wait)).await;
+            }
+            self.last_timestamp = Some(timestamp);
+        }
+
+        let now = Local::now().timestamp();
+        Ok(Some(ticks.into_iter().map(|tick| PriceTick { timestamp: now, ..tick }).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn replays_batches_in_order() {
+        let contents = r#"{"symbol": "SOL-PERP", "price": 100, "timestamp": 10}
+{"symbol": "BTC-PERP", "price": 50000, "timestamp": 10}
+
+{"SOL-PERP": 90}
+[{"symbol": "SOL-PERP", "price": 95, "timestamp": 70}]"#;
+        let mut source = FilePriceSource::parse("prices.jsonl", contents).unwrap();
+
+        let started = tokio::time::Instant::now();
+        let first = source.next_prices().await.unwrap().unwrap();
+        assert_eq!(first.iter().map(|t| t.price).collect::<Vec<_>>(), vec![100, 50_000]);
+        assert_eq!(source.next_prices().await.unwrap().unwrap()[0].price, 90);
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert_eq!(source.next_prices().await.unwrap().unwrap()[0].price, 95);
+        assert_eq!(started.elapsed(), Duration::from_secs(60));
+        assert!(source.next_prices().await.unwrap().is_none());
+
+        let Err(error) = FilePriceSource::parse("bad.jsonl", "{") else { panic!("expected a parse error") };
+        assert!(error.starts_with("bad.jsonl:1:"));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,166 @@
+//! Mark price ingestion. Each `PriceSource` yields batches of ticks, which the
+//! backend writes to the `PriceCache` and uses to mark open positions to
+//! market. Sources are enabled by environment variables; see `from_env`.
+
+mod feed;
+mod file;
+mod oracle;
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub use feed::FeedPriceSource;
+pub use file::FilePriceSource;
+pub use oracle::OraclePriceSource;
+
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceTick {
+    pub symbol: String,
+    pub price: u64,
+    pub timestamp: i64,
+}
+
+#[async_trait]
+pub trait PriceSource: Send {
+    fn name(&self) -> &str;
+
+    /// Waits for the next batch of prices. `None` means the source is exhausted.
+    async fn next_prices(&mut self) -> Result<Option<Vec<PriceTick>>, String>;
+}
+
+/// Latest tick per symbol.
+#[derive(Default)]
+pub struct PriceCache {
+    ticks: RwLock<HashMap<String, PriceTick>>,
+}
+
+impl PriceCache {
+    /// Stores each tick unless the cached one is newer. Returns the stored ticks.
+    pub fn update(&self, ticks: Vec<PriceTick>) -> Vec<PriceTick> {
+        let mut cached = self.ticks.write().unwrap();
+        ticks
+            .into_iter()
+            .filter(|tick| {
+                if cached.get(&tick.symbol).is_some_and(|c| c.timestamp > tick.timestamp) {
+                    return false;
+                }
+                cached.insert(tick.symbol.clone(), tick.clone());
+                true
+            })
+            .collect()
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<PriceTick> {
+        self.ticks.read().unwrap().get(symbol).cloned()
+    }
+
+    pub fn all(&self) -> Vec<PriceTick> {
+        let mut ticks: Vec<PriceTick> = self.ticks.read().unwrap().values().cloned().collect();
+        ticks.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        ticks
+    }
+
+    /// Price per symbol.
+    pub fn prices(&self) -> HashMap<String, u64> {
+        self.ticks.read().unwrap().iter().map(|(symbol, tick)| (symbol.clone(), tick.price)).collect()
+    }
+}
+
+/// Reads ticks from `{"SYMBOL": price, ...}`, a single `{"symbol", "price",
+/// "timestamp"}` object or an array of them. Missing timestamps become `now`.
+pub fn parse_prices(value: &serde_json::Value, now: i64) -> Result<Vec<PriceTick>, String> {
+    let tick = |v: &serde_json::Value| -> Result<PriceTick, String> {
+        let symbol = v.get("symbol").and_then(|s| s.as_str()).ok_or("Missing symbol")?;
+        let price = v.get("price").and_then(|p| p.as_u64()).ok_or("Invalid price")?;
+        let timestamp = match v.get("timestamp") {
+            None | Some(serde_json::Value::Null) => now,
+            Some(t) => t.as_i64().ok_or("Invalid timestamp")?,
+        };
+        Ok(PriceTick { symbol: symbol.to_string(), price, timestamp })
+    };
+
+    match value {
+        serde_json::Value::Array(items) => items.iter().map(tick).collect(),
+        serde_json::Value::Object(map) if map.contains_key("symbol") => Ok(vec![tick(value)?]),
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(symbol, price)| {
+                let price = price.as_u64().ok_or(format!("Invalid price for {}", symbol))?;
+                Ok(PriceTick { symbol: symbol.clone(), price, timestamp: now })
+            })
+            .collect(),
+        _ => Err("Expected a price object or array".to_string()),
+    }
+}
+
+/// Sources configured by `PRICE_FILE` (replay file), `PRICE_FEED_URL` (http(s)
+/// polling or ws(s) stream) and `PRICE_ORACLE_RPC_URL` with
+/// `PRICE_ORACLE_SYMBOLS` (on-chain `Market` accounts). Polling sources wait
+/// `PRICE_POLL_INTERVAL_SECS` between reads.
+pub fn from_env() -> Result<Vec<Box<dyn PriceSource>>, String> {
+    let poll_interval = match std::env::var("PRICE_POLL_INTERVAL_SECS") {
+        Err(_) => DEFAULT_POLL_INTERVAL_SECS,
+        Ok(v) => v
+            .parse::<u64>()
+            .ok()
+            .filter(|&secs| secs > 0)
+            .ok_or(format!("Invalid PRICE_POLL_INTERVAL_SECS: {}", v))?,
+    };
+    let poll_interval = Duration::from_secs(poll_interval);
+
+    let mut sources: Vec<Box<dyn PriceSource>> = Vec::new();
+    if let Ok(path) = std::env::var("PRICE_FILE") {
+        sources.push(Box::new(FilePriceSource::open(&path)?));
+    }
+    if let Ok(url) = std::env::var("PRICE_FEED_URL") {
+        sources.push(Box::new(FeedPriceSource::new(url, poll_interval)?));
+    }
+    if let Ok(rpc_url) = std::env::var("PRICE_ORACLE_RPC_URL") {
+        let symbols = std::env::var("PRICE_ORACLE_SYMBOLS").map_err(|_| "PRICE_ORACLE_SYMBOLS not set")?;
+        let symbols = symbols.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect();
+        sources.push(Box::new(OraclePriceSource::new(rpc_url, symbols, poll_interval)));
+    }
+    Ok(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_keeps_the_newest_tick() {
+        let cache = PriceCache::default();
+        let tick = |symbol: &str, price, timestamp| PriceTick { symbol: symbol.to_string(), price, timestamp };
+
+        assert_eq!(cache.update(vec![tick("SOL-PERP", 100, 10), tick("BTC-PERP", 50_000, 10)]).len(), 2);
+        assert!(cache.update(vec![tick("SOL-PERP", 90, 9)]).is_empty());
+        assert_eq!(cache.update(vec![tick("SOL-PERP", 110, 10)]), vec![tick("SOL-PERP", 110, 10)]);
+
+        assert_eq!(cache.get("SOL-PERP").unwrap().price, 110);
+        assert_eq!(cache.all().iter().map(|t| t.symbol.as_str()).collect::<Vec<_>>(), vec!["BTC-PERP", "SOL-PERP"]);
+        assert_eq!(cache.prices()["BTC-PERP"], 50_000);
+    }
+
+    #[test]
+    fn parses_every_price_format() {
+        let map = parse_prices(&serde_json::json!({"SOL-PERP": 100}), 7).unwrap();
+        assert_eq!(map, vec![PriceTick { symbol: "SOL-PERP".to_string(), price: 100, timestamp: 7 }]);
+
+        let one = parse_prices(&serde_json::json!({"symbol": "SOL-PERP", "price": 101, "timestamp": 3}), 7).unwrap();
+        assert_eq!((one[0].price, one[0].timestamp), (101, 3));
+
+        let many = parse_prices(&serde_json::json!([{"symbol": "A", "price": 1}, {"symbol": "B", "price": 2}]), 7).unwrap();
+        assert_eq!(many.len(), 2);
+        assert_eq!(many[1].timestamp, 7);
+
+        assert!(parse_prices(&serde_json::json!({"SOL-PERP": -1}), 7).is_err());
+        assert!(parse_prices(&serde_json::json!([{"symbol": "A"}]), 7).is_err());
+        assert!(parse_prices(&serde_json::json!(5), 7).is_err());
+    }
+}
\ No newline at end of file
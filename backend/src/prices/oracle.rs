@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use position_management_client::state::Market;
+use position_management_client::{accounts, pda};
+
+use super::{PriceSource, PriceTick};
+
+/// Reads `mark_price` from the program's `Market` account of each symbol over
+/// JSON-RPC. Markets without a mark price yet are skipped.
+pub struct OraclePriceSource {
+    rpc_url: String,
+    symbols: Vec<String>,
+    interval: Duration,
+    polled: bool,
+    name: String,
+}
+
+impl OraclePriceSource {
+    pub fn new(rpc_url: String, symbols: Vec<String>, interval: Duration) -> Self {
+        Self { name: format!("oracle:{}", rpc_url), rpc_url, symbols, interval, polled: false }
+    }
+}
+
+/// Blocking; run off the async runtime.
+fn read_markets(rpc_url: &str, symbols: &[String]) -> Result<Vec<PriceTick>, String> {
+    let addresses: Vec<String> = symbols.iter().map(|symbol| pda::market(symbol).to_string()).collect();
+    let response: serde_json::Value = ureq::post(rpc_url)
+        .send_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "getMultipleAccounts",
+            "params": [addresses, {"encoding": "base64", "commitment": "confirmed"}]
+        }))
+        .map_err(|e| e.to_string())?
+        .into_json()
+        .map_err(|e| e.to_string())?;
+    if let Some(error) = response.get("error") {
+        return Err(format!("getMultipleAccounts failed: {}", error["message"]));
+    }
+
+    let accounts_info = response["result"]["value"].as_array().ok_or("getMultipleAccounts returned no accounts")?;
+    let mut ticks = Vec::new();
+    for (symbol, account) in symbols.iter().zip(accounts_info) {
+        if account.is_null() {
+            continue;
+        }
+        let encoded = account["data"][0].as_str().ok_or("account data is not base64")?;
+        let data = BASE64.decode(encoded).map_err(|e| e.to_string())?;
+        let market: Market = accounts::decode(&data).map_err(|e| format!("{}: {}", symbol, e))?;
+        if market.mark_price > 0 {
+            ticks.push(PriceTick { symbol: symbol.clone(), price: market.mark_price, timestamp: market.last_price_update });
+        }
+    }
+    Ok(ticks)
+}
+
+#[async_trait]
+impl PriceSource for OraclePriceSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn next_prices(&mut self) -> Result<Option<Vec<PriceTick>>, String> {
+        if self.polled {
+            tokio::time::sleep(self.interval).await;
+        }
+        self.polled = true;
+
+        let rpc_url = self.rpc_url.clone();
+        let symbols = self.symbols.clone();
+        tokio::task::spawn_blocking(move || read_markets(&rpc_url, &symbols))
+            .await
+            .map_err(|e| e.to_string())?
+            .map(Some)
+    }
+}
\ No newline at end of file
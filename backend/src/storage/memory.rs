@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use super::{apply_margin_change, history_entry, is_unchanged, Change, Repository, StorageResult, TimeRange};
+use crate::{HistoryAction, PnlSnapshot, Position, PositionHistory, Referral, ReferralCode, User};
+
+/// Process-local state; everything is lost on restart.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    users: Mutex<HashMap<String, User>>,
+    positions: Mutex<HashMap<String, Position>>,
+    history: Mutex<Vec<PositionHistory>>,
+    pnl_snapshots: Mutex<Vec<PnlSnapshot>>,
+    referral_codes: Mutex<HashMap<String, ReferralCode>>,
+    referrals: Mutex<HashMap<String, Referral>>,
+}
+
+impl InMemoryRepository {
+    /// Matching entries by timestamp, in insertion order within one.
+    fn history_where(&self, predicate: impl Fn(&PositionHistory) -> bool) -> Vec<PositionHistory> {
+        let mut entries: Vec<PositionHistory> =
+            self.history.lock().unwrap().iter().filter(|e| predicate(e)).cloned().collect();
+        entries.sort_by_key(|e| e.created_at);
+        entries
+    }
+}
+
+#[async_trait]
+impl Repository for InMemoryRepository {
+    async fn get_user(&self, address: &str) -> StorageResult<Option<User>> {
+        Ok(self.users.lock().unwrap().get(address).cloned())
+    }
+
+    async fn list_users(&self) -> StorageResult<Vec<User>> {
+        Ok(self.users.lock().unwrap().values().cloned().collect())
+    }
+
+    async fn initialize_user(&self, address: &str, sub_account_id: u16, created_at: i64) -> StorageResult<bool> {
+        let mut users = self.users.lock().unwrap();
+        match users.get_mut(address) {
+            Some(user) => {
+                if user.sub_accounts.contains(&sub_account_id) {
+                    return Ok(false);
+                }
+                user.sub_accounts.push(sub_account_id);
+                user.sub_accounts.sort_unstable();
+            }
+            None => {
+                let user = User {
+                    address: address.to_string(),
+                    collateral: 0,
+                    locked_collateral: 0,
+                    sub_accounts: vec![sub_account_id],
+                    positions: vec![],
+                    total_pnl: 0,
+                    created_at,
+                };
+                users.insert(address.to_string(), user);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn get_position(&self, id: &str) -> StorageResult<Option<Position>> {
+        Ok(self.positions.lock().unwrap().get(id).cloned())
+    }
+
+    async fn list_positions(&self) -> StorageResult<Vec<Position>> {
+        Ok(self.positions.lock().unwrap().values().cloned().collect())
+    }
+
+    async fn user_positions(&self, owner: &str) -> StorageResult<Vec<Position>> {
+        let mut positions: Vec<Position> = self
+            .positions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|p| p.owner == owner)
+            .cloned()
+            .collect();
+        positions.sort_by(|a, b| (a.opened_at, &a.id).cmp(&(b.opened_at, &b.id)));
+        Ok(positions)
+    }
+
+    async fn open_position(&self, position: &Position) -> StorageResult<bool> {
+        let mut users = self.users.lock().unwrap();
+        let Some(user) = users.get_mut(&position.owner) else {
+            return Ok(false);
+        };
+        user.positions.push(position.id.clone());
+        user.locked_collateral = user.locked_collateral.saturating_add(position.margin);
+
+        self.positions.lock().unwrap().insert(position.id.clone(), position.clone());
+        let change = Change { action: HistoryAction::Opened, reason: None, at: position.opened_at };
+        self.history.lock().unwrap().push(history_entry(None, position, &change));
+        Ok(true)
+    }
+
+    async fn modify_position(&self, old: &Position, new: &Position, change: &Change<'_>) -> StorageResult<bool> {
+        let mut users = self.users.lock().unwrap();
+        let mut stored = self.positions.lock().unwrap();
+        let Some(current) = stored.get_mut(&new.id).filter(|p| is_unchanged(p, old)) else {
+            return Ok(false);
+        };
+        self.history.lock().unwrap().push(history_entry(Some(current), new, change));
+        *current = new.clone();
+
+        if let Some(user) = users.get_mut(&new.owner) {
+            user.locked_collateral = apply_margin_change(user.locked_collateral, old, new);
+        }
+        Ok(true)
+    }
+
+    async fn mark_positions(&self, positions: &[Position]) -> StorageResult<()> {
+        let mut stored = self.positions.lock().unwrap();
+        for position in positions {
+            if let Some(current) = stored.get_mut(&position.id).filter(|p| p.status == 1) {
+                current.unrealized_pnl = position.unrealized_pnl;
+                current.margin_ratio = position.margin_ratio;
+            }
+        }
+        Ok(())
+    }
+
+    async fn settle_positions(&self, owner: &str, positions: &[Position], change: &Change<'_>) -> StorageResult<bool> {
+        let mut users = self.users.lock().unwrap();
+        let mut stored = self.positions.lock().unwrap();
+        let mut history = self.history.lock().unwrap();
+
+        if !positions.iter().all(|p| stored.get(&p.id).is_some_and(|current| is_unchanged(current, p))) {
+            return Ok(false);
+        }
+
+        let mut released_margin: u64 = 0;
+        let mut realized_pnl: i64 = 0;
+        for position in positions {
+            let current = stored.get_mut(&position.id).expect("checked above");
+            let settled = Position {
+                status: position.status,
+                unrealized_pnl: position.unrealized_pnl,
+                realized_pnl: position.realized_pnl,
+                closed_at: position.closed_at,
+                ..current.clone()
+            };
+            history.push(history_entry(Some(current), &settled, change));
+            released_margin = released_margin.saturating_add(current.margin);
+            realized_pnl = realized_pnl.saturating_add(position.realized_pnl);
+            *current = settled;
+        }
+
+        if let Some(user) = users.get_mut(owner) {
+            user.locked_collateral = user.locked_collateral.saturating_sub(released_margin);
+            user.total_pnl = user.total_pnl.saturating_add(realized_pnl);
+        }
+        Ok(true)
+    }
+
+    async fn position_history(&self, position_id: &str, range: TimeRange) -> StorageResult<Vec<PositionHistory>> {
+        Ok(self.history_where(|e| e.position_id == position_id && range.contains(e.created_at)))
+    }
+
+    async fn user_history(&self, owner: &str, range: TimeRange) -> StorageResult<Vec<PositionHistory>> {
+        Ok(self.history_where(|e| e.owner == owner && range.contains(e.created_at)))
+    }
+
+    async fn insert_pnl_snapshots(&self, snapshots: &[PnlSnapshot]) -> StorageResult<()> {
+        self.pnl_snapshots.lock().unwrap().extend_from_slice(snapshots);
+        Ok(())
+    }
+
+    async fn pnl_snapshots(&self, owner: &str, range: TimeRange) -> StorageResult<Vec<PnlSnapshot>> {
+        let mut snapshots: Vec<PnlSnapshot> = self
+            .pnl_snapshots
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|s| s.owner == owner && s.position_id.is_none() && range.contains(s.snapshot_time))
+            .cloned()
+            .collect();
+        snapshots.sort_by_key(|s| s.snapshot_time);
+        Ok(snapshots)
+    }
+
+    async fn insert_referral_code(&self, code: &ReferralCode) -> StorageResult<bool> {
+        let mut codes = self.referral_codes.lock().unwrap();
+        if codes.contains_key(&code.code) {
+            return Ok(false);
+        }
+        codes.insert(code.code.clone(), code.clone());
+        Ok(true)
+    }
+
+    async fn get_referral_code(&self, code: &str) -> StorageResult<Option<ReferralCode>> {
+        Ok(self.referral_codes.lock().unwrap().get(code).cloned())
+    }
+
+    async fn referral_codes(&self, referrer: &str) -> StorageResult<Vec<ReferralCode>> {
+        Ok(self.referral_codes.lock().unwrap().values().filter(|c| c.referrer == referrer).cloned().collect())
+    }
+
+    async fn insert_referral(&self, referral: &Referral) -> StorageResult<bool> {
+        let mut referrals = self.referrals.lock().unwrap();
+        if referrals.contains_key(&referral.referee) {
+            return Ok(false);
+        }
+        referrals.insert(referral.referee.clone(), referral.clone());
+        Ok(true)
+    }
+
+    async fn referrals(&self, referrer: &str) -> StorageResult<Vec<Referral>> {
+        Ok(self.referrals.lock().unwrap().values().filter(|r| r.referrer == referrer).cloned().collect())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,370 @@
+//! Persistence for users, positions, their audit trail, PnL snapshots and
+//! referrals.
+//! `DATABASE_URL` selects the Postgres backend, which runs `migrations/` on
+//! startup; without it state is kept in memory and lost on restart.
+
+mod memory;
+mod postgres;
+
+use std::fmt;
+
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::{HistoryAction, PnlSnapshot, Position, PositionHistory, Referral, ReferralCode, User};
+
+pub use memory::InMemoryRepository;
+pub use postgres::PostgresRepository;
+
+#[derive(Debug)]
+pub enum StorageError {
+    Database(sqlx::Error),
+    Migration(sqlx::migrate::MigrateError),
+    /// An amount in the named column does not fit the other side's type.
+    OutOfRange(&'static str),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Database(e) => write!(f, "database error: {}", e),
+            StorageError::Migration(e) => write!(f, "migration error: {}", e),
+            StorageError::OutOfRange(column) => write!(f, "{} out of range", column),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<sqlx::Error> for StorageError {
+    fn from(e: sqlx::Error) -> Self {
+        StorageError::Database(e)
+    }
+}
+
+impl From<sqlx::migrate::MigrateError> for StorageError {
+    fn from(e: sqlx::migrate::MigrateError) -> Self {
+        StorageError::Migration(e)
+    }
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+/// Why a position is written, recorded in its history. `at` is a unix timestamp.
+pub struct Change<'a> {
+    pub action: HistoryAction,
+    pub reason: Option<&'a str>,
+    pub at: i64,
+}
+
+/// Inclusive bounds on unix timestamps; `None` is unbounded.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TimeRange {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl TimeRange {
+    pub fn contains(&self, t: i64) -> bool {
+        self.from.is_none_or(|from| t >= from) && self.to.is_none_or(|to| t <= to)
+    }
+}
+
+/// Whether `stored` is still the open position `expected` was read as.
+fn is_unchanged(stored: &Position, expected: &Position) -> bool {
+    stored.status == 1 && stored.size == expected.size && stored.margin == expected.margin
+}
+
+/// `locked_collateral` after `old`'s margin is replaced by `new`'s.
+fn apply_margin_change(locked_collateral: u64, old: &Position, new: &Position) -> u64 {
+    if new.margin >= old.margin {
+        locked_collateral.saturating_add(new.margin - old.margin)
+    } else {
+        locked_collateral.saturating_sub(old.margin - new.margin)
+    }
+}
+
+/// The position's serialized fields by name.
+fn position_map(position: &Position) -> serde_json::Map<String, serde_json::Value> {
+    match serde_json::to_value(position) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    }
+}
+
+/// The fields that differ between `old` and `new`, with their old and new values.
+pub fn changed_fields(
+    old: &Position,
+    new: &Position,
+) -> (serde_json::Map<String, serde_json::Value>, serde_json::Map<String, serde_json::Value>) {
+    let (old_map, new_map) = (position_map(old), position_map(new));
+    let changed: Vec<&String> = new_map.keys().filter(|k| old_map.get(*k) != new_map.get(*k)).collect();
+    let pick = |map: &serde_json::Map<String, serde_json::Value>| {
+        changed.iter().filter_map(|k| map.get(*k).map(|v| ((*k).clone(), v.clone()))).collect()
+    };
+    (pick(&old_map), pick(&new_map))
+}
+
+/// Builds the history entry for writing `new` over `old`, keeping only the
+/// fields that differ on each side.
+fn history_entry(old: Option<&Position>, new: &Position, change: &Change) -> PositionHistory {
+    let (old_values, new_values) = match old {
+        None => (None, position_map(new)),
+        Some(old) => {
+            let (old_values, new_values) = changed_fields(old, new);
+            (Some(old_values), new_values)
+        }
+    };
+
+    PositionHistory {
+        id: Uuid::new_v4().to_string(),
+        position_id: new.id.clone(),
+        owner: new.owner.clone(),
+        action: change.action,
+        old_values: old_values.map(serde_json::Value::Object),
+        new_values: Some(serde_json::Value::Object(new_values)),
+        reason: change.reason.map(str::to_string),
+        created_at: change.at,
+    }
+}
+
+/// Operations that read and then write do so atomically, so concurrent requests
+/// cannot register a code twice or release the same margin twice.
+#[async_trait]
+pub trait Repository: Send + Sync {
+    async fn get_user(&self, address: &str) -> StorageResult<Option<User>>;
+
+    async fn list_users(&self) -> StorageResult<Vec<User>>;
+
+    /// Creates the user with `sub_account_id`, or adds it to an existing user.
+    /// Returns false if the sub-account already exists.
+    async fn initialize_user(&self, address: &str, sub_account_id: u16, created_at: i64) -> StorageResult<bool>;
+
+    async fn get_position(&self, id: &str) -> StorageResult<Option<Position>>;
+
+    async fn list_positions(&self) -> StorageResult<Vec<Position>>;
+
+    async fn user_positions(&self, owner: &str) -> StorageResult<Vec<Position>>;
+
+    /// Stores a new position and locks its margin on the owner. Recorded as
+    /// `OPENED` at `opened_at`. Returns false, storing nothing, if the owner is
+    /// not registered.
+    async fn open_position(&self, position: &Position) -> StorageResult<bool>;
+
+    /// Writes `new` over `old` and moves the margin difference into or out of
+    /// the owner's `locked_collateral`, if registered. Returns false, changing
+    /// nothing, unless the stored position is still open with `old`'s size and
+    /// margin.
+    async fn modify_position(&self, old: &Position, new: &Position, change: &Change<'_>) -> StorageResult<bool>;
+
+    /// Updates the mark-to-market fields, `unrealized_pnl` and `margin_ratio`,
+    /// of those `positions` still open. Not recorded in the history.
+    async fn mark_positions(&self, positions: &[Position]) -> StorageResult<()>;
+
+    /// Stores the status, PnL and `closed_at` of `positions`, releasing their
+    /// stored margin and adding their realized PnL to `owner`, and records
+    /// each. Returns false, changing nothing, unless every stored position is
+    /// still open with the size and margin it was settled from.
+    async fn settle_positions(&self, owner: &str, positions: &[Position], change: &Change<'_>) -> StorageResult<bool>;
+
+    /// Entries for one position, oldest first.
+    async fn position_history(&self, position_id: &str, range: TimeRange) -> StorageResult<Vec<PositionHistory>>;
+
+    /// Entries for every position of `owner`, oldest first.
+    async fn user_history(&self, owner: &str, range: TimeRange) -> StorageResult<Vec<PositionHistory>>;
+
+    async fn insert_pnl_snapshots(&self, snapshots: &[PnlSnapshot]) -> StorageResult<()>;
+
+    /// Owner-level snapshots (without `position_id`) of `owner`, oldest first.
+    async fn pnl_snapshots(&self, owner: &str, range: TimeRange) -> StorageResult<Vec<PnlSnapshot>>;
+
+    /// Returns false if the code is already registered.
+    async fn insert_referral_code(&self, code: &ReferralCode) -> StorageResult<bool>;
+
+    async fn get_referral_code(&self, code: &str) -> StorageResult<Option<ReferralCode>>;
+
+    async fn referral_codes(&self, referrer: &str) -> StorageResult<Vec<ReferralCode>>;
+
+    /// Returns false if the referee is already registered.
+    async fn insert_referral(&self, referral: &Referral) -> StorageResult<bool>;
+
+    async fn referrals(&self, referrer: &str) -> StorageResult<Vec<Referral>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::long_position;
+
+    fn position(owner: &str, margin: u64, opened_at: i64) -> Position {
+        Position { margin, opened_at, ..long_position(&format!("{}-{}", owner, Uuid::new_v4()), owner) }
+    }
+
+    /// Behaviour every backend must share. Addresses are unique per run, so a
+    /// shared database can be reused.
+    async fn exercise(repo: &dyn Repository) {
+        let owner = Uuid::new_v4().to_string();
+
+        assert!(repo.get_user(&owner).await.unwrap().is_none());
+        assert!(repo.initialize_user(&owner, 0, 1_700_000_000).await.unwrap());
+        assert!(repo.initialize_user(&owner, 2, 1_700_000_100).await.unwrap());
+        assert!(!repo.initialize_user(&owner, 2, 1_700_000_200).await.unwrap());
+
+        let first = position(&owner, 100, 1);
+        let second = position(&owner, 50, 2);
+        assert!(repo.open_position(&first).await.unwrap());
+        assert!(repo.open_position(&second).await.unwrap());
+
+        let user = repo.get_user(&owner).await.unwrap().unwrap();
+        assert_eq!(user.sub_accounts, vec![0, 2]);
+        assert_eq!(user.created_at, 1_700_000_000);
+        assert_eq!(user.locked_collateral, 150);
+        assert_eq!(user.positions, vec![first.id.clone(), second.id.clone()]);
+        assert!(repo.list_users().await.unwrap().iter().any(|u| u.address == owner));
+
+        let mut closed = first.clone();
+        closed.status = 2;
+        closed.realized_pnl = -30;
+        closed.closed_at = 3;
+        let close = Change { action: HistoryAction::Closed, reason: Some("test"), at: 3 };
+        assert!(repo.settle_positions(&owner, &[closed.clone()], &close).await.unwrap());
+        // Already closed, so settling again releases and records nothing.
+        assert!(!repo.settle_positions(&owner, &[closed.clone()], &close).await.unwrap());
+
+        let user = repo.get_user(&owner).await.unwrap().unwrap();
+        assert_eq!((user.locked_collateral, user.total_pnl), (50, -30));
+        let stored = repo.get_position(&first.id).await.unwrap().unwrap();
+        assert_eq!((stored.status, stored.realized_pnl, stored.closed_at), (2, -30, 3));
+
+        // Marking touches only open positions and leaves no history.
+        let mut marked = second.clone();
+        marked.unrealized_pnl = 3;
+        marked.margin_ratio = 0.25;
+        repo.mark_positions(&[marked, Position { unrealized_pnl: 9, ..closed.clone() }]).await.unwrap();
+        let stored = repo.get_position(&second.id).await.unwrap().unwrap();
+        assert_eq!((stored.unrealized_pnl, stored.margin_ratio), (3, 0.25));
+        assert_eq!(repo.get_position(&first.id).await.unwrap().unwrap().unrealized_pnl, first.unrealized_pnl);
+        assert_eq!(repo.position_history(&second.id, TimeRange::default()).await.unwrap().len(), 1);
+
+        let mut updated = second.clone();
+        updated.unrealized_pnl = 7;
+        updated.margin_ratio = 0.5;
+        let modify = Change { action: HistoryAction::Modified, reason: None, at: 10 };
+        assert!(repo.modify_position(&second, &updated, &modify).await.unwrap());
+        // Unknown positions are neither stored nor recorded.
+        let unknown = position(&owner, 1, 11);
+        assert!(!repo.modify_position(&unknown, &unknown, &modify).await.unwrap());
+        let positions = repo.user_positions(&owner).await.unwrap();
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[1].unrealized_pnl, 7);
+        assert_eq!(positions[1].margin_ratio, 0.5);
+        assert!(repo.list_positions().await.unwrap().iter().any(|p| p.id == second.id));
+
+        let history = repo.position_history(&first.id, TimeRange::default()).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!((history[0].action, history[0].created_at), (HistoryAction::Opened, 1));
+        assert!(history[0].old_values.is_none());
+        assert_eq!(history[0].new_values.as_ref().unwrap()["margin"], 100);
+        assert_eq!((history[1].action, history[1].reason.as_deref()), (HistoryAction::Closed, Some("test")));
+        assert_eq!(
+            history[1].old_values,
+            Some(serde_json::json!({"status": 1, "realized_pnl": 0, "closed_at": 0}))
+        );
+        assert_eq!(
+            history[1].new_values,
+            Some(serde_json::json!({"status": 2, "realized_pnl": -30, "closed_at": 3}))
+        );
+
+        let history = repo.position_history(&second.id, TimeRange { from: Some(5), to: None }).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].action, HistoryAction::Modified);
+        assert_eq!(history[0].new_values, Some(serde_json::json!({"unrealized_pnl": 7, "margin_ratio": 0.5})));
+
+        let actions = |entries: Vec<PositionHistory>| entries.iter().map(|e| e.action).collect::<Vec<_>>();
+        assert_eq!(
+            actions(repo.user_history(&owner, TimeRange::default()).await.unwrap()),
+            vec![HistoryAction::Opened, HistoryAction::Opened, HistoryAction::Closed, HistoryAction::Modified]
+        );
+        assert_eq!(
+            actions(repo.user_history(&owner, TimeRange { from: Some(2), to: Some(3) }).await.unwrap()),
+            vec![HistoryAction::Opened, HistoryAction::Closed]
+        );
+
+        let mut grown = updated.clone();
+        grown.size = 2_000;
+        grown.margin = 80;
+        let grow = Change { action: HistoryAction::Modified, reason: None, at: 12 };
+        assert!(repo.modify_position(&updated, &grown, &grow).await.unwrap());
+        // `updated` is stale now, so modifying from it again changes nothing.
+        assert!(!repo.modify_position(&updated, &grown, &grow).await.unwrap());
+        assert!(!repo.modify_position(&closed, &grown, &grow).await.unwrap());
+        assert_eq!(repo.get_user(&owner).await.unwrap().unwrap().locked_collateral, 80);
+        assert_eq!(repo.get_position(&second.id).await.unwrap().unwrap().size, 2_000);
+        let history = repo.position_history(&second.id, TimeRange { from: Some(12), to: None }).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].old_values, Some(serde_json::json!({"size": 1_000, "margin": 50})));
+
+        let mut shrunk = grown.clone();
+        shrunk.margin = 20;
+        assert!(repo.modify_position(&grown, &shrunk, &grow).await.unwrap());
+        assert_eq!(repo.get_user(&owner).await.unwrap().unwrap().locked_collateral, 20);
+
+        // A settlement priced from a stale read is refused, releasing nothing.
+        let stale = Position { status: 2, realized_pnl: 5, ..second.clone() };
+        assert!(!repo.settle_positions(&owner, &[stale], &close).await.unwrap());
+        let user = repo.get_user(&owner).await.unwrap().unwrap();
+        assert_eq!((user.locked_collateral, user.total_pnl), (20, -30));
+        assert_eq!(repo.get_position(&second.id).await.unwrap().unwrap().status, 1);
+
+        // Positions of unregistered owners are rejected.
+        let stranger = Uuid::new_v4().to_string();
+        let orphan = position(&stranger, 10, 4);
+        assert!(!repo.open_position(&orphan).await.unwrap());
+        assert!(repo.get_position(&orphan.id).await.unwrap().is_none());
+        assert!(repo.user_history(&stranger, TimeRange::default()).await.unwrap().is_empty());
+
+        let snapshot = |position_id: Option<&str>, snapshot_time: i64, total_pnl: i64| PnlSnapshot {
+            owner: owner.clone(),
+            position_id: position_id.map(str::to_string),
+            snapshot_time,
+            unrealized_pnl: total_pnl,
+            realized_pnl: 0,
+            total_pnl,
+            margin_ratio: Some(0.25),
+        };
+        repo.insert_pnl_snapshots(&[snapshot(None, 7_200, 2), snapshot(Some(&second.id), 3_600, 9)]).await.unwrap();
+        repo.insert_pnl_snapshots(&[snapshot(None, 3_600, 1), snapshot(None, 10_800, 3)]).await.unwrap();
+        let series = repo.pnl_snapshots(&owner, TimeRange::default()).await.unwrap();
+        assert_eq!(series.iter().map(|s| s.total_pnl).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(series[0].margin_ratio, Some(0.25));
+        let series = repo.pnl_snapshots(&owner, TimeRange { from: Some(7_200), to: Some(7_200) }).await.unwrap();
+        assert_eq!((series.len(), series[0].snapshot_time), (1, 7_200));
+
+        let code_name = Uuid::new_v4().simple().to_string()[..16].to_string();
+        let code = ReferralCode { code: code_name.clone(), referrer: owner.clone(), created_at: 5 };
+        assert!(repo.insert_referral_code(&code).await.unwrap());
+        assert!(!repo.insert_referral_code(&code).await.unwrap());
+        assert_eq!(repo.get_referral_code(&code_name).await.unwrap().unwrap().referrer, owner);
+        assert_eq!(repo.referral_codes(&owner).await.unwrap().len(), 1);
+
+        let referral = Referral { referee: stranger.clone(), referrer: owner.clone(), code: code_name, created_at: 6 };
+        assert!(repo.insert_referral(&referral).await.unwrap());
+        assert!(!repo.insert_referral(&referral).await.unwrap());
+        let referrals = repo.referrals(&owner).await.unwrap();
+        assert_eq!(referrals.len(), 1);
+        assert_eq!(referrals[0].referee, stranger);
+    }
+
+    #[tokio::test]
+    async fn in_memory_repository() {
+        exercise(&InMemoryRepository::default()).await;
+    }
+
+    /// Runs against the database in `TEST_DATABASE_URL`.
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn postgres_repository() {
+        let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+        exercise(&PostgresRepository::connect(&url).await.unwrap()).await;
+    }
+}
\ No newline at end of file
@@ -0,0 +1,518 @@
+use async_trait::async_trait;
+use sqlx::postgres::{PgConnection, PgPool, PgPoolOptions, PgRow};
+use sqlx::Row;
+
+use super::{apply_margin_change, history_entry, is_unchanged, Change, Repository, StorageError, StorageResult, TimeRange};
+use crate::{HistoryAction, PnlSnapshot, Position, PositionHistory, Referral, ReferralCode, User};
+
+// Amounts are `u64` in the API and stored in BIGINT columns; see `bigint` and `amount`.
+const USER_COLUMNS: &str = "u.address, u.total_collateral, u.locked_collateral, u.sub_accounts, u.total_pnl, \
+    EXTRACT(EPOCH FROM u.created_at)::BIGINT AS created_at, \
+    ARRAY(SELECT p.id FROM positions p WHERE p.owner = u.address ORDER BY p.opened_at, p.id) AS positions";
+
+const POSITION_COLUMNS: &str = "id, owner, sub_account_id, symbol, side, size, entry_price, leverage, status, margin, \
+    unrealized_pnl, realized_pnl, liquidation_price, margin_ratio, opened_at, closed_at";
+
+const HISTORY_COLUMNS: &str = "id::TEXT AS id, position_id, owner, action, old_values, new_values, reason, \
+    EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at";
+
+const SNAPSHOT_COLUMNS: &str = "owner, position_id, EXTRACT(EPOCH FROM snapshot_time)::BIGINT AS snapshot_time, \
+    unrealized_pnl, realized_pnl, total_pnl, margin_ratio";
+
+// Amounts above `i64::MAX` are rejected rather than wrapped into negative BIGINTs.
+fn bigint(value: u64, column: &'static str) -> StorageResult<i64> {
+    i64::try_from(value).map_err(|_| StorageError::OutOfRange(column))
+}
+
+fn amount(row: &PgRow, column: &'static str) -> StorageResult<u64> {
+    u64::try_from(row.try_get::<i64, _>(column)?).map_err(|_| StorageError::OutOfRange(column))
+}
+
+// Bounds `column` by the range in $2 and $3, either of which may be null.
+fn in_range(column: &str) -> String {
+    format!(
+        "(($2::FLOAT8 IS NULL OR {0} >= to_timestamp($2) AT TIME ZONE 'UTC') \
+         AND ($3::FLOAT8 IS NULL OR {0} <= to_timestamp($3) AT TIME ZONE 'UTC'))",
+        column
+    )
+}
+
+pub struct PostgresRepository {
+    pool: PgPool,
+}
+
+impl PostgresRepository {
+    /// Connects and applies any pending migrations from `migrations/`.
+    pub async fn connect(url: &str) -> StorageResult<Self> {
+        let pool = PgPoolOptions::new().max_connections(10).connect(url).await?;
+        sqlx::migrate!("./migrations").run(&pool).await?;
+        Ok(Self { pool })
+    }
+}
+
+fn user_from_row(row: &PgRow) -> StorageResult<User> {
+    let sub_accounts: Vec<i32> = row.try_get("sub_accounts")?;
+    Ok(User {
+        address: row.try_get("address")?,
+        collateral: amount(row, "total_collateral")?,
+        locked_collateral: amount(row, "locked_collateral")?,
+        sub_accounts: sub_accounts.into_iter().map(|id| id as u16).collect(),
+        positions: row.try_get("positions")?,
+        total_pnl: row.try_get("total_pnl")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn position_from_row(row: &PgRow) -> StorageResult<Position> {
+    Ok(Position {
+        id: row.try_get("id")?,
+        owner: row.try_get("owner")?,
+        sub_account_id: row.try_get::<i32, _>("sub_account_id")? as u16,
+        symbol: row.try_get("symbol")?,
+        side: row.try_get::<i32, _>("side")? as u8,
+        size: amount(row, "size")?,
+        entry_price: amount(row, "entry_price")?,
+        leverage: row.try_get::<i32, _>("leverage")? as u16,
+        status: row.try_get::<i32, _>("status")? as u8,
+        margin: amount(row, "margin")?,
+        unrealized_pnl: row.try_get("unrealized_pnl")?,
+        realized_pnl: row.try_get("realized_pnl")?,
+        liquidation_price: amount(row, "liquidation_price")?,
+        margin_ratio: row.try_get("margin_ratio")?,
+        opened_at: row.try_get("opened_at")?,
+        closed_at: row.try_get("closed_at")?,
+    })
+}
+
+fn history_from_row(row: &PgRow) -> StorageResult<PositionHistory> {
+    let action: String = row.try_get("action")?;
+    Ok(PositionHistory {
+        id: row.try_get("id")?,
+        position_id: row.try_get("position_id")?,
+        owner: row.try_get("owner")?,
+        action: HistoryAction::parse(&action).ok_or_else(|| sqlx::Error::Decode(format!("unknown action {}", action).into()))?,
+        old_values: row.try_get("old_values")?,
+        new_values: row.try_get("new_values")?,
+        reason: row.try_get("reason")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+async fn update_position(conn: &mut PgConnection, p: &Position) -> StorageResult<()> {
+    sqlx::query(
+        "UPDATE positions SET sub_account_id = $2, symbol = $3, side = $4, size = $5, entry_price = $6, \
+         leverage = $7, status = $8, margin = $9, unrealized_pnl = $10, realized_pnl = $11, \
+         liquidation_price = $12, margin_ratio = $13, opened_at = $14, closed_at = $15, updated_at = NOW() \
+         WHERE id = $1",
+    )
+    .bind(&p.id)
+    .bind(p.sub_account_id as i32)
+    .bind(&p.symbol)
+    .bind(p.side as i32)
+    .bind(bigint(p.size, "size")?)
+    .bind(bigint(p.entry_price, "entry_price")?)
+    .bind(p.leverage as i32)
+    .bind(p.status as i32)
+    .bind(bigint(p.margin, "margin")?)
+    .bind(p.unrealized_pnl)
+    .bind(p.realized_pnl)
+    .bind(bigint(p.liquidation_price, "liquidation_price")?)
+    .bind(p.margin_ratio)
+    .bind(p.opened_at)
+    .bind(p.closed_at)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn insert_history(conn: &mut PgConnection, entry: &PositionHistory) -> StorageResult<()> {
+    sqlx::query(
+        "INSERT INTO position_history (id, position_id, user_id, owner, action, old_values, new_values, reason, created_at) \
+         VALUES ($1::UUID, $2, (SELECT id FROM users WHERE address = $3), $3, $4, $5, $6, $7, to_timestamp($8) AT TIME ZONE 'UTC')",
+    )
+    .bind(&entry.id)
+    .bind(&entry.position_id)
+    .bind(&entry.owner)
+    .bind(entry.action.as_str())
+    .bind(&entry.old_values)
+    .bind(&entry.new_values)
+    .bind(&entry.reason)
+    .bind(entry.created_at as f64)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+fn snapshot_from_row(row: &PgRow) -> StorageResult<PnlSnapshot> {
+    Ok(PnlSnapshot {
+        owner: row.try_get("owner")?,
+        position_id: row.try_get("position_id")?,
+        snapshot_time: row.try_get("snapshot_time")?,
+        unrealized_pnl: row.try_get("unrealized_pnl")?,
+        realized_pnl: row.try_get("realized_pnl")?,
+        total_pnl: row.try_get("total_pnl")?,
+        margin_ratio: row.try_get("margin_ratio")?,
+    })
+}
+
+fn referral_code_from_row(row: &PgRow) -> StorageResult<ReferralCode> {
+    Ok(ReferralCode {
+        code: row.try_get("code")?,
+        referrer: row.try_get("referrer")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn referral_from_row(row: &PgRow) -> StorageResult<Referral> {
+    Ok(Referral {
+        referee: row.try_get("referee")?,
+        referrer: row.try_get("referrer")?,
+        code: row.try_get("code")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+#[async_trait]
+impl Repository for PostgresRepository {
+    async fn get_user(&self, address: &str) -> StorageResult<Option<User>> {
+        let row = sqlx::query(&format!("SELECT {} FROM users u WHERE u.address = $1", USER_COLUMNS))
+            .bind(address)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(user_from_row).transpose()
+    }
+
+    async fn list_users(&self) -> StorageResult<Vec<User>> {
+        let rows = sqlx::query(&format!("SELECT {} FROM users u ORDER BY u.created_at, u.address", USER_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(user_from_row).collect()
+    }
+
+    async fn initialize_user(&self, address: &str, sub_account_id: u16, created_at: i64) -> StorageResult<bool> {
+        let inserted = sqlx::query(
+            "INSERT INTO users (address, sub_accounts, created_at, updated_at) \
+             VALUES ($1, ARRAY[$2::INT], to_timestamp($3) AT TIME ZONE 'UTC', NOW()) \
+             ON CONFLICT (address) DO NOTHING",
+        )
+        .bind(address)
+        .bind(sub_account_id as i32)
+        .bind(created_at as f64)
+        .execute(&self.pool)
+        .await?;
+        if inserted.rows_affected() == 1 {
+            return Ok(true);
+        }
+
+        let added = sqlx::query(
+            "UPDATE users SET sub_accounts = ARRAY(SELECT unnest(sub_accounts || $2::INT) ORDER BY 1), updated_at = NOW() \
+             WHERE address = $1 AND NOT ($2::INT = ANY(sub_accounts))",
+        )
+        .bind(address)
+        .bind(sub_account_id as i32)
+        .execute(&self.pool)
+        .await?;
+        Ok(added.rows_affected() == 1)
+    }
+
+    async fn get_position(&self, id: &str) -> StorageResult<Option<Position>> {
+        let row = sqlx::query(&format!("SELECT {} FROM positions WHERE id = $1", POSITION_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(position_from_row).transpose()
+    }
+
+    async fn list_positions(&self) -> StorageResult<Vec<Position>> {
+        let rows = sqlx::query(&format!("SELECT {} FROM positions ORDER BY opened_at, id", POSITION_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(position_from_row).collect()
+    }
+
+    async fn user_positions(&self, owner: &str) -> StorageResult<Vec<Position>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM positions WHERE owner = $1 ORDER BY opened_at, id",
+            POSITION_COLUMNS
+        ))
+        .bind(owner)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(position_from_row).collect()
+    }
+
+    async fn open_position(&self, p: &Position) -> StorageResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        let Some(user) = sqlx::query("SELECT locked_collateral FROM users WHERE address = $1 FOR UPDATE")
+            .bind(&p.owner)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(false);
+        };
+
+        sqlx::query(
+            "INSERT INTO positions (id, user_id, owner, sub_account_id, symbol, side, size, entry_price, leverage, \
+             status, margin, unrealized_pnl, realized_pnl, liquidation_price, margin_ratio, opened_at, closed_at) \
+             VALUES ($1, (SELECT id FROM users WHERE address = $2), $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, \
+             $13, $14, $15, $16)",
+        )
+        .bind(&p.id)
+        .bind(&p.owner)
+        .bind(p.sub_account_id as i32)
+        .bind(&p.symbol)
+        .bind(p.side as i32)
+        .bind(bigint(p.size, "size")?)
+        .bind(bigint(p.entry_price, "entry_price")?)
+        .bind(p.leverage as i32)
+        .bind(p.status as i32)
+        .bind(bigint(p.margin, "margin")?)
+        .bind(p.unrealized_pnl)
+        .bind(p.realized_pnl)
+        .bind(bigint(p.liquidation_price, "liquidation_price")?)
+        .bind(p.margin_ratio)
+        .bind(p.opened_at)
+        .bind(p.closed_at)
+        .execute(&mut *tx)
+        .await?;
+
+        let locked = amount(&user, "locked_collateral")?.saturating_add(p.margin);
+        sqlx::query("UPDATE users SET locked_collateral = $2, updated_at = NOW() WHERE address = $1")
+            .bind(&p.owner)
+            .bind(bigint(locked, "locked_collateral")?)
+            .execute(&mut *tx)
+            .await?;
+
+        let change = Change { action: HistoryAction::Opened, reason: None, at: p.opened_at };
+        insert_history(&mut tx, &history_entry(None, p, &change)).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn modify_position(&self, old: &Position, p: &Position, change: &Change<'_>) -> StorageResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        let Some(row) = sqlx::query(&format!("SELECT {} FROM positions WHERE id = $1 FOR UPDATE", POSITION_COLUMNS))
+            .bind(&p.id)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(false);
+        };
+        let current = position_from_row(&row)?;
+        if !is_unchanged(&current, old) {
+            return Ok(false);
+        }
+
+        update_position(&mut tx, p).await?;
+
+        if let Some(row) = sqlx::query("SELECT locked_collateral FROM users WHERE address = $1 FOR UPDATE")
+            .bind(&p.owner)
+            .fetch_optional(&mut *tx)
+            .await?
+        {
+            let locked = apply_margin_change(amount(&row, "locked_collateral")?, old, p);
+            sqlx::query("UPDATE users SET locked_collateral = $2, updated_at = NOW() WHERE address = $1")
+                .bind(&p.owner)
+                .bind(bigint(locked, "locked_collateral")?)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        insert_history(&mut tx, &history_entry(Some(&current), p, change)).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn mark_positions(&self, positions: &[Position]) -> StorageResult<()> {
+        let mut tx = self.pool.begin().await?;
+        for p in positions {
+            sqlx::query(
+                "UPDATE positions SET unrealized_pnl = $2, margin_ratio = $3, updated_at = NOW() \
+                 WHERE id = $1 AND status = 1",
+            )
+            .bind(&p.id)
+            .bind(p.unrealized_pnl)
+            .bind(p.margin_ratio)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn settle_positions(&self, owner: &str, positions: &[Position], change: &Change<'_>) -> StorageResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        let user = sqlx::query("SELECT locked_collateral, total_pnl FROM users WHERE address = $1 FOR UPDATE")
+            .bind(owner)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let mut released_margin: u64 = 0;
+        let mut realized_pnl: i64 = 0;
+        for p in positions {
+            let Some(row) = sqlx::query(&format!("SELECT {} FROM positions WHERE id = $1 FOR UPDATE", POSITION_COLUMNS))
+                .bind(&p.id)
+                .fetch_optional(&mut *tx)
+                .await?
+            else {
+                return Ok(false);
+            };
+            let old = position_from_row(&row)?;
+            if !is_unchanged(&old, p) {
+                return Ok(false);
+            }
+
+            sqlx::query(
+                "UPDATE positions SET status = $2, unrealized_pnl = $3, realized_pnl = $4, closed_at = $5, \
+                 updated_at = NOW() WHERE id = $1",
+            )
+            .bind(&p.id)
+            .bind(p.status as i32)
+            .bind(p.unrealized_pnl)
+            .bind(p.realized_pnl)
+            .bind(p.closed_at)
+            .execute(&mut *tx)
+            .await?;
+            let settled = Position {
+                status: p.status,
+                unrealized_pnl: p.unrealized_pnl,
+                realized_pnl: p.realized_pnl,
+                closed_at: p.closed_at,
+                ..old.clone()
+            };
+            insert_history(&mut tx, &history_entry(Some(&old), &settled, change)).await?;
+
+            released_margin = released_margin.saturating_add(old.margin);
+            realized_pnl = realized_pnl.saturating_add(p.realized_pnl);
+        }
+
+        if let Some(row) = user {
+            let locked = amount(&row, "locked_collateral")?.saturating_sub(released_margin);
+            let total_pnl = row.try_get::<i64, _>("total_pnl")?.saturating_add(realized_pnl);
+            sqlx::query("UPDATE users SET locked_collateral = $2, total_pnl = $3, updated_at = NOW() WHERE address = $1")
+                .bind(owner)
+                .bind(bigint(locked, "locked_collateral")?)
+                .bind(total_pnl)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn position_history(&self, position_id: &str, range: TimeRange) -> StorageResult<Vec<PositionHistory>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM position_history WHERE position_id = $1 AND {} ORDER BY created_at, seq",
+            HISTORY_COLUMNS,
+            in_range("created_at")
+        ))
+        .bind(position_id)
+        .bind(range.from.map(|t| t as f64))
+        .bind(range.to.map(|t| t as f64))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(history_from_row).collect()
+    }
+
+    async fn user_history(&self, owner: &str, range: TimeRange) -> StorageResult<Vec<PositionHistory>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM position_history WHERE owner = $1 AND {} ORDER BY created_at, seq",
+            HISTORY_COLUMNS,
+            in_range("created_at")
+        ))
+        .bind(owner)
+        .bind(range.from.map(|t| t as f64))
+        .bind(range.to.map(|t| t as f64))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(history_from_row).collect()
+    }
+
+    async fn insert_pnl_snapshots(&self, snapshots: &[PnlSnapshot]) -> StorageResult<()> {
+        let mut tx = self.pool.begin().await?;
+        for s in snapshots {
+            sqlx::query(
+                "INSERT INTO pnl_snapshots (user_id, owner, position_id, snapshot_time, unrealized_pnl, realized_pnl, \
+                 total_pnl, margin_ratio) VALUES ((SELECT id FROM users WHERE address = $1), $1, $2, \
+                 to_timestamp($3) AT TIME ZONE 'UTC', $4, $5, $6, $7)",
+            )
+            .bind(&s.owner)
+            .bind(&s.position_id)
+            .bind(s.snapshot_time as f64)
+            .bind(s.unrealized_pnl)
+            .bind(s.realized_pnl)
+            .bind(s.total_pnl)
+            .bind(s.margin_ratio)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn pnl_snapshots(&self, owner: &str, range: TimeRange) -> StorageResult<Vec<PnlSnapshot>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM pnl_snapshots WHERE owner = $1 AND position_id IS NULL AND {} ORDER BY snapshot_time, created_at",
+            SNAPSHOT_COLUMNS,
+            in_range("snapshot_time")
+        ))
+        .bind(owner)
+        .bind(range.from.map(|t| t as f64))
+        .bind(range.to.map(|t| t as f64))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(snapshot_from_row).collect()
+    }
+
+    async fn insert_referral_code(&self, code: &ReferralCode) -> StorageResult<bool> {
+        let result = sqlx::query(
+            "INSERT INTO referral_codes (code, referrer, created_at) VALUES ($1, $2, $3) ON CONFLICT (code) DO NOTHING",
+        )
+        .bind(&code.code)
+        .bind(&code.referrer)
+        .bind(code.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn get_referral_code(&self, code: &str) -> StorageResult<Option<ReferralCode>> {
+        let row = sqlx::query("SELECT code, referrer, created_at FROM referral_codes WHERE code = $1")
+            .bind(code)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(referral_code_from_row).transpose()
+    }
+
+    async fn referral_codes(&self, referrer: &str) -> StorageResult<Vec<ReferralCode>> {
+        let rows = sqlx::query("SELECT code, referrer, created_at FROM referral_codes WHERE referrer = $1 ORDER BY code")
+            .bind(referrer)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(referral_code_from_row).collect()
+    }
+
+    async fn insert_referral(&self, referral: &Referral) -> StorageResult<bool> {
+        let result = sqlx::query(
+            "INSERT INTO referrals (referee, referrer, code, created_at) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (referee) DO NOTHING",
+        )
+        .bind(&referral.referee)
+        .bind(&referral.referrer)
+        .bind(&referral.code)
+        .bind(referral.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn referrals(&self, referrer: &str) -> StorageResult<Vec<Referral>> {
+        let rows = sqlx::query(
+            "SELECT referee, referrer, code, created_at FROM referrals WHERE referrer = $1 ORDER BY created_at, referee",
+        )
+        .bind(referrer)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(referral_from_row).collect()
+    }
+}
\ No newline at end of file
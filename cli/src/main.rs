@@ -0,0 +1,517 @@
+//! `pm-cli`: operate the position-management program from the command line.
+
+mod output;
+mod rpc;
+
+use std::path::PathBuf;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_spl::associated_token::get_associated_token_address;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use position_management::state::{
+    CollateralAccount, CollateralMint, Config, Market, Position, PositionBook, PositionIndex, UserAccount,
+};
+use position_management::utils::POSITION_MODE_ONE_WAY;
+use position_management_client::accounts::{decode, decode_position, decode_user_account};
+use position_management_client::instructions::{self, OpenPositionArgs, Trader};
+use position_management_client::{invariants, pda};
+use solana_keypair::{read_keypair_file, Keypair};
+use solana_signer::Signer;
+
+use output::{
+    config_json, market_json, position_json, print_record, print_records, symbol, user_json, violation_json, Format,
+};
+use rpc::RpcClient;
+
+pub type CliResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Parser)]
+#[command(name = "pm-cli", version, about = "Operate the position-management program")]
+struct Cli {
+    /// JSON-RPC endpoint of the cluster.
+    #[arg(long, global = true, env = "PM_RPC_URL", default_value = "http://127.0.0.1:8899")]
+    url: String,
+
+    /// Signer keypair; defaults to the Solana CLI keypair.
+    #[arg(long, global = true, env = "PM_KEYPAIR")]
+    keypair: Option<PathBuf>,
+
+    /// Output format for account state.
+    #[arg(long, global = true, value_enum, default_value_t = Format::Table)]
+    output: Format,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct SubAccount {
+    /// Sub-account id of the signer's user account.
+    #[arg(long, default_value_t = 0)]
+    sub_account: u16,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Side {
+    Long,
+    Short,
+}
+
+impl Side {
+    fn value(self) -> u8 {
+        match self {
+            Side::Long => 1,
+            Side::Short => 2,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a user account (or another sub-account) for the signer.
+    InitUser {
+        #[command(flatten)]
+        sub: SubAccount,
+    },
+    /// Deposit SPL collateral, creating the collateral account if needed.
+    Deposit {
+        #[command(flatten)]
+        sub: SubAccount,
+        #[arg(long)]
+        mint: Pubkey,
+        /// Amount in base units of the mint.
+        #[arg(long)]
+        amount: u64,
+        /// Source token account; defaults to the signer's associated token account.
+        #[arg(long)]
+        token_account: Option<Pubkey>,
+    },
+    /// Withdraw SPL collateral, subject to the haircut-weighted margin check.
+    Withdraw {
+        #[command(flatten)]
+        sub: SubAccount,
+        #[arg(long)]
+        mint: Pubkey,
+        #[arg(long)]
+        amount: u64,
+        /// Destination token account; defaults to the signer's associated token account.
+        #[arg(long)]
+        token_account: Option<Pubkey>,
+    },
+    /// Open a position at its market's mark price.
+    Open {
+        #[command(flatten)]
+        sub: SubAccount,
+        #[arg(long)]
+        symbol: String,
+        #[arg(long, value_enum)]
+        side: Side,
+        #[arg(long)]
+        size: u64,
+        #[arg(long)]
+        leverage: u16,
+        /// Fail if the market's mark price is above this.
+        #[arg(long)]
+        max_price: Option<u64>,
+        /// Fail if the market's mark price is below this.
+        #[arg(long)]
+        min_price: Option<u64>,
+        /// Only reduce `--existing-position`.
+        #[arg(long)]
+        reduce_only: bool,
+        /// Open position on the symbol to net against (one-way mode or reduce-only).
+        /// Defaults to the symbol's indexed position in one-way mode.
+        #[arg(long)]
+        existing_position: Option<Pubkey>,
+    },
+    /// Change a position's size and/or margin.
+    Modify {
+        #[command(flatten)]
+        sub: SubAccount,
+        #[arg(long)]
+        position: Pubkey,
+        #[arg(long, default_value_t = 0, allow_hyphen_values = true)]
+        size_delta: i64,
+        #[arg(long, default_value_t = 0, allow_hyphen_values = true)]
+        margin_delta: i64,
+    },
+    /// Close a position at its market's mark price.
+    Close {
+        #[command(flatten)]
+        sub: SubAccount,
+        #[arg(long)]
+        position: Pubkey,
+        /// Fail if the market's mark price is above this.
+        #[arg(long)]
+        max_price: Option<u64>,
+        /// Fail if the market's mark price is below this.
+        #[arg(long)]
+        min_price: Option<u64>,
+    },
+    /// Liquidate any user's position at its market's mark price.
+    Liquidate {
+        #[arg(long)]
+        position: Pubkey,
+    },
+    /// Show a user account.
+    ShowUser {
+        #[command(flatten)]
+        sub: SubAccount,
+        /// Wallet owning the account; defaults to the signer.
+        #[arg(long)]
+        owner: Option<Pubkey>,
+    },
+    /// List a wallet's positions.
+    ListPositions {
+        /// Wallet owning the positions; defaults to the signer.
+        #[arg(long)]
+        owner: Option<Pubkey>,
+        /// Only this sub-account.
+        #[arg(long)]
+        sub_account: Option<u16>,
+        /// Include closed and liquidated positions.
+        #[arg(long)]
+        all: bool,
+    },
+    /// Check a user account's totals against its positions, as `verify_user`
+    /// does on-chain. Exits non-zero on any mismatch.
+    VerifyUser {
+        #[command(flatten)]
+        sub: SubAccount,
+        /// Wallet owning the account; defaults to the signer.
+        #[arg(long)]
+        owner: Option<Pubkey>,
+    },
+    /// Show a market.
+    ShowMarket {
+        #[arg(long)]
+        symbol: String,
+    },
+    /// Config and market administration (config authority only).
+    #[command(subcommand)]
+    Admin(AdminCommand),
+}
+
+#[derive(Subcommand)]
+enum AdminCommand {
+    /// Create the global config with the signer as authority. The signer must
+    /// be the program's upgrade authority.
+    InitConfig {
+        #[arg(long)]
+        max_user_notional: u64,
+        #[arg(long, default_value_t = 0)]
+        fee_bps: u16,
+        #[arg(long, default_value_t = 0)]
+        referral_share_bps: u16,
+        /// Seconds after which mark and collateral prices are rejected as stale.
+        #[arg(long, default_value_t = 60)]
+        max_price_age: i64,
+    },
+    UpdateConfig {
+        #[arg(long)]
+        max_user_notional: u64,
+        #[arg(long)]
+        fee_bps: u16,
+        #[arg(long)]
+        referral_share_bps: u16,
+        #[arg(long)]
+        max_price_age: i64,
+    },
+    ShowConfig,
+    InitMarket {
+        #[arg(long)]
+        symbol: String,
+        #[arg(long)]
+        max_open_interest: u64,
+    },
+    UpdateMarket {
+        #[arg(long)]
+        symbol: String,
+        #[arg(long)]
+        max_open_interest: u64,
+    },
+    SetMarkPrice {
+        #[arg(long)]
+        symbol: String,
+        #[arg(long)]
+        price: u64,
+    },
+}
+
+struct Context {
+    rpc: RpcClient,
+    payer: Keypair,
+    format: Format,
+}
+
+impl Context {
+    fn send(&self, instruction: Instruction) -> CliResult<()> {
+        let signature = self.rpc.send(&[instruction], &self.payer)?;
+        println!("Signature: {}", signature);
+        Ok(())
+    }
+
+    fn exists(&self, address: &Pubkey) -> CliResult<bool> {
+        Ok(self.rpc.get_account_data(address)?.is_some())
+    }
+
+    fn fetch<T>(&self, address: &Pubkey, decoder: fn(&[u8]) -> Result<T, position_management_client::ClientError>) -> CliResult<T> {
+        let data = self.rpc.get_account_data(address)?.ok_or(format!("account {} not found", address))?;
+        Ok(decoder(&data)?)
+    }
+
+    /// Every position ever opened by `owner`, across its sub-accounts.
+    fn positions(&self, owner: &Pubkey) -> CliResult<Vec<(Pubkey, Position)>> {
+        // The owner follows the discriminator and version byte.
+        let accounts = self.rpc.get_program_accounts(Position::LEN, Some((8 + 1, owner.as_ref())))?;
+        accounts
+            .iter()
+            .map(|(address, data)| Ok((*address, decode_position(data)?)))
+            .collect()
+    }
+
+    /// The signer as owner of `sub_account_id`, passing the referral and collateral
+    /// accounts when they exist, plus the mints of its non-zero collateral balances.
+    fn trader(&self, sub_account_id: u16) -> CliResult<(Trader, Vec<Pubkey>)> {
+        let mut trader = Trader::owner(self.payer.pubkey(), sub_account_id);
+        let user_account = trader.user_account();
+
+        if self.exists(&pda::referral(&user_account))? {
+            trader = trader.with_referral();
+        }
+
+        let mut mints = Vec::new();
+        if let Some(data) = self.rpc.get_account_data(&pda::collateral_account(&user_account))? {
+            trader = trader.with_collateral_account();
+            let collateral: CollateralAccount = decode(&data)?;
+            mints.extend(collateral.balances.iter().filter(|b| b.amount > 0).map(|b| b.mint));
+        }
+        Ok((trader, mints))
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(err) = run(cli) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> CliResult<()> {
+    let keypair_path = match cli.keypair {
+        Some(path) => path,
+        None => PathBuf::from(std::env::var("HOME")?).join(".config/solana/id.json"),
+    };
+    let ctx = Context {
+        rpc: RpcClient::new(cli.url),
+        payer: read_keypair_file(&keypair_path)
+            .map_err(|e| format!("failed to read keypair {}: {}", keypair_path.display(), e))?,
+        format: cli.output,
+    };
+    let signer = ctx.payer.pubkey();
+
+    match cli.command {
+        Command::InitUser { sub } => ctx.send(instructions::initialize_user(signer, sub.sub_account)),
+
+        Command::Deposit { sub, mint, amount, token_account } => {
+            let user_account = pda::user_account(&signer, sub.sub_account);
+            if !ctx.exists(&pda::collateral_account(&user_account))? {
+                ctx.send(instructions::initialize_collateral_account(signer, sub.sub_account))?;
+            }
+            let token_account = token_account.unwrap_or_else(|| get_associated_token_address(&signer, &mint));
+            ctx.send(instructions::deposit_collateral(signer, sub.sub_account, mint, token_account, amount))
+        }
+
+        Command::Withdraw { sub, mint, amount, token_account } => {
+            let (_, mints) = ctx.trader(sub.sub_account)?;
+            let other_mints: Vec<Pubkey> = mints.into_iter().filter(|m| *m != mint).collect();
+            let token_account = token_account.unwrap_or_else(|| get_associated_token_address(&signer, &mint));
+
+            // The program values the account after the withdrawal with every open
+            // position at its mark price.
+            let positions: Vec<(Pubkey, String)> = ctx
+                .positions(&signer)?
+                .into_iter()
+                .filter(|(_, p)| p.sub_account_id == sub.sub_account && p.status == 1)
+                .map(|(address, p)| (address, symbol(&p.symbol)))
+                .collect();
+            let positions: Vec<(Pubkey, &str)> = positions.iter().map(|(a, s)| (*a, s.as_str())).collect();
+            let user_account = pda::user_account(&signer, sub.sub_account);
+            let book_symbols = match ctx.rpc.get_account_data(&pda::position_book(&user_account))? {
+                Some(data) => {
+                    let book = decode::<PositionBook>(&data)?;
+                    let mut symbols: Vec<String> = Vec::new();
+                    for slot in book.slots.iter().filter(|slot| slot.is_open()) {
+                        let slot_symbol = symbol(&slot.symbol);
+                        if !symbols.contains(&slot_symbol) {
+                            symbols.push(slot_symbol);
+                        }
+                    }
+                    Some(symbols)
+                }
+                None => None,
+            };
+            let book_symbols: Option<Vec<&str>> = book_symbols.as_ref().map(|s| s.iter().map(String::as_str).collect());
+            ctx.send(instructions::withdraw_collateral(
+                signer,
+                sub.sub_account,
+                mint,
+                token_account,
+                amount,
+                &positions,
+                book_symbols.as_deref(),
+                &other_mints,
+            ))
+        }
+
+        Command::Open {
+            sub,
+            symbol,
+            side,
+            size,
+            leverage,
+            max_price,
+            min_price,
+            reduce_only,
+            existing_position,
+        } => {
+            let (mut trader, mints) = ctx.trader(sub.sub_account)?;
+            let user: UserAccount = ctx.fetch(&trader.user_account(), decode_user_account)?;
+            let mut existing_position = existing_position;
+            if user.position_mode == POSITION_MODE_ONE_WAY {
+                trader = trader.with_one_way();
+                let index = pda::position_index(&trader.user_account(), &symbol);
+                if let (None, Some(data)) = (existing_position, ctx.rpc.get_account_data(&index)?) {
+                    let index: PositionIndex = decode(&data)?;
+                    existing_position = (index.position != Pubkey::default()).then_some(index.position);
+                }
+            }
+            let args = OpenPositionArgs {
+                symbol,
+                side: side.value(),
+                size,
+                leverage,
+                max_price,
+                min_price,
+                reduce_only,
+            };
+            println!("Position: {}", pda::position(&signer, sub.sub_account, user.next_position_id));
+            ctx.send(instructions::open_position(&trader, user.next_position_id, existing_position, &mints, args))
+        }
+
+        Command::Modify { sub, position, size_delta, margin_delta } => {
+            let (trader, mints) = ctx.trader(sub.sub_account)?;
+            let state = ctx.fetch(&position, decode_position)?;
+            ctx.send(instructions::modify_position(
+                &trader,
+                position,
+                &symbol(&state.symbol),
+                size_delta,
+                margin_delta,
+                &mints,
+            ))
+        }
+
+        Command::Close { sub, position, max_price, min_price } => {
+            let (trader, _) = ctx.trader(sub.sub_account)?;
+            let state = ctx.fetch(&position, decode_position)?;
+            ctx.send(instructions::close_position(&trader, position, &symbol(&state.symbol), max_price, min_price))
+        }
+
+        Command::Liquidate { position } => {
+            let state = ctx.fetch(&position, decode_position)?;
+            let user_account = pda::user_account(&state.owner, state.sub_account_id);
+            ctx.send(instructions::liquidate_position(signer, position, user_account, &symbol(&state.symbol)))
+        }
+
+        Command::ShowUser { sub, owner } => {
+            let address = pda::user_account(&owner.unwrap_or(signer), sub.sub_account);
+            let user = ctx.fetch(&address, decode_user_account)?;
+            print_record(ctx.format, &user_json(&address, &user));
+            Ok(())
+        }
+
+        Command::ListPositions { owner, sub_account, all } => {
+            let mut positions = ctx.positions(&owner.unwrap_or(signer))?;
+            positions.retain(|(_, p)| (all || p.status == 1) && sub_account.is_none_or(|id| p.sub_account_id == id));
+            positions.sort_by_key(|(_, p)| (p.sub_account_id, p.opened_at));
+
+            let records: Vec<_> = positions.iter().map(|(address, p)| position_json(address, p)).collect();
+            print_records(ctx.format, &records);
+            Ok(())
+        }
+
+        Command::VerifyUser { sub, owner } => {
+            let owner = owner.unwrap_or(signer);
+            let address = pda::user_account(&owner, sub.sub_account);
+            let user = ctx.fetch(&address, decode_user_account)?;
+            let positions: Vec<Position> = ctx
+                .positions(&owner)?
+                .into_iter()
+                .map(|(_, p)| p)
+                .filter(|p| p.sub_account_id == sub.sub_account)
+                .collect();
+            let book = match ctx.rpc.get_account_data(&pda::position_book(&address))? {
+                Some(data) => Some(decode::<PositionBook>(&data)?),
+                None => None,
+            };
+            let collateral = match ctx.rpc.get_account_data(&pda::collateral_account(&address))? {
+                Some(data) => Some(decode::<CollateralAccount>(&data)?),
+                None => None,
+            };
+            let mints = collateral
+                .iter()
+                .flat_map(|c| c.balances.iter().filter(|b| b.amount > 0))
+                .map(|b| ctx.fetch(&pda::collateral_mint(&b.mint), decode::<CollateralMint>))
+                .collect::<CliResult<Vec<_>>>()?;
+
+            let violations = invariants::verify_user(&user, &positions, book.as_ref(), collateral.as_ref(), &mints)?;
+            if violations.is_empty() {
+                println!("User account {} is consistent", address);
+                return Ok(());
+            }
+            let records: Vec<_> = violations.iter().map(violation_json).collect();
+            print_records(ctx.format, &records);
+            Err(format!("{} invariant(s) violated", violations.len()).into())
+        }
+
+        Command::ShowMarket { symbol } => {
+            let address = pda::market(&symbol);
+            let market: Market = ctx.fetch(&address, decode)?;
+            print_record(ctx.format, &market_json(&address, &market));
+            Ok(())
+        }
+
+        Command::Admin(command) => match command {
+            AdminCommand::InitConfig { max_user_notional, fee_bps, referral_share_bps, max_price_age } => {
+                ctx.send(instructions::initialize_config(
+                    signer,
+                    max_user_notional,
+                    fee_bps,
+                    referral_share_bps,
+                    max_price_age,
+                ))
+            }
+            AdminCommand::UpdateConfig { max_user_notional, fee_bps, referral_share_bps, max_price_age } => {
+                ctx.send(instructions::update_config(signer, max_user_notional, fee_bps, referral_share_bps, max_price_age))
+            }
+            AdminCommand::ShowConfig => {
+                let address = pda::config();
+                let config: Config = ctx.fetch(&address, decode)?;
+                print_record(ctx.format, &config_json(&address, &config));
+                Ok(())
+            }
+            AdminCommand::InitMarket { symbol, max_open_interest } => {
+                ctx.send(instructions::initialize_market(signer, &symbol, max_open_interest))
+            }
+            AdminCommand::UpdateMarket { symbol, max_open_interest } => {
+                ctx.send(instructions::update_market(signer, &symbol, max_open_interest))
+            }
+            AdminCommand::SetMarkPrice { symbol, price } => {
+                ctx.send(instructions::update_mark_price(signer, &symbol, price))
+            }
+        },
+    }
+}
\ No newline at end of file
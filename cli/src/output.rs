@@ -0,0 +1,153 @@
+use anchor_lang::prelude::Pubkey;
+use comfy_table::Table;
+use position_management::state::{Config, Market, Position, UserAccount};
+use position_management_client::invariants::InvariantViolation;
+use serde_json::{json, Value};
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+pub fn symbol(bytes: &[u8; 16]) -> String {
+    String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string()
+}
+
+fn side(side: u8) -> &'static str {
+    match side {
+        1 => "long",
+        2 => "short",
+        _ => "unknown",
+    }
+}
+
+fn status(status: u8) -> &'static str {
+    match status {
+        1 => "open",
+        2 => "closed",
+        3 => "liquidated",
+        _ => "unknown",
+    }
+}
+
+fn position_mode(mode: u8) -> &'static str {
+    match mode {
+        0 => "hedge",
+        1 => "one-way",
+        _ => "unknown",
+    }
+}
+
+pub fn position_json(address: &Pubkey, p: &Position) -> Value {
+    json!({
+        "address": address.to_string(),
+        "owner": p.owner.to_string(),
+        "sub_account_id": p.sub_account_id,
+        "symbol": symbol(&p.symbol),
+        "side": side(p.side),
+        "status": status(p.status),
+        "size": p.size,
+        "entry_price": p.entry_price,
+        "leverage": p.leverage,
+        "margin": p.margin,
+        "liquidation_price": p.liquidation_price,
+        "realized_pnl": p.realized_pnl,
+        "close_price": p.close_price,
+        "opened_at": p.opened_at,
+        "closed_at": p.closed_at
+    })
+}
+
+pub fn user_json(address: &Pubkey, u: &UserAccount) -> Value {
+    json!({
+        "address": address.to_string(),
+        "owner": u.owner.to_string(),
+        "sub_account_id": u.sub_account_id,
+        "version": u.version,
+        "position_mode": position_mode(u.position_mode),
+        "total_collateral": u.total_collateral,
+        "locked_collateral": u.locked_collateral,
+        "position_count": u.position_count,
+        "next_position_id": u.next_position_id,
+        "collateral_debt": u.collateral_debt,
+        "open_notional": u.open_notional,
+        "total_pnl": u.total_pnl,
+        "created_at": u.created_at,
+        "last_activity": u.last_activity
+    })
+}
+
+pub fn violation_json(v: &InvariantViolation) -> Value {
+    json!({
+        "invariant": format!("{:?}", v.error_code()),
+        "detail": v.to_string()
+    })
+}
+
+pub fn config_json(address: &Pubkey, c: &Config) -> Value {
+    json!({
+        "address": address.to_string(),
+        "authority": c.authority.to_string(),
+        "max_user_notional": c.max_user_notional,
+        "insurance_fund": c.insurance_fund,
+        "fee_bps": c.fee_bps,
+        "referral_share_bps": c.referral_share_bps,
+        "max_price_age": c.max_price_age
+    })
+}
+
+pub fn market_json(address: &Pubkey, m: &Market) -> Value {
+    json!({
+        "address": address.to_string(),
+        "symbol": symbol(&m.symbol),
+        "mark_price": m.mark_price,
+        "last_price_update": m.last_price_update,
+        "long_open_interest": m.long_open_interest,
+        "short_open_interest": m.short_open_interest,
+        "max_open_interest": m.max_open_interest,
+        "long_bad_debt": m.long_bad_debt,
+        "short_bad_debt": m.short_bad_debt
+    })
+}
+
+/// Prints a single record as field/value rows, or as a JSON object.
+pub fn print_record(format: Format, record: &Value) {
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(record).unwrap()),
+        Format::Table => {
+            let mut table = Table::new();
+            table.set_header(["Field", "Value"]);
+            for (field, value) in record.as_object().into_iter().flatten() {
+                table.add_row([field.clone(), cell(value)]);
+            }
+            println!("{table}");
+        }
+    }
+}
+
+/// Prints records as one row each, or as a JSON array.
+pub fn print_records(format: Format, records: &[Value]) {
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(records).unwrap()),
+        Format::Table => {
+            let Some(first) = records.first().and_then(Value::as_object) else {
+                println!("No records");
+                return;
+            };
+            let mut table = Table::new();
+            table.set_header(first.keys());
+            for record in records {
+                table.add_row(record.as_object().into_iter().flatten().map(|(_, v)| cell(v)));
+            }
+            println!("{table}");
+        }
+    }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,130 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+use solana_hash::Hash;
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+
+use crate::CliResult;
+
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Minimal JSON-RPC client covering the calls the CLI needs.
+pub struct RpcClient {
+    url: String,
+}
+
+impl RpcClient {
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+
+    fn call(&self, method: &str, params: Value) -> CliResult<Value> {
+        let response: Value = ureq::post(&self.url)
+            .send_json(json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params}))?
+            .into_json()?;
+
+        if let Some(error) = response.get("error") {
+            let reason = describe_error(&error["data"]["err"]).unwrap_or_else(|| error["message"].to_string());
+            return Err(format!("{} failed: {}", method, reason).into());
+        }
+        Ok(response["result"].clone())
+    }
+
+    pub fn get_account_data(&self, address: &Pubkey) -> CliResult<Option<Vec<u8>>> {
+        let result = self.call(
+            "getAccountInfo",
+            json!([address.to_string(), {"encoding": "base64", "commitment": "confirmed"}]),
+        )?;
+        match result["value"].as_object() {
+            Some(account) => Ok(Some(decode_data(&account["data"])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Program accounts of exactly `data_size` bytes whose data at `offset` equals `bytes`.
+    pub fn get_program_accounts(
+        &self,
+        data_size: usize,
+        memcmp: Option<(usize, &[u8])>,
+    ) -> CliResult<Vec<(Pubkey, Vec<u8>)>> {
+        let mut filters = vec![json!({"dataSize": data_size})];
+        if let Some((offset, bytes)) = memcmp {
+            filters.push(json!({"memcmp": {"offset": offset, "bytes": BASE64.encode(bytes), "encoding": "base64"}}));
+        }
+
+        let result = self.call(
+            "getProgramAccounts",
+            json!([
+                position_management::ID.to_string(),
+                {"encoding": "base64", "commitment": "confirmed", "filters": filters}
+            ]),
+        )?;
+
+        result
+            .as_array()
+            .ok_or("getProgramAccounts returned no accounts")?
+            .iter()
+            .map(|entry| {
+                let address = entry["pubkey"].as_str().ok_or("missing pubkey")?.parse()?;
+                Ok((address, decode_data(&entry["account"]["data"])?))
+            })
+            .collect()
+    }
+
+    fn latest_blockhash(&self) -> CliResult<Hash> {
+        let result = self.call("getLatestBlockhash", json!([{"commitment": "confirmed"}]))?;
+        Ok(result["value"]["blockhash"].as_str().ok_or("missing blockhash")?.parse()?)
+    }
+
+    /// Signs with `payer`, sends and waits for confirmation. Returns the signature.
+    pub fn send(&self, instructions: &[Instruction], payer: &Keypair) -> CliResult<String> {
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&payer.pubkey()),
+            &[payer],
+            self.latest_blockhash()?,
+        );
+        let encoded = BASE64.encode(bincode::serialize(&transaction)?);
+        let signature = self.call(
+            "sendTransaction",
+            json!([encoded, {"encoding": "base64", "preflightCommitment": "confirmed"}]),
+        )?;
+        let signature = signature.as_str().ok_or("missing signature")?.to_string();
+
+        let started = Instant::now();
+        while started.elapsed() < CONFIRM_TIMEOUT {
+            let statuses = self.call("getSignatureStatuses", json!([[signature]]))?;
+            let status = &statuses["value"][0];
+            if !status.is_null() {
+                if !status["err"].is_null() {
+                    let reason = describe_error(&status["err"]).unwrap_or_else(|| status["err"].to_string());
+                    return Err(format!("transaction {} failed: {}", signature, reason).into());
+                }
+                if matches!(status["confirmationStatus"].as_str(), Some("confirmed" | "finalized")) {
+                    return Ok(signature);
+                }
+            }
+            sleep(Duration::from_millis(500));
+        }
+        Err(format!("transaction {} was not confirmed", signature).into())
+    }
+}
+
+fn decode_data(data: &Value) -> CliResult<Vec<u8>> {
+    let encoded = data[0].as_str().ok_or("account data is not base64")?;
+    Ok(BASE64.decode(encoded)?)
+}
+
+/// Names the program error in an `{"InstructionError": [index, {"Custom": code}]}` result.
+fn describe_error(err: &Value) -> Option<String> {
+    let code = err["InstructionError"][1]["Custom"].as_u64()?;
+    let error = position_management_client::program_error(u32::try_from(code).ok()?)?;
+    Some(format!("{:?} ({}): {}", error, code, error))
+}
\ No newline at end of file
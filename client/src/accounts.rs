@@ -0,0 +1,31 @@
+use anchor_lang::{AccountDeserialize, Discriminator};
+use position_management::state::{account_version, upgrade_account_data, Position, PositionV1, UserAccount, UserAccountV1};
+
+use crate::errors::ClientError;
+
+/// Decodes any program account from its raw data, checking the discriminator.
+pub fn decode<T: AccountDeserialize>(data: &[u8]) -> Result<T, ClientError> {
+    Ok(T::try_deserialize(&mut &data[..])?)
+}
+
+/// Decodes a `Position`, upgrading accounts still in the v1 layout.
+pub fn decode_position(data: &[u8]) -> Result<Position, ClientError> {
+    decode_versioned(data, Position::DISCRIMINATOR, Position::LEN, PositionV1::LEN)
+}
+
+/// Decodes a `UserAccount`, upgrading accounts still in the v1 layout.
+pub fn decode_user_account(data: &[u8]) -> Result<UserAccount, ClientError> {
+    decode_versioned(data, UserAccount::DISCRIMINATOR, UserAccount::LEN, UserAccountV1::LEN)
+}
+
+fn decode_versioned<T: AccountDeserialize>(
+    data: &[u8],
+    discriminator: &[u8],
+    len: usize,
+    v1_len: usize,
+) -> Result<T, ClientError> {
+    if data.starts_with(discriminator) && account_version(data, len, v1_len) == Some(1) {
+        return decode(&upgrade_account_data(data)?);
+    }
+    decode(data)
+}
\ No newline at end of file
@@ -0,0 +1,111 @@
+use std::fmt;
+
+use anchor_lang::error::{Error as AnchorError, ERROR_CODE_OFFSET};
+use anchor_lang::prelude::instruction::error::InstructionError;
+use position_management::errors::ErrorCode;
+
+/// Every program error, in declaration order.
+pub const PROGRAM_ERRORS: &[ErrorCode] = &[
+    ErrorCode::InsufficientCollateral,
+    ErrorCode::PositionAlreadyClosed,
+    ErrorCode::InvalidPositionSize,
+    ErrorCode::InvalidLeverageValue,
+    ErrorCode::InvalidPrice,
+    ErrorCode::CalculationOverflow,
+    ErrorCode::CalculationUnderflow,
+    ErrorCode::CannotModifyOthersPosition,
+    ErrorCode::Unauthorized,
+    ErrorCode::CannotReduceMargin,
+    ErrorCode::PositionNotFound,
+    ErrorCode::InvalidSide,
+    ErrorCode::MarketMismatch,
+    ErrorCode::LongOpenInterestCapExceeded,
+    ErrorCode::ShortOpenInterestCapExceeded,
+    ErrorCode::UserNotionalCapExceeded,
+    ErrorCode::PositionNotLiquidatable,
+    ErrorCode::NoBadDebt,
+    ErrorCode::InvalidAdlRanking,
+    ErrorCode::InvalidAdlCandidate,
+    ErrorCode::DelegateExpired,
+    ErrorCode::DelegatePermissionDenied,
+    ErrorCode::DelegateNotionalExceeded,
+    ErrorCode::InvalidDelegatePermissions,
+    ErrorCode::InvalidSubAccount,
+    ErrorCode::InvalidFeeConfig,
+    ErrorCode::NoRebatesToClaim,
+    ErrorCode::SelfReferral,
+    ErrorCode::ReferralRequired,
+    ErrorCode::AccountAlreadyMigrated,
+    ErrorCode::UnknownAccountLayout,
+    ErrorCode::MigrationAccountsRequired,
+    ErrorCode::PositionBookFull,
+    ErrorCode::InvalidBookSlot,
+    ErrorCode::SlippageExceeded,
+    ErrorCode::InvalidHaircut,
+    ErrorCode::CollateralSlotsFull,
+    ErrorCode::CollateralMintNotFound,
+    ErrorCode::InvalidCollateralAmount,
+    ErrorCode::InvalidPositionMode,
+    ErrorCode::PositionModeLocked,
+    ErrorCode::PositionModeConflict,
+    ErrorCode::ReduceOnlyViolation,
+    ErrorCode::LockedCollateralMismatch,
+    ErrorCode::LockedCollateralExceedsCollateral,
+    ErrorCode::PositionCountMismatch,
+    ErrorCode::DuplicatePosition,
+    ErrorCode::OpenPositionRequired,
+    ErrorCode::StalePrice,
+    ErrorCode::InvalidPriceAge,
+];
+
+/// Maps a custom program error number (as returned on-chain, including
+/// Anchor's offset) back to the program's `ErrorCode`.
+pub fn program_error(code: u32) -> Option<ErrorCode> {
+    let number = code.checked_sub(ERROR_CODE_OFFSET)?;
+    PROGRAM_ERRORS.iter().copied().find(|e| *e as u32 == number)
+}
+
+/// Extracts the program's `ErrorCode` from a failed instruction, if it has one.
+pub fn instruction_error(error: &InstructionError) -> Option<ErrorCode> {
+    match error {
+        InstructionError::Custom(code) => program_error(*code),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// A program `ErrorCode`, e.g. from a rejected layout or failed calculation.
+    Program(ErrorCode),
+    /// Any other Anchor error, such as a discriminator mismatch when decoding.
+    Anchor(AnchorError),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Program(code) => write!(f, "{:?} ({}): {}", code, u32::from(*code), code),
+            ClientError::Anchor(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<ErrorCode> for ClientError {
+    fn from(code: ErrorCode) -> Self {
+        ClientError::Program(code)
+    }
+}
+
+impl From<AnchorError> for ClientError {
+    fn from(err: AnchorError) -> Self {
+        match &err {
+            AnchorError::AnchorError(e) => match program_error(e.error_code_number) {
+                Some(code) => ClientError::Program(code),
+                None => ClientError::Anchor(err),
+            },
+            AnchorError::ProgramError(_) => ClientError::Anchor(err),
+        }
+    }
+}
\ No newline at end of file
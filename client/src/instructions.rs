@@ -0,0 +1,721 @@
+//! Instruction builders. Each derives the program's PDAs from the given keys, so
+//! callers only supply wallets, sub-account ids and instruction arguments.
+
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+use anchor_spl::token;
+use position_management::state::UserAccount;
+use position_management::{accounts, instruction, ID};
+
+use crate::pda;
+
+pub use position_management::instructions::PriceBounds;
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn with_remaining(mut ix: Instruction, remaining: impl IntoIterator<Item = AccountMeta>) -> Instruction {
+    ix.accounts.extend(remaining);
+    ix
+}
+
+fn collateral_mint_metas(mints: &[Pubkey]) -> Vec<AccountMeta> {
+    mints
+        .iter()
+        .map(|mint| AccountMeta::new_readonly(pda::collateral_mint(mint), false))
+        .collect()
+}
+
+/// The signer of a trading instruction and the optional accounts it passes.
+#[derive(Clone, Copy, Debug)]
+pub struct Trader {
+    /// The owner, or a delegate holding a `Delegate` account for the sub-account.
+    pub authority: Pubkey,
+    pub owner: Pubkey,
+    pub sub_account_id: u16,
+    /// Pass the sub-account's `Referral` so fees accrue rebates to its referrer.
+    pub referral: bool,
+    /// Pass the sub-account's `CollateralAccount` for margin checks.
+    pub collateral_account: bool,
+    /// Pass the sub-account's `PositionIndex` for the opened symbol, which
+    /// one-way mode requires.
+    pub one_way: bool,
+}
+
+impl Trader {
+    pub fn owner(owner: Pubkey, sub_account_id: u16) -> Self {
+        Self {
+            authority: owner,
+            owner,
+            sub_account_id,
+            referral: false,
+            collateral_account: false,
+            one_way: false,
+        }
+    }
+
+    pub fn delegate(delegate: Pubkey, owner: Pubkey, sub_account_id: u16) -> Self {
+        Self {
+            authority: delegate,
+            ..Self::owner(owner, sub_account_id)
+        }
+    }
+
+    pub fn with_referral(self) -> Self {
+        Self { referral: true, ..self }
+    }
+
+    pub fn with_collateral_account(self) -> Self {
+        Self { collateral_account: true, ..self }
+    }
+
+    pub fn with_one_way(self) -> Self {
+        Self { one_way: true, ..self }
+    }
+
+    pub fn user_account(&self) -> Pubkey {
+        pda::user_account(&self.owner, self.sub_account_id)
+    }
+
+    fn delegate_account(&self) -> Option<Pubkey> {
+        (self.authority != self.owner).then(|| pda::delegate(&self.user_account(), &self.authority))
+    }
+
+    fn referral_account(&self) -> Option<Pubkey> {
+        self.referral.then(|| pda::referral(&self.user_account()))
+    }
+
+    fn collateral(&self) -> Option<Pubkey> {
+        self.collateral_account.then(|| pda::collateral_account(&self.user_account()))
+    }
+}
+
+// ===== Admin =====
+
+/// `authority` must be the program's upgrade authority.
+pub fn initialize_config(
+    authority: Pubkey,
+    max_user_notional: u64,
+    fee_bps: u16,
+    referral_share_bps: u16,
+    max_price_age: i64,
+) -> Instruction {
+    build(
+        accounts::InitializeConfig {
+            authority,
+            config: pda::config(),
+            program_data: pda::program_data(),
+            system_program: system_program::ID,
+        },
+        instruction::InitializeConfig { max_user_notional, fee_bps, referral_share_bps, max_price_age },
+    )
+}
+
+pub fn update_config(
+    authority: Pubkey,
+    max_user_notional: u64,
+    fee_bps: u16,
+    referral_share_bps: u16,
+    max_price_age: i64,
+) -> Instruction {
+    build(
+        accounts::UpdateConfig { authority, config: pda::config() },
+        instruction::UpdateConfig { max_user_notional, fee_bps, referral_share_bps, max_price_age },
+    )
+}
+
+pub fn initialize_market(authority: Pubkey, symbol: &str, max_open_interest: u64) -> Instruction {
+    build(
+        accounts::InitializeMarket {
+            authority,
+            config: pda::config(),
+            market: pda::market(symbol),
+            system_program: system_program::ID,
+        },
+        instruction::InitializeMarket { symbol: symbol.to_string(), max_open_interest },
+    )
+}
+
+pub fn update_market(authority: Pubkey, symbol: &str, max_open_interest: u64) -> Instruction {
+    build(
+        accounts::UpdateMarket { authority, config: pda::config(), market: pda::market(symbol) },
+        instruction::UpdateMarket { max_open_interest },
+    )
+}
+
+pub fn update_mark_price(authority: Pubkey, symbol: &str, price: u64) -> Instruction {
+    build(
+        accounts::UpdateMarkPrice { authority, config: pda::config(), market: pda::market(symbol) },
+        instruction::UpdateMarkPrice { price },
+    )
+}
+
+pub fn register_collateral_mint(authority: Pubkey, mint: Pubkey, oracle: Pubkey, haircut_bps: u16) -> Instruction {
+    build(
+        accounts::RegisterCollateralMint {
+            authority,
+            config: pda::config(),
+            mint,
+            collateral_mint: pda::collateral_mint(&mint),
+            vault: pda::vault(&mint),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::RegisterCollateralMint { oracle, haircut_bps },
+    )
+}
+
+pub fn update_collateral_mint(authority: Pubkey, mint: Pubkey, oracle: Pubkey, haircut_bps: u16) -> Instruction {
+    build(
+        accounts::UpdateCollateralMint {
+            authority,
+            config: pda::config(),
+            collateral_mint: pda::collateral_mint(&mint),
+        },
+        instruction::UpdateCollateralMint { oracle, haircut_bps },
+    )
+}
+
+pub fn update_collateral_price(oracle: Pubkey, mint: Pubkey, price: u64) -> Instruction {
+    build(
+        accounts::UpdateCollateralPrice { oracle, collateral_mint: pda::collateral_mint(&mint) },
+        instruction::UpdateCollateralPrice { price },
+    )
+}
+
+// ===== Accounts =====
+
+pub fn initialize_user(owner: Pubkey, sub_account_id: u16) -> Instruction {
+    build(
+        accounts::InitializeUser {
+            owner,
+            user_account: pda::user_account(&owner, sub_account_id),
+            system_program: system_program::ID,
+        },
+        instruction::InitializeUser { sub_account_id },
+    )
+}
+
+pub fn set_position_mode(owner: Pubkey, sub_account_id: u16, position_mode: u8) -> Instruction {
+    build(
+        accounts::SetPositionMode { owner, user_account: pda::user_account(&owner, sub_account_id) },
+        instruction::SetPositionMode { position_mode },
+    )
+}
+
+pub fn transfer_collateral(owner: Pubkey, from_sub_account_id: u16, to_sub_account_id: u16, amount: u64) -> Instruction {
+    build(
+        accounts::TransferCollateral {
+            owner,
+            from_account: pda::user_account(&owner, from_sub_account_id),
+            to_account: pda::user_account(&owner, to_sub_account_id),
+        },
+        instruction::TransferCollateral { amount },
+    )
+}
+
+pub fn create_delegate(
+    owner: Pubkey,
+    sub_account_id: u16,
+    delegate: Pubkey,
+    permissions: u8,
+    max_notional: u64,
+    expires_at: i64,
+) -> Instruction {
+    let user_account = pda::user_account(&owner, sub_account_id);
+    build(
+        accounts::CreateDelegate {
+            owner,
+            user_account,
+            delegate_account: pda::delegate(&user_account, &delegate),
+            system_program: system_program::ID,
+        },
+        instruction::CreateDelegate { delegate, permissions, max_notional, expires_at },
+    )
+}
+
+pub fn revoke_delegate(owner: Pubkey, sub_account_id: u16, delegate: Pubkey) -> Instruction {
+    let user_account = pda::user_account(&owner, sub_account_id);
+    build(
+        accounts::RevokeDelegate {
+            owner,
+            user_account,
+            delegate_account: pda::delegate(&user_account, &delegate),
+        },
+        instruction::RevokeDelegate {},
+    )
+}
+
+pub fn register_referral(owner: Pubkey, sub_account_id: u16, referrer: Pubkey) -> Instruction {
+    let user_account = pda::user_account(&owner, sub_account_id);
+    build(
+        accounts::RegisterReferral {
+            owner,
+            user_account,
+            referral: pda::referral(&user_account),
+            system_program: system_program::ID,
+        },
+        instruction::RegisterReferral { referrer },
+    )
+}
+
+/// Claims the rebates accrued on `referee_account`'s referral into one of the
+/// referrer's own sub-accounts.
+pub fn claim_rebates(referrer: Pubkey, referrer_sub_account_id: u16, referee_account: Pubkey) -> Instruction {
+    build(
+        accounts::ClaimRebates {
+            referrer,
+            referral: pda::referral(&referee_account),
+            referrer_account: pda::user_account(&referrer, referrer_sub_account_id),
+        },
+        instruction::ClaimRebates {},
+    )
+}
+
+/// Migrates a v1 user account or closed position.
+pub fn migrate_account(payer: Pubkey, account: Pubkey) -> Instruction {
+    build(
+        accounts::MigrateAccount {
+            payer,
+            account,
+            user_account: None,
+            market: None,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateAccount {},
+    )
+}
+
+/// Migrates an open v1 position of `owner`, whose user account must be
+/// migrated first, adding it to the user's notional and the market's open
+/// interest.
+pub fn migrate_open_position(payer: Pubkey, position: Pubkey, owner: Pubkey, symbol: &str) -> Instruction {
+    build(
+        accounts::MigrateAccount {
+            payer,
+            account: position,
+            user_account: Some(pda::user_account(&owner, UserAccount::LEGACY_SUB_ACCOUNT_ID)),
+            market: Some(pda::market(symbol)),
+            system_program: system_program::ID,
+        },
+        instruction::MigrateAccount {},
+    )
+}
+
+// ===== Collateral =====
+
+pub fn initialize_collateral_account(owner: Pubkey, sub_account_id: u16) -> Instruction {
+    let user_account = pda::user_account(&owner, sub_account_id);
+    build(
+        accounts::InitializeCollateralAccount {
+            owner,
+            user_account,
+            collateral_account: pda::collateral_account(&user_account),
+            system_program: system_program::ID,
+        },
+        instruction::InitializeCollateralAccount {},
+    )
+}
+
+pub fn deposit_collateral(
+    owner: Pubkey,
+    sub_account_id: u16,
+    mint: Pubkey,
+    owner_token_account: Pubkey,
+    amount: u64,
+) -> Instruction {
+    let user_account = pda::user_account(&owner, sub_account_id);
+    build(
+        accounts::DepositCollateral {
+            owner,
+            user_account,
+            collateral_account: pda::collateral_account(&user_account),
+            collateral_mint: pda::collateral_mint(&mint),
+            mint,
+            vault: pda::vault(&mint),
+            owner_token_account,
+            token_program: token::ID,
+        },
+        instruction::DepositCollateral { amount },
+    )
+}
+
+/// `positions` must cover every open position of the user. `book_symbols` lists
+/// every market held in the user's `PositionBook`, if it has one. `other_mints`
+/// are the mints of every other non-zero balance, used to value the collateral
+/// left after the withdrawal.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_collateral(
+    owner: Pubkey,
+    sub_account_id: u16,
+    mint: Pubkey,
+    owner_token_account: Pubkey,
+    amount: u64,
+    positions: &[(Pubkey, &str)],
+    book_symbols: Option<&[&str]>,
+    other_mints: &[Pubkey],
+) -> Instruction {
+    let user_account = pda::user_account(&owner, sub_account_id);
+    let ix = build(
+        accounts::WithdrawCollateral {
+            owner,
+            user_account,
+            config: pda::config(),
+            position_book: book_symbols.is_some().then(|| pda::position_book(&user_account)),
+            collateral_account: pda::collateral_account(&user_account),
+            collateral_mint: pda::collateral_mint(&mint),
+            mint,
+            vault: pda::vault(&mint),
+            owner_token_account,
+            token_program: token::ID,
+        },
+        instruction::WithdrawCollateral { amount },
+    );
+    let positions = positions.iter().flat_map(|(position, symbol)| {
+        [AccountMeta::new_readonly(*position, false), AccountMeta::new_readonly(pda::market(symbol), false)]
+    });
+    let markets = book_symbols
+        .unwrap_or_default()
+        .iter()
+        .map(|symbol| AccountMeta::new_readonly(pda::market(symbol), false));
+    with_remaining(ix, positions.chain(markets).chain(collateral_mint_metas(other_mints)))
+}
+
+// ===== Trading =====
+
+#[derive(Clone, Debug, Default)]
+pub struct OpenPositionArgs {
+    pub symbol: String,
+    pub side: u8,
+    pub size: u64,
+    pub leverage: u16,
+    pub max_price: Option<u64>,
+    pub min_price: Option<u64>,
+    pub reduce_only: bool,
+}
+
+/// `position_id` is the sub-account's current `UserAccount.next_position_id`,
+/// which seeds the new position. `existing_position` is the open position on the
+/// symbol to net against in one-way mode or with `reduce_only`; in one-way mode it
+/// must be the position recorded by the symbol's `PositionIndex`, if any.
+/// `collateral_mints` are the mints of the sub-account's non-zero collateral balances.
+pub fn open_position(
+    trader: &Trader,
+    position_id: u32,
+    existing_position: Option<Pubkey>,
+    collateral_mints: &[Pubkey],
+    args: OpenPositionArgs,
+) -> Instruction {
+    let ix = build(
+        accounts::OpenPosition {
+            authority: trader.authority,
+            user_account: trader.user_account(),
+            position: Some(pda::position(&trader.owner, trader.sub_account_id, position_id)),
+            existing_position,
+            position_index: trader.one_way.then(|| pda::position_index(&trader.user_account(), &args.symbol)),
+            config: pda::config(),
+            market: pda::market(&args.symbol),
+            delegate: trader.delegate_account(),
+            referral: trader.referral_account(),
+            collateral_account: trader.collateral(),
+            system_program: system_program::ID,
+        },
+        instruction::OpenPosition {
+            symbol: args.symbol,
+            side: args.side,
+            size: args.size,
+            leverage: args.leverage,
+            max_price: args.max_price,
+            min_price: args.min_price,
+            reduce_only: args.reduce_only,
+        },
+    );
+    with_remaining(ix, collateral_mint_metas(collateral_mints))
+}
+
+pub fn modify_position(
+    trader: &Trader,
+    position: Pubkey,
+    symbol: &str,
+    size_delta: i64,
+    margin_delta: i64,
+    collateral_mints: &[Pubkey],
+) -> Instruction {
+    let ix = build(
+        accounts::ModifyPosition {
+            authority: trader.authority,
+            user_account: trader.user_account(),
+            position,
+            config: pda::config(),
+            market: pda::market(symbol),
+            delegate: trader.delegate_account(),
+            referral: trader.referral_account(),
+            collateral_account: trader.collateral(),
+        },
+        instruction::ModifyPosition { size_delta, margin_delta },
+    );
+    with_remaining(ix, collateral_mint_metas(collateral_mints))
+}
+
+pub fn close_position(
+    trader: &Trader,
+    position: Pubkey,
+    symbol: &str,
+    max_price: Option<u64>,
+    min_price: Option<u64>,
+) -> Instruction {
+    build(
+        accounts::ClosePosition {
+            authority: trader.authority,
+            user_account: trader.user_account(),
+            position,
+            market: pda::market(symbol),
+            delegate: trader.delegate_account(),
+            config: pda::config(),
+            referral: trader.referral_account(),
+        },
+        instruction::ClosePosition { max_price, min_price },
+    )
+}
+
+/// Closes every `(position, symbol)` at its market's mark price. `price_bounds`
+/// is either empty or holds the bounds of each position, in order.
+pub fn close_all_positions(trader: &Trader, positions: &[(Pubkey, &str)], price_bounds: &[PriceBounds]) -> Instruction {
+    let ix = build(
+        accounts::CloseAllPositions {
+            authority: trader.authority,
+            user_account: trader.user_account(),
+            delegate: trader.delegate_account(),
+            config: pda::config(),
+            referral: trader.referral_account(),
+        },
+        instruction::CloseAllPositions { price_bounds: price_bounds.to_vec() },
+    );
+    with_remaining(
+        ix,
+        positions.iter().flat_map(|(position, symbol)| {
+            [AccountMeta::new(*position, false), AccountMeta::new(pda::market(symbol), false)]
+        }),
+    )
+}
+
+/// Moves `position` between two wallets' sub-accounts; both owners sign.
+/// `to_collateral_mints` are the mints of the receiver's non-zero collateral
+/// balances; its `CollateralAccount` is passed when there are any.
+pub fn transfer_position(
+    from_owner: Pubkey,
+    from_sub_account_id: u16,
+    to_owner: Pubkey,
+    to_sub_account_id: u16,
+    position: Pubkey,
+    to_collateral_mints: &[Pubkey],
+) -> Instruction {
+    let to_account = pda::user_account(&to_owner, to_sub_account_id);
+    let ix = build(
+        accounts::TransferPosition {
+            from_owner,
+            to_owner,
+            from_account: pda::user_account(&from_owner, from_sub_account_id),
+            to_account,
+            position,
+            config: pda::config(),
+            to_collateral_account: (!to_collateral_mints.is_empty()).then(|| pda::collateral_account(&to_account)),
+        },
+        instruction::TransferPosition {},
+    );
+    with_remaining(ix, collateral_mint_metas(to_collateral_mints))
+}
+
+// ===== Position book =====
+
+pub fn initialize_position_book(owner: Pubkey, sub_account_id: u16) -> Instruction {
+    let user_account = pda::user_account(&owner, sub_account_id);
+    build(
+        accounts::InitializePositionBook {
+            owner,
+            user_account,
+            position_book: pda::position_book(&user_account),
+            system_program: system_program::ID,
+        },
+        instruction::InitializePositionBook {},
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn open_book_position(
+    trader: &Trader,
+    collateral_mints: &[Pubkey],
+    symbol: &str,
+    side: u8,
+    size: u64,
+    leverage: u16,
+    max_price: Option<u64>,
+    min_price: Option<u64>,
+) -> Instruction {
+    let user_account = trader.user_account();
+    let ix = build(
+        accounts::OpenBookPosition {
+            authority: trader.authority,
+            user_account,
+            position_book: pda::position_book(&user_account),
+            config: pda::config(),
+            market: pda::market(symbol),
+            delegate: trader.delegate_account(),
+            referral: trader.referral_account(),
+            collateral_account: trader.collateral(),
+        },
+        instruction::OpenBookPosition {
+            symbol: symbol.to_string(),
+            side,
+            size,
+            leverage,
+            max_price,
+            min_price,
+        },
+    );
+    with_remaining(ix, collateral_mint_metas(collateral_mints))
+}
+
+pub fn close_book_position(
+    trader: &Trader,
+    symbol: &str,
+    slot_index: u8,
+    max_price: Option<u64>,
+    min_price: Option<u64>,
+) -> Instruction {
+    let user_account = trader.user_account();
+    build(
+        accounts::CloseBookPosition {
+            authority: trader.authority,
+            user_account,
+            position_book: pda::position_book(&user_account),
+            config: pda::config(),
+            market: pda::market(symbol),
+            delegate: trader.delegate_account(),
+            referral: trader.referral_account(),
+        },
+        instruction::CloseBookPosition { slot_index, max_price, min_price },
+    )
+}
+
+/// `positions` must cover every open position of the user and `book_symbols`
+/// every market held in its book. Pass `collateral_account` if the user has one,
+/// with the mints of its non-zero collateral balances.
+pub fn check_health(
+    user_account: Pubkey,
+    positions: &[(Pubkey, &str)],
+    book_symbols: &[&str],
+    collateral_account: bool,
+    collateral_mints: &[Pubkey],
+) -> Instruction {
+    let ix = build(
+        accounts::CheckHealth {
+            user_account,
+            config: pda::config(),
+            position_book: pda::position_book(&user_account),
+            collateral_account: collateral_account.then(|| pda::collateral_account(&user_account)),
+        },
+        instruction::CheckHealth {},
+    );
+    with_remaining(ix, health_metas(positions, book_symbols, collateral_mints))
+}
+
+/// Like `check_health`, with `book_symbols` set only if the user has a book.
+pub fn check_positions_health(
+    user_account: Pubkey,
+    positions: &[(Pubkey, &str)],
+    book_symbols: Option<&[&str]>,
+    collateral_account: bool,
+    collateral_mints: &[Pubkey],
+) -> Instruction {
+    let ix = build(
+        accounts::CheckPositionsHealth {
+            user_account,
+            config: pda::config(),
+            position_book: book_symbols.is_some().then(|| pda::position_book(&user_account)),
+            collateral_account: collateral_account.then(|| pda::collateral_account(&user_account)),
+        },
+        instruction::CheckPositionsHealth {},
+    );
+    with_remaining(ix, health_metas(positions, book_symbols.unwrap_or_default(), collateral_mints))
+}
+
+fn health_metas(positions: &[(Pubkey, &str)], book_symbols: &[&str], collateral_mints: &[Pubkey]) -> Vec<AccountMeta> {
+    let positions = positions.iter().flat_map(|(position, symbol)| {
+        [AccountMeta::new_readonly(*position, false), AccountMeta::new_readonly(pda::market(symbol), false)]
+    });
+    let markets = book_symbols.iter().map(|symbol| AccountMeta::new_readonly(pda::market(symbol), false));
+    positions.chain(markets).chain(collateral_mint_metas(collateral_mints)).collect()
+}
+
+/// `positions` must cover every open position of the user; closed ones are
+/// skipped. Pass the user's `PositionBook` and `CollateralAccount` if they exist,
+/// with the mints of its non-zero collateral balances.
+pub fn verify_user(
+    user_account: Pubkey,
+    positions: &[Pubkey],
+    position_book: bool,
+    collateral_account: bool,
+    collateral_mints: &[Pubkey],
+) -> Instruction {
+    let ix = build(
+        accounts::VerifyUser {
+            user_account,
+            position_book: position_book.then(|| pda::position_book(&user_account)),
+            collateral_account: collateral_account.then(|| pda::collateral_account(&user_account)),
+        },
+        instruction::VerifyUser {},
+    );
+    let positions = positions.iter().map(|position| AccountMeta::new_readonly(*position, false));
+    with_remaining(ix, positions.chain(collateral_mint_metas(collateral_mints)))
+}
+
+// ===== Keepers =====
+
+pub fn liquidate_position(liquidator: Pubkey, position: Pubkey, user_account: Pubkey, symbol: &str) -> Instruction {
+    build(
+        accounts::LiquidatePosition {
+            liquidator,
+            position,
+            user_account,
+            market: pda::market(symbol),
+            config: pda::config(),
+        },
+        instruction::LiquidatePosition {},
+    )
+}
+
+pub fn liquidate_book_position(liquidator: Pubkey, user_account: Pubkey, symbol: &str, slot_index: u8) -> Instruction {
+    build(
+        accounts::LiquidateBookPosition {
+            liquidator,
+            user_account,
+            position_book: pda::position_book(&user_account),
+            market: pda::market(symbol),
+            config: pda::config(),
+        },
+        instruction::LiquidateBookPosition { slot_index },
+    )
+}
+
+/// `candidates` are `(position, user_account)` pairs in ADL order: descending
+/// score, ties broken by ascending position address.
+/// `authority` must be the config authority.
+pub fn auto_deleverage(authority: Pubkey, symbol: &str, candidates: &[(Pubkey, Pubkey)]) -> Instruction {
+    let ix = build(
+        accounts::AutoDeleverage { authority, config: pda::config(), market: pda::market(symbol) },
+        instruction::AutoDeleverage {},
+    );
+    with_remaining(
+        ix,
+        candidates.iter().flat_map(|(position, user_account)| {
+            [AccountMeta::new(*position, false), AccountMeta::new(*user_account, false)]
+        }),
+    )
+}
\ No newline at end of file
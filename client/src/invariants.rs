@@ -0,0 +1,31 @@
+//! The checks `verify_user` runs on-chain, for monitoring jobs that fetch the
+//! accounts themselves.
+
+use position_management::state::{CollateralAccount, CollateralMint, Position, PositionBook, UserAccount};
+use position_management::utils;
+
+use crate::errors::ClientError;
+
+pub use position_management::utils::{InvariantViolation, UserInvariants};
+
+/// Every way `user` disagrees with its positions and collateral; empty if it is
+/// consistent. `positions` must include all of the user's open positions and may
+/// include closed ones. Pass the user's `PositionBook` and `CollateralAccount` if
+/// they exist, and the `CollateralMint` of each non-zero balance.
+pub fn verify_user(
+    user: &UserAccount,
+    positions: &[Position],
+    book: Option<&PositionBook>,
+    collateral: Option<&CollateralAccount>,
+    mints: &[CollateralMint],
+) -> Result<Vec<InvariantViolation>, ClientError> {
+    let mut invariants = UserInvariants::default();
+    for position in positions {
+        invariants.add_position(position);
+    }
+    if let Some(book) = book {
+        invariants.add_book(book);
+    }
+    let collateral_value = utils::collateral_value(user, collateral, mints)?;
+    Ok(invariants.violations(user, collateral_value))
+}
\ No newline at end of file
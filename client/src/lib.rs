@@ -0,0 +1,15 @@
+//! Client for the position-management program: PDA derivation, instruction
+//! builders, account decoders, error mapping, the program's margin math and its
+//! account invariants.
+
+pub mod accounts;
+pub mod errors;
+pub mod instructions;
+pub mod invariants;
+pub mod math;
+pub mod pda;
+
+pub use errors::{program_error, ClientError};
+pub use position_management::errors::ErrorCode;
+pub use position_management::state;
+pub use position_management::ID;
\ No newline at end of file
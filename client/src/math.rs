@@ -0,0 +1,43 @@
+//! The program's margin and PnL math, for computing the same values off-chain.
+//! Each helper calls the on-chain implementation, so results match exactly.
+
+use position_management::utils;
+
+use crate::errors::ClientError;
+
+pub use position_management::utils::{AccountHealth, HealthAccumulator};
+
+/// `size × price`, the notional counted against open-interest and user caps.
+pub fn notional_value(size: u64, price: u64) -> Result<u64, ClientError> {
+    Ok(utils::notional_value(size, price)?)
+}
+
+/// Margin locked when opening a position.
+pub fn initial_margin(entry_price: u64, leverage: u16) -> Result<u64, ClientError> {
+    Ok(utils::initial_margin(entry_price, leverage)?)
+}
+
+/// Price at which a position becomes liquidatable.
+pub fn liquidation_price(side: u8, entry_price: u64) -> Result<u64, ClientError> {
+    Ok(utils::liquidation_price(side, entry_price)?)
+}
+
+/// PnL of `size` units marked at `price`.
+pub fn unrealized_pnl(side: u8, entry_price: u64, price: u64, size: u64) -> Result<i64, ClientError> {
+    Ok(utils::unrealized_pnl(side, entry_price, price, size)?)
+}
+
+/// Price at which `margin + unrealized_pnl` reaches zero.
+pub fn bankruptcy_price(side: u8, entry_price: u64, margin: u64, size: u64) -> Result<u64, ClientError> {
+    Ok(utils::bankruptcy_price(side, entry_price, margin, size)?)
+}
+
+/// Trading fee charged on `notional` at `fee_bps`.
+pub fn trading_fee(notional: u64, fee_bps: u16) -> Result<u64, ClientError> {
+    Ok(utils::bps_of(notional, fee_bps)?)
+}
+
+/// Auto-deleveraging rank; higher scores are deleveraged first.
+pub fn adl_score(unrealized_pnl: i64, margin: u64, leverage: u16) -> u128 {
+    utils::adl_score(unrealized_pnl, margin, leverage)
+}
\ No newline at end of file
@@ -0,0 +1,69 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::bpf_loader_upgradeable;
+use position_management::state::UserAccount;
+use position_management::utils::symbol_bytes;
+use position_management::ID;
+
+fn find(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &ID).0
+}
+
+/// The program's `ProgramData` account, holding its upgrade authority.
+pub fn program_data() -> Pubkey {
+    Pubkey::find_program_address(&[ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+pub fn config() -> Pubkey {
+    find(&[b"config"])
+}
+
+pub fn market(symbol: &str) -> Pubkey {
+    find(&[b"market", &symbol_bytes(symbol)])
+}
+
+/// Accounts migrated from v1 keep their original `[b"user", owner]` address
+/// under `UserAccount::LEGACY_SUB_ACCOUNT_ID`.
+pub fn user_account(owner: &Pubkey, sub_account_id: u16) -> Pubkey {
+    if sub_account_id == UserAccount::LEGACY_SUB_ACCOUNT_ID {
+        return find(&[b"user", owner.as_ref()]);
+    }
+    find(&[b"user", owner.as_ref(), &sub_account_id.to_le_bytes()])
+}
+
+/// `position_id` is the user's `UserAccount.next_position_id` when the position is opened.
+pub fn position(owner: &Pubkey, sub_account_id: u16, position_id: u32) -> Pubkey {
+    find(&[
+        b"position",
+        owner.as_ref(),
+        &sub_account_id.to_le_bytes(),
+        &position_id.to_le_bytes(),
+    ])
+}
+
+pub fn position_index(user_account: &Pubkey, symbol: &str) -> Pubkey {
+    find(&[b"position_index", user_account.as_ref(), &symbol_bytes(symbol)])
+}
+
+pub fn delegate(user_account: &Pubkey, delegate: &Pubkey) -> Pubkey {
+    find(&[b"delegate", user_account.as_ref(), delegate.as_ref()])
+}
+
+pub fn referral(user_account: &Pubkey) -> Pubkey {
+    find(&[b"referral", user_account.as_ref()])
+}
+
+pub fn position_book(user_account: &Pubkey) -> Pubkey {
+    find(&[b"position_book", user_account.as_ref()])
+}
+
+pub fn collateral_account(user_account: &Pubkey) -> Pubkey {
+    find(&[b"collateral", user_account.as_ref()])
+}
+
+pub fn collateral_mint(mint: &Pubkey) -> Pubkey {
+    find(&[b"collateral_mint", mint.as_ref()])
+}
+
+pub fn vault(mint: &Pubkey) -> Pubkey {
+    find(&[b"vault", mint.as_ref()])
+}
\ No newline at end of file
@@ -0,0 +1,133 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use position_management::errors::ErrorCode;
+use position_management::state::{Position, UserAccount, UserAccountV1};
+use position_management_client::accounts::{decode_position, decode_user_account};
+use position_management_client::errors::{instruction_error, PROGRAM_ERRORS};
+use position_management_client::instructions::{self, OpenPositionArgs, Trader};
+use position_management_client::{math, pda, program_error, ClientError, ID};
+
+fn user_account_v1(owner: Pubkey) -> UserAccountV1 {
+    UserAccountV1 {
+        owner,
+        bump: 255,
+        total_collateral: 10_000,
+        locked_collateral: 4_000,
+        position_count: 1,
+        total_pnl: 12,
+        created_at: 1_700_000_000,
+        last_activity: 1_700_000_100,
+    }
+}
+
+#[test]
+fn pdas_match_program_seeds() {
+    let owner = Pubkey::new_unique();
+    let (user_account, _) = Pubkey::find_program_address(&[b"user", owner.as_ref(), &7u16.to_le_bytes()], &ID);
+    assert_eq!(pda::user_account(&owner, 7), user_account);
+
+    let (position, _) = Pubkey::find_program_address(
+        &[b"position", owner.as_ref(), &7u16.to_le_bytes(), &3u32.to_le_bytes()],
+        &ID,
+    );
+    assert_eq!(pda::position(&owner, 7, 3), position);
+
+    let mut symbol = [0u8; 16];
+    symbol[..8].copy_from_slice(b"SOL-PERP");
+    let (market, _) = Pubkey::find_program_address(&[b"market", &symbol], &ID);
+    assert_eq!(pda::market("SOL-PERP"), market);
+}
+
+#[test]
+fn open_position_builder_derives_accounts() {
+    let owner = Pubkey::new_unique();
+    let session_key = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let trader = Trader::delegate(session_key, owner, 1).with_collateral_account();
+
+    let ix = instructions::open_position(
+        &trader,
+        4,
+        None,
+        &[mint],
+        OpenPositionArgs {
+            symbol: "BTC-PERP".to_string(),
+            side: 1,
+            size: 10,
+            leverage: 5,
+            ..Default::default()
+        },
+    );
+
+    let user_account = pda::user_account(&owner, 1);
+    let keys: Vec<Pubkey> = ix.accounts.iter().map(|a| a.pubkey).collect();
+    assert_eq!(ix.program_id, ID);
+    assert_eq!(keys[0], session_key);
+    assert!(ix.accounts[0].is_signer);
+    assert_eq!(keys[1], user_account);
+    assert_eq!(keys[2], pda::position(&owner, 1, 4));
+    // Omitted optional accounts are passed as the program id.
+    assert_eq!(keys[3], ID);
+    assert_eq!(keys[4], ID);
+    assert_eq!(keys[7], pda::delegate(&user_account, &session_key));
+    assert_eq!(keys[8], ID);
+    assert_eq!(keys[9], pda::collateral_account(&user_account));
+    assert_eq!(keys.last(), Some(&pda::collateral_mint(&mint)));
+    assert!(ix.data.starts_with(position_management::instruction::OpenPosition::DISCRIMINATOR));
+}
+
+#[test]
+fn decodes_current_and_legacy_user_accounts() {
+    let owner = Pubkey::new_unique();
+    let mut data = UserAccount::DISCRIMINATOR.to_vec();
+    user_account_v1(owner).serialize(&mut data).unwrap();
+
+    let user = decode_user_account(&data).unwrap();
+    assert_eq!(user.version, UserAccount::VERSION);
+    assert_eq!(user.owner, owner);
+    assert_eq!(user.locked_collateral, 4_000);
+
+    let mut current = Vec::new();
+    user.try_serialize(&mut current).unwrap();
+    assert_eq!(decode_user_account(&current).unwrap().total_pnl, 12);
+}
+
+#[test]
+fn decoding_the_wrong_account_type_fails() {
+    let mut data = UserAccount::DISCRIMINATOR.to_vec();
+    user_account_v1(Pubkey::new_unique()).serialize(&mut data).unwrap();
+
+    assert!(matches!(decode_position(&data), Err(ClientError::Anchor(_))));
+    assert!(Position::try_deserialize(&mut data.as_slice()).is_err());
+}
+
+#[test]
+fn maps_error_numbers_back_to_error_codes() {
+    let code = u32::from(ErrorCode::SlippageExceeded);
+    assert_eq!(program_error(code).map(|e| e as u32), Some(ErrorCode::SlippageExceeded as u32));
+    assert_eq!(
+        instruction_error(&anchor_lang::prelude::instruction::error::InstructionError::Custom(code)).map(|e| e as u32),
+        Some(ErrorCode::SlippageExceeded as u32)
+    );
+    assert!(program_error(ErrorCode::SlippageExceeded as u32).is_none());
+    // Anchor's own constraint errors are not program errors.
+    assert!(program_error(2003).is_none());
+
+    let mut numbers: Vec<u32> = PROGRAM_ERRORS.iter().map(|e| *e as u32).collect();
+    numbers.sort();
+    numbers.dedup();
+    assert_eq!(numbers.len(), PROGRAM_ERRORS.len());
+}
+
+#[test]
+fn math_matches_program() {
+    assert_eq!(math::initial_margin(50_000, 10).unwrap(), 5_000);
+    assert_eq!(math::liquidation_price(1, 50_000).unwrap(), 45_000);
+    assert_eq!(math::liquidation_price(2, 50_000).unwrap(), 55_000);
+    assert_eq!(math::unrealized_pnl(2, 50_000, 49_000, 3).unwrap(), 3_000);
+    assert_eq!(math::trading_fee(1_000_000, 10).unwrap(), 1_000);
+    assert!(matches!(
+        math::notional_value(u64::MAX, 2),
+        Err(ClientError::Program(ErrorCode::CalculationOverflow))
+    ));
+}
\ No newline at end of file
@@ -0,0 +1 @@
+//! Integration tests for the position-management program; see `tests/`.
\ No newline at end of file
@@ -0,0 +1,1195 @@
+mod svm;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use position_management::errors::ErrorCode;
+use position_management::state::{
+    CollateralAccount, CollateralMint, Config, Delegate, Market, Position, PositionIndex, PositionV1, Referral,
+    UserAccount, UserAccountV1,
+};
+use position_management::utils::{self, PERMISSION_ALL, POSITION_MODE_HEDGE, POSITION_MODE_ONE_WAY};
+use position_management_client::instructions::{self, OpenPositionArgs, PriceBounds, Trader};
+use position_management_client::invariants::{self, InvariantViolation};
+use position_management_client::pda;
+use proptest::prelude::*;
+use svm::{Svm, GENESIS_TIMESTAMP};
+
+const SYMBOL: &str = "SOL-PERP";
+const PRICE: u64 = 100_000_000;
+/// Collateral deposited by each trader, worth one quote unit per base unit.
+const DEPOSIT: u64 = 1_000_000_000;
+const MAX_PRICE_AGE: i64 = 3_600;
+
+struct Env {
+    svm: Svm,
+    admin: Pubkey,
+    mint: Pubkey,
+}
+
+impl Env {
+    /// A config and market with no caps or fees, and a 6-decimal collateral mint
+    /// with no haircut, all priced.
+    fn new() -> Self {
+        let mut svm = Svm::new();
+        let admin = svm.wallet();
+        let mint = svm.token_mint(6);
+        svm.set_upgrade_authority(admin);
+
+        let mut env = Env { svm, admin, mint };
+        env.ok(instructions::initialize_config(admin, u64::MAX, 0, 0, MAX_PRICE_AGE));
+        env.ok(instructions::initialize_market(admin, SYMBOL, u64::MAX));
+        env.ok(instructions::register_collateral_mint(admin, mint, admin, 0));
+        env.ok(instructions::update_mark_price(admin, SYMBOL, PRICE));
+        env.ok(instructions::update_collateral_price(admin, mint, 1_000_000));
+        env
+    }
+
+    fn ok(&mut self, ix: Instruction) {
+        if let Err(err) = self.svm.process(&ix) {
+            panic!("instruction failed: {:?}", err);
+        }
+    }
+
+    fn fails_with(&mut self, ix: Instruction, code: ErrorCode) {
+        let result = self.svm.process(&ix);
+        assert_eq!(result, Err(ProgramError::Custom(u32::from(code))), "expected {:?}", code);
+    }
+
+    /// A wallet with sub-account 0 holding `collateral` of the collateral mint,
+    /// deposited from its own token account.
+    fn trader_with(&mut self, collateral: u64) -> Trader {
+        let owner = self.svm.wallet();
+        self.ok(instructions::initialize_user(owner, 0));
+        self.ok(instructions::initialize_collateral_account(owner, 0));
+        if collateral > 0 {
+            let token_account = self.svm.token_account(self.mint, owner, collateral);
+            self.ok(instructions::deposit_collateral(owner, 0, self.mint, token_account, collateral));
+        }
+        Trader::owner(owner, 0).with_collateral_account()
+    }
+
+    fn trader(&mut self) -> Trader {
+        self.trader_with(DEPOSIT)
+    }
+
+    fn open_ix(&self, trader: &Trader, existing_position: Option<Pubkey>, args: OpenPositionArgs) -> Instruction {
+        let user: UserAccount = self.svm.get(&trader.user_account());
+        instructions::open_position(trader, user.next_position_id, existing_position, &[self.mint], args)
+    }
+
+    /// Opens a position and returns its address.
+    fn open(&mut self, trader: &Trader, side: u8, size: u64, leverage: u16) -> Pubkey {
+        let ix = self.open_ix(trader, None, args(side, size, leverage));
+        let position = ix.accounts[2].pubkey;
+        if let Err(err) = self.svm.process(&ix) {
+            panic!("open failed: {:?}", err);
+        }
+        position
+    }
+
+    fn open_fails_with(&mut self, trader: &Trader, args: OpenPositionArgs, code: ErrorCode) {
+        let ix = self.open_ix(trader, None, args);
+        self.fails_with(ix, code);
+    }
+
+    /// `verify_user` over `positions`, valuing the trader's collateral.
+    fn verify_ix(&self, trader: &Trader, positions: &[Pubkey]) -> Instruction {
+        instructions::verify_user(trader.user_account(), positions, false, true, &[self.mint])
+    }
+
+    fn user(&self, trader: &Trader) -> UserAccount {
+        self.svm.get(&trader.user_account())
+    }
+
+    fn position(&self, position: &Pubkey) -> Position {
+        self.svm.get(position)
+    }
+
+    fn market(&self) -> Market {
+        self.svm.get(&pda::market(SYMBOL))
+    }
+}
+
+fn args(side: u8, size: u64, leverage: u16) -> OpenPositionArgs {
+    OpenPositionArgs {
+        symbol: SYMBOL.to_string(),
+        side,
+        size,
+        leverage,
+        ..OpenPositionArgs::default()
+    }
+}
+
+#[test]
+fn price_updates_are_authority_gated_and_timestamped() {
+    let mut env = Env::new();
+    assert_eq!(env.market().mark_price, PRICE);
+    assert_eq!(env.market().last_price_update, GENESIS_TIMESTAMP);
+
+    env.svm.warp(30);
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE + 1));
+    assert_eq!(env.market().last_price_update, GENESIS_TIMESTAMP + 30);
+
+    let outsider = env.svm.wallet();
+    env.fails_with(instructions::update_mark_price(outsider, SYMBOL, 1), ErrorCode::Unauthorized);
+    env.fails_with(instructions::update_collateral_price(outsider, env.mint, 1), ErrorCode::Unauthorized);
+    assert_eq!(env.market().mark_price, PRICE + 1);
+}
+
+#[test]
+fn only_the_upgrade_authority_can_initialize_the_config() {
+    let mut svm = Svm::new();
+    let deployer = svm.wallet();
+    let outsider = svm.wallet();
+    svm.set_upgrade_authority(deployer);
+
+    let result = svm.process(&instructions::initialize_config(outsider, u64::MAX, 0, 0, MAX_PRICE_AGE));
+    assert_eq!(result, Err(ProgramError::Custom(u32::from(ErrorCode::Unauthorized))));
+    assert!(svm.account(&pda::config()).is_none());
+
+    svm.process(&instructions::initialize_config(deployer, u64::MAX, 0, 0, MAX_PRICE_AGE)).unwrap();
+    let config: Config = svm.get(&pda::config());
+    assert_eq!(config.authority, deployer);
+}
+
+#[test]
+fn open_interest_is_capped_per_side() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    env.ok(instructions::update_market(env.admin, SYMBOL, 10));
+
+    let long = env.open(&trader, 1, 10, 10);
+    env.open_fails_with(&trader, args(1, 1, 10), ErrorCode::LongOpenInterestCapExceeded);
+    env.fails_with(
+        instructions::modify_position(&trader, long, SYMBOL, 1, 0, &[env.mint]),
+        ErrorCode::LongOpenInterestCapExceeded,
+    );
+
+    let short = env.open(&trader, 2, 10, 10);
+    env.open_fails_with(&trader, args(2, 1, 10), ErrorCode::ShortOpenInterestCapExceeded);
+    assert_eq!((env.market().long_open_interest, env.market().short_open_interest), (10, 10));
+
+    // Closing frees capacity on that side only.
+    env.ok(instructions::close_position(&trader, short, SYMBOL, None, None));
+    env.open(&trader, 2, 10, 10);
+    env.open_fails_with(&trader, args(1, 1, 10), ErrorCode::LongOpenInterestCapExceeded);
+}
+
+#[test]
+fn open_notional_is_capped_per_user() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    let other = env.trader();
+    env.ok(instructions::update_config(env.admin, 10 * PRICE, 0, 0, MAX_PRICE_AGE));
+
+    let address = env.open(&trader, 1, 6, 10);
+    env.open_fails_with(&trader, args(2, 5, 10), ErrorCode::UserNotionalCapExceeded);
+    env.fails_with(
+        instructions::modify_position(&trader, address, SYMBOL, 5, 0, &[env.mint]),
+        ErrorCode::UserNotionalCapExceeded,
+    );
+    env.open(&trader, 2, 4, 10);
+    assert_eq!(env.user(&trader).open_notional, 10 * PRICE);
+
+    // The cap is per user, not global.
+    env.open(&other, 1, 10, 10);
+}
+
+#[test]
+fn position_lifecycle() {
+    let mut env = Env::new();
+    let trader = env.trader();
+
+    let address = env.open(&trader, 1, 10, 10);
+    let position = env.position(&address);
+    assert_eq!(position.version, Position::VERSION);
+    assert_eq!(position.owner, trader.owner);
+    assert_eq!((position.side, position.size, position.leverage, position.status), (1, 10, 10, 1));
+    assert_eq!(position.entry_price, PRICE);
+    assert_eq!(position.margin, PRICE / 10);
+    assert_eq!(position.liquidation_price, PRICE * 90 / 100);
+    assert_eq!(position.opened_at, GENESIS_TIMESTAMP);
+
+    let user = env.user(&trader);
+    assert_eq!(user.position_count, 1);
+    assert_eq!(user.locked_collateral, PRICE / 10);
+    assert_eq!(user.open_notional, 10 * PRICE);
+    assert_eq!(user.last_activity, GENESIS_TIMESTAMP);
+    assert_eq!(env.market().long_open_interest, 10);
+
+    env.svm.warp(3_600);
+    env.ok(instructions::modify_position(&trader, address, SYMBOL, 5, 1_000, &[env.mint]));
+    let position = env.position(&address);
+    assert_eq!((position.size, position.margin), (15, PRICE / 10 + 1_000));
+    let user = env.user(&trader);
+    assert_eq!(user.open_notional, 15 * PRICE);
+    assert_eq!(user.locked_collateral, PRICE / 10 + 1_000);
+    assert_eq!(env.market().long_open_interest, 15);
+
+    env.ok(instructions::modify_position(&trader, address, SYMBOL, -5, -1_000, &[env.mint]));
+    assert_eq!(env.user(&trader).open_notional, 10 * PRICE);
+    assert_eq!(env.market().long_open_interest, 10);
+
+    env.svm.warp(60);
+    let exit_price = PRICE + 1_000_000;
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, exit_price));
+    env.ok(instructions::close_position(&trader, address, SYMBOL, None, None));
+    let position = env.position(&address);
+    assert_eq!(position.status, 2);
+    assert_eq!(position.close_price, exit_price);
+    assert_eq!(position.realized_pnl, 10_000_000);
+    assert_eq!(position.closed_at, GENESIS_TIMESTAMP + 3_660);
+
+    let user = env.user(&trader);
+    assert_eq!(user.position_count, 0);
+    assert_eq!(user.locked_collateral, 0);
+    assert_eq!(user.open_notional, 0);
+    assert_eq!(user.total_pnl, 10_000_000);
+    assert_eq!(user.last_activity, GENESIS_TIMESTAMP + 3_660);
+    assert_eq!(env.market().long_open_interest, 0);
+
+    env.fails_with(
+        instructions::close_position(&trader, address, SYMBOL, None, None),
+        ErrorCode::PositionAlreadyClosed,
+    );
+    env.fails_with(
+        instructions::modify_position(&trader, address, SYMBOL, 1, 0, &[]),
+        ErrorCode::PositionAlreadyClosed,
+    );
+}
+
+#[test]
+fn positions_opened_after_a_close_get_new_addresses() {
+    let mut env = Env::new();
+    let trader = env.trader();
+
+    let first = env.open(&trader, 1, 10, 10);
+    let second = env.open(&trader, 2, 5, 10);
+    env.ok(instructions::close_position(&trader, first, SYMBOL, None, None));
+    assert_eq!(env.user(&trader).position_count, 1);
+
+    let third = env.open(&trader, 1, 10, 10);
+    assert_eq!(third, pda::position(&trader.owner, 0, 2));
+    assert_ne!(third, first);
+    assert_eq!(env.position(&first).status, 2);
+    assert_eq!(env.position(&second).status, 1);
+
+    let user = env.user(&trader);
+    assert_eq!((user.position_count, user.next_position_id), (2, 3));
+}
+
+#[test]
+fn position_mode_changes_only_without_open_positions() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    env.fails_with(instructions::set_position_mode(trader.owner, 0, 2), ErrorCode::InvalidPositionMode);
+
+    let address = env.open(&trader, 1, 10, 10);
+    env.fails_with(
+        instructions::set_position_mode(trader.owner, 0, POSITION_MODE_ONE_WAY),
+        ErrorCode::PositionModeLocked,
+    );
+
+    env.ok(instructions::close_position(&trader, address, SYMBOL, None, None));
+    env.ok(instructions::set_position_mode(trader.owner, 0, POSITION_MODE_ONE_WAY));
+    assert_eq!(env.user(&trader).position_mode, POSITION_MODE_ONE_WAY);
+    env.ok(instructions::set_position_mode(trader.owner, 0, POSITION_MODE_HEDGE));
+    assert_eq!(env.user(&trader).position_mode, POSITION_MODE_HEDGE);
+}
+
+#[test]
+fn book_positions_require_hedge_mode() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    env.ok(instructions::initialize_position_book(trader.owner, 0));
+    env.ok(instructions::set_position_mode(trader.owner, 0, POSITION_MODE_ONE_WAY));
+    env.fails_with(
+        instructions::open_book_position(&trader, &[env.mint], SYMBOL, 1, 1, 10, None, None),
+        ErrorCode::PositionModeConflict,
+    );
+
+    // An open book slot keeps the account in hedge mode.
+    env.ok(instructions::set_position_mode(trader.owner, 0, POSITION_MODE_HEDGE));
+    env.ok(instructions::open_book_position(&trader, &[env.mint], SYMBOL, 1, 1, 10, None, None));
+    env.fails_with(
+        instructions::set_position_mode(trader.owner, 0, POSITION_MODE_ONE_WAY),
+        ErrorCode::PositionModeLocked,
+    );
+}
+
+#[test]
+fn hedge_mode_keeps_both_sides_open() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    let long = env.open(&trader, 1, 10, 10);
+    let short = env.open(&trader, 2, 4, 10);
+
+    assert_eq!((env.position(&long).status, env.position(&long).size), (1, 10));
+    assert_eq!((env.position(&short).status, env.position(&short).size), (1, 4));
+    assert_eq!(env.user(&trader).position_count, 2);
+    let market = env.market();
+    assert_eq!((market.long_open_interest, market.short_open_interest), (10, 4));
+}
+
+#[test]
+fn one_way_mode_nets_opens_against_the_indexed_position() {
+    let mut env = Env::new();
+    let hedge = env.trader();
+    let trader = hedge.with_one_way();
+    let index = pda::position_index(&trader.user_account(), SYMBOL);
+    env.ok(instructions::set_position_mode(trader.owner, 0, POSITION_MODE_ONE_WAY));
+
+    env.open_fails_with(&hedge, args(1, 10, 10), ErrorCode::OpenPositionRequired);
+    let long = env.open(&trader, 1, 10, 10);
+    assert_eq!(env.svm.get::<PositionIndex>(&index).position, long);
+
+    // The open position cannot be left out to open a second one beside it.
+    env.open_fails_with(&trader, args(2, 4, 10), ErrorCode::OpenPositionRequired);
+    let ix = env.open_ix(&trader, Some(long), args(1, 5, 10));
+    env.fails_with(ix, ErrorCode::PositionModeConflict);
+
+    // An opposite open reduces the position at the mark price.
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE + 1_000));
+    env.ok(env.open_ix(&trader, Some(long), args(2, 4, 10)));
+    let position = env.position(&long);
+    assert_eq!((position.size, position.status, position.realized_pnl), (6, 1, 4_000));
+    let user = env.user(&trader);
+    assert_eq!((user.position_count, user.next_position_id, user.total_pnl), (1, 1, 4_000));
+    assert!(env.svm.account(&pda::position(&trader.owner, 0, 1)).is_none());
+    assert_eq!(env.market().long_open_interest, 6);
+
+    // Any size beyond it flips into a new position, which the index then records.
+    let ix = env.open_ix(&trader, Some(long), args(2, 10, 10));
+    let short = ix.accounts[2].pubkey;
+    env.ok(ix);
+    assert_eq!(env.position(&long).status, 2);
+    assert_eq!((env.position(&short).side, env.position(&short).size), (2, 4));
+    assert_eq!(env.svm.get::<PositionIndex>(&index).position, short);
+    let market = env.market();
+    assert_eq!((market.long_open_interest, market.short_open_interest), (0, 4));
+    assert_eq!(env.user(&trader).position_count, 1);
+
+    // Once closed, the recorded position no longer blocks a new one.
+    env.ok(instructions::close_position(&trader, short, SYMBOL, None, None));
+    env.open_fails_with(&trader, args(1, 10, 10), ErrorCode::OpenPositionRequired);
+    let ix = env.open_ix(&trader, Some(short), args(1, 10, 10));
+    let reopened = ix.accounts[2].pubkey;
+    env.ok(ix);
+    assert_eq!(env.svm.get::<PositionIndex>(&index).position, reopened);
+}
+
+#[test]
+fn transferred_positions_must_be_covered_by_the_receiver() {
+    let mut env = Env::new();
+    let sender = env.trader();
+    let receiver = env.trader();
+    let short_of_margin = env.trader_with(PRICE / 10 - 1);
+    let one_way = env.trader();
+    env.ok(instructions::set_position_mode(one_way.owner, 0, POSITION_MODE_ONE_WAY));
+    let address = env.open(&sender, 1, 10, 10);
+    let transfer = |to: &Trader, mints: &[Pubkey]| {
+        instructions::transfer_position(sender.owner, 0, to.owner, 0, address, mints)
+    };
+
+    env.fails_with(transfer(&short_of_margin, &[env.mint]), ErrorCode::InsufficientCollateral);
+    // Without its collateral account, the receiver's mint balances are not counted.
+    env.fails_with(transfer(&receiver, &[]), ErrorCode::InsufficientCollateral);
+    env.fails_with(transfer(&one_way, &[env.mint]), ErrorCode::PositionModeConflict);
+
+    env.ok(transfer(&receiver, &[env.mint]));
+    assert_eq!(env.position(&address).owner, receiver.owner);
+    let from = env.user(&sender);
+    assert_eq!((from.position_count, from.locked_collateral, from.open_notional), (0, 0, 0));
+    let to = env.user(&receiver);
+    assert_eq!((to.position_count, to.locked_collateral, to.open_notional), (1, PRICE / 10, 10 * PRICE));
+
+    env.fails_with(
+        instructions::close_position(&sender, address, SYMBOL, None, None),
+        ErrorCode::CannotModifyOthersPosition,
+    );
+    env.ok(instructions::close_position(&receiver, address, SYMBOL, None, None));
+    assert_eq!(env.user(&receiver).locked_collateral, 0);
+}
+
+#[test]
+fn close_all_flattens_every_position_at_the_mark() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    let long = env.open(&trader, 1, 10, 10);
+    let short = env.open(&trader, 2, 5, 10);
+    let other = env.trader();
+    let theirs = env.open(&other, 1, 1, 10);
+    let both = [(long, SYMBOL), (short, SYMBOL)];
+
+    env.fails_with(instructions::close_all_positions(&trader, &[], &[]), ErrorCode::PositionNotFound);
+    env.fails_with(
+        instructions::close_all_positions(&trader, &both, &[PriceBounds::default()]),
+        ErrorCode::PositionNotFound,
+    );
+    // Any failure reverts the whole batch.
+    env.fails_with(
+        instructions::close_all_positions(&trader, &[(long, SYMBOL), (theirs, SYMBOL)], &[]),
+        ErrorCode::CannotModifyOthersPosition,
+    );
+    assert_eq!(env.position(&long).status, 1);
+
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE + 1_000_000));
+    env.ok(instructions::close_all_positions(&trader, &both, &[]));
+    let (long, short) = (env.position(&long), env.position(&short));
+    assert_eq!((long.status, long.close_price, long.realized_pnl), (2, PRICE + 1_000_000, 10_000_000));
+    assert_eq!((short.status, short.close_price, short.realized_pnl), (2, PRICE + 1_000_000, -5_000_000));
+
+    let user = env.user(&trader);
+    assert_eq!((user.position_count, user.locked_collateral, user.open_notional), (0, 0, 0));
+    assert_eq!((user.total_pnl, user.total_collateral), (5_000_000, 5_000_000));
+    let market = env.market();
+    assert_eq!((market.long_open_interest, market.short_open_interest), (1, 0));
+
+    env.fails_with(instructions::close_all_positions(&trader, &both, &[]), ErrorCode::PositionAlreadyClosed);
+}
+
+#[test]
+fn short_positions_profit_when_price_falls() {
+    let mut env = Env::new();
+    let trader = env.trader();
+
+    let address = env.open(&trader, 2, 4, 5);
+    assert_eq!(env.position(&address).liquidation_price, PRICE * 110 / 100);
+    assert_eq!(env.market().short_open_interest, 4);
+
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE - 2_000_000));
+    env.ok(instructions::close_position(&trader, address, SYMBOL, None, None));
+    assert_eq!(env.position(&address).realized_pnl, 8_000_000);
+    assert_eq!(env.user(&trader).total_pnl, 8_000_000);
+    assert_eq!(env.market().short_open_interest, 0);
+}
+
+#[test]
+fn slippage_bounds_apply_to_the_mark_price() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    let address = env.open(&trader, 1, 10, 10);
+    env.ok(instructions::initialize_position_book(trader.owner, 0));
+    env.ok(instructions::open_book_position(&trader, &[env.mint], SYMBOL, 1, 10, 10, None, None));
+
+    // Trades execute at the mark, which has moved past the bounds.
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE + 2));
+    let max = Some(PRICE + 1);
+    env.open_fails_with(&trader, OpenPositionArgs { max_price: max, ..args(1, 10, 10) }, ErrorCode::SlippageExceeded);
+    env.fails_with(
+        instructions::close_position(&trader, address, SYMBOL, max, None),
+        ErrorCode::SlippageExceeded,
+    );
+    env.fails_with(
+        instructions::close_all_positions(
+            &trader,
+            &[(address, SYMBOL)],
+            &[PriceBounds { max_price: max, min_price: None }],
+        ),
+        ErrorCode::SlippageExceeded,
+    );
+    env.fails_with(
+        instructions::open_book_position(&trader, &[env.mint], SYMBOL, 1, 10, 10, max, None),
+        ErrorCode::SlippageExceeded,
+    );
+    env.fails_with(
+        instructions::close_book_position(&trader, SYMBOL, 0, None, Some(PRICE + 3)),
+        ErrorCode::SlippageExceeded,
+    );
+    assert_eq!(env.position(&address).status, 1);
+
+    let exact = Some(PRICE + 2);
+    env.ok(instructions::close_position(&trader, address, SYMBOL, exact, exact));
+    env.ok(instructions::close_book_position(&trader, SYMBOL, 0, exact, exact));
+    assert_eq!(env.position(&address).close_price, PRICE + 2);
+
+    // Without a fresh mark nothing can trade.
+    env.svm.warp(MAX_PRICE_AGE + 1);
+    env.open_fails_with(&trader, args(1, 10, 10), ErrorCode::StalePrice);
+}
+
+#[test]
+fn liquidation_requires_mark_price_past_liquidation_price() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    let liquidator = env.svm.wallet();
+    let address = env.open(&trader, 1, 1, 10);
+    let liquidate = instructions::liquidate_position(liquidator, address, trader.user_account(), SYMBOL);
+
+    env.fails_with(liquidate.clone(), ErrorCode::PositionNotLiquidatable);
+    assert_eq!(env.position(&address).status, 1);
+
+    env.svm.warp(120);
+    let liquidation_price = env.position(&address).liquidation_price;
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, liquidation_price));
+    env.ok(liquidate.clone());
+
+    let position = env.position(&address);
+    assert_eq!(position.status, 3);
+    assert_eq!(position.close_price, liquidation_price);
+    assert_eq!(position.realized_pnl, -((PRICE / 10) as i64));
+    assert_eq!(position.closed_at, GENESIS_TIMESTAMP + 120);
+
+    let user = env.user(&trader);
+    assert_eq!(user.position_count, 0);
+    assert_eq!(user.locked_collateral, 0);
+    assert_eq!(user.open_notional, 0);
+    assert_eq!(user.total_pnl, -((PRICE / 10) as i64));
+    assert_eq!(env.market().long_open_interest, 0);
+
+    env.fails_with(liquidate, ErrorCode::PositionAlreadyClosed);
+}
+
+#[test]
+fn realized_pnl_is_settled_into_collateral() {
+    let mut env = Env::new();
+    let trader = env.trader_with(PRICE / 10);
+
+    let address = env.open(&trader, 1, 10, 10);
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE + 1_000_000));
+    env.ok(instructions::close_position(&trader, address, SYMBOL, None, None));
+    let user = env.user(&trader);
+    assert_eq!((user.total_collateral, user.collateral_debt), (10_000_000, 0));
+
+    // A loss draws on settled profit first and books the rest as debt, which
+    // counts against the deposited collateral.
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE));
+    let address = env.open(&trader, 1, 10, 10);
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE - 2_000_000));
+    env.ok(instructions::close_position(&trader, address, SYMBOL, None, None));
+    let user = env.user(&trader);
+    assert_eq!((user.total_collateral, user.collateral_debt), (0, 10_000_000));
+    assert_eq!(user.total_pnl, -10_000_000);
+    env.open_fails_with(&trader, args(1, 10, 10), ErrorCode::InsufficientCollateral);
+
+    let token_account = env.svm.token_account(env.mint, trader.owner, PRICE);
+    env.ok(instructions::deposit_collateral(trader.owner, 0, env.mint, token_account, PRICE));
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE));
+    let address = env.open(&trader, 2, 1, 1);
+    env.svm.warp(60);
+    let liquidation_price = env.position(&address).liquidation_price;
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, liquidation_price));
+    let liquidator = env.svm.wallet();
+    env.ok(instructions::liquidate_position(liquidator, address, trader.user_account(), SYMBOL));
+    let user = env.user(&trader);
+    assert_eq!(user.collateral_debt, 10_000_000 + PRICE);
+    assert_eq!(user.total_pnl, -10_000_000 - PRICE as i64);
+}
+
+#[test]
+fn withdrawals_keep_equity_non_negative() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    let token_account = env.svm.token_account(env.mint, trader.owner, 0);
+    let first = env.open(&trader, 1, 10, 10);
+    let second = env.open(&trader, 1, 10, 10);
+    let positions = [(first, SYMBOL), (second, SYMBOL)];
+    let mint = env.mint;
+    let withdraw = |amount, positions: &[(Pubkey, &str)]| {
+        instructions::withdraw_collateral(trader.owner, 0, mint, token_account, amount, positions, None, &[])
+    };
+
+    // Every open position must be passed, once.
+    env.fails_with(withdraw(1, &positions[..1]), ErrorCode::PositionNotFound);
+    env.fails_with(withdraw(1, &[(first, SYMBOL), (first, SYMBOL)]), ErrorCode::DuplicatePosition);
+
+    // Down a quarter of the price on each of 20 units, equity is the deposit less
+    // that loss and the locked margin.
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE * 3 / 4));
+    let available = DEPOSIT - 20 * (PRICE / 4) - 2 * (PRICE / 10);
+    env.fails_with(withdraw(available + 1, &positions), ErrorCode::InsufficientCollateral);
+    env.ok(withdraw(available, &positions));
+    assert_eq!(env.svm.token_balance(&token_account), available);
+}
+
+#[test]
+fn stale_prices_are_rejected() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    let liquidator = env.svm.wallet();
+    let address = env.open(&trader, 1, 1, 10);
+
+    env.fails_with(instructions::update_config(env.admin, u64::MAX, 0, 0, 0), ErrorCode::InvalidPriceAge);
+
+    env.svm.warp(MAX_PRICE_AGE + 1);
+    env.fails_with(
+        instructions::liquidate_position(liquidator, address, trader.user_account(), SYMBOL),
+        ErrorCode::StalePrice,
+    );
+    env.open_fails_with(&trader, args(1, 1, 10), ErrorCode::StalePrice);
+
+    env.ok(instructions::update_collateral_price(env.admin, env.mint, 1_000_000));
+    env.open_fails_with(&trader, args(1, 1, 10), ErrorCode::StalePrice);
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE));
+    env.open(&trader, 1, 1, 10);
+}
+
+#[test]
+fn bad_debt_is_deleveraged_against_profitable_positions_in_rank_order() {
+    let mut env = Env::new();
+    let (longs, shorts) = (env.trader(), env.trader());
+    let liquidator = env.svm.wallet();
+    // Bankruptcy prices 99 and 95: entry less margin per unit.
+    let high = env.open(&longs, 1, 10, 10);
+    let low = env.open(&longs, 1, 10, 2);
+    let first = env.open(&shorts, 2, 10, 10);
+    let second = env.open(&shorts, 2, 10, 2);
+    let candidates = [(first, shorts.user_account()), (second, shorts.user_account())];
+
+    env.fails_with(instructions::auto_deleverage(env.admin, SYMBOL, &candidates), ErrorCode::NoBadDebt);
+
+    let mark = 80_000_000;
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, mark));
+    env.ok(instructions::liquidate_position(liquidator, high, longs.user_account(), SYMBOL));
+    env.ok(instructions::liquidate_position(liquidator, low, longs.user_account(), SYMBOL));
+
+    // Each loss beyond its margin becomes bad debt, priced by the debt-weighted average.
+    let (high_debt, low_debt) = (20 * PRICE / 10 - PRICE / 10, 20 * PRICE / 10 - PRICE / 2);
+    let bankruptcy_price = ((high_debt as u128 * 99_000_000 + low_debt as u128 * 95_000_000)
+        / (high_debt + low_debt) as u128) as u64;
+    assert_eq!(env.market().long_bad_debt, high_debt + low_debt);
+    assert_eq!(env.market().long_bankruptcy_price, bankruptcy_price);
+
+    // Only the config authority picks the candidates, and they must be ranked.
+    let outsider = env.svm.wallet();
+    env.fails_with(instructions::auto_deleverage(outsider, SYMBOL, &candidates), ErrorCode::Unauthorized);
+    let reversed = [candidates[1], candidates[0]];
+    env.fails_with(instructions::auto_deleverage(env.admin, SYMBOL, &reversed), ErrorCode::InvalidAdlRanking);
+    env.fails_with(
+        instructions::auto_deleverage(env.admin, SYMBOL, &[(high, longs.user_account())]),
+        ErrorCode::PositionAlreadyClosed,
+    );
+
+    env.ok(instructions::auto_deleverage(env.admin, SYMBOL, &candidates));
+    let market = env.market();
+    assert_eq!((market.long_bad_debt, market.short_open_interest), (0, 0));
+
+    for address in [first, second] {
+        let position = env.position(&address);
+        assert_eq!((position.status, position.size, position.close_price), (2, 0, bankruptcy_price));
+        assert_eq!(position.realized_pnl, ((PRICE - bankruptcy_price) * 10) as i64);
+    }
+    let user = env.user(&shorts);
+    assert_eq!((user.position_count, user.locked_collateral, user.open_notional), (0, 0, 0));
+    assert_eq!(user.total_pnl, ((PRICE - bankruptcy_price) * 20) as i64);
+}
+
+#[test]
+fn invalid_open_parameters_are_rejected() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    let before = env.user(&trader);
+
+    let cases = [
+        (args(1, 0, 10), ErrorCode::InvalidPositionSize),
+        (args(1, 10, 0), ErrorCode::InvalidLeverageValue),
+        (args(1, 10, 101), ErrorCode::InvalidLeverageValue),
+        (args(3, 10, 10), ErrorCode::InvalidSide),
+        (OpenPositionArgs { max_price: Some(PRICE - 1), ..args(1, 10, 10) }, ErrorCode::SlippageExceeded),
+        (OpenPositionArgs { min_price: Some(PRICE + 1), ..args(2, 10, 10) }, ErrorCode::SlippageExceeded),
+        (OpenPositionArgs { reduce_only: true, ..args(1, 10, 10) }, ErrorCode::ReduceOnlyViolation),
+    ];
+    for (args, code) in cases {
+        env.open_fails_with(&trader, args, code);
+    }
+
+    let after = env.user(&trader);
+    assert_eq!((after.position_count, after.locked_collateral), (before.position_count, before.locked_collateral));
+    assert!(env.svm.account(&pda::position(&trader.owner, 0, 0)).is_none());
+}
+
+#[test]
+fn margin_is_checked_against_collateral() {
+    let mut env = Env::new();
+
+    let unfunded = env.trader_with(0);
+    env.open_fails_with(&unfunded, args(1, 10, 10), ErrorCode::InsufficientCollateral);
+
+    // Every non-zero balance must be valued through its `CollateralMint`.
+    let trader = env.trader_with(PRICE / 10);
+    env.fails_with(
+        instructions::open_position(&trader, 0, None, &[], args(1, 10, 10)),
+        ErrorCode::CollateralMintNotFound,
+    );
+
+    let address = env.open(&trader, 1, 10, 10);
+    env.fails_with(
+        instructions::modify_position(&trader, address, SYMBOL, 0, 1, &[env.mint]),
+        ErrorCode::InsufficientCollateral,
+    );
+    env.open_fails_with(&trader, args(2, 10, 10), ErrorCode::InsufficientCollateral);
+}
+
+#[test]
+fn fees_are_charged_against_collateral_and_shared_with_referrers() {
+    let mut env = Env::new();
+    env.ok(instructions::update_config(env.admin, u64::MAX, 100, 5_000, MAX_PRICE_AGE));
+    let referrer = env.trader_with(0);
+    let trader = env.trader();
+    env.ok(instructions::register_referral(trader.owner, 0, referrer.owner));
+    env.open_fails_with(&trader, args(1, 10, 10), ErrorCode::ReferralRequired);
+    let trader = trader.with_referral();
+
+    // 1% of 10 × PRICE, half of it rebated. With nothing settled yet, the fee is
+    // booked against the deposit.
+    let address = env.open(&trader, 1, 10, 10);
+    let user = env.user(&trader);
+    assert_eq!((user.total_collateral, user.collateral_debt), (0, 10_000_000));
+    let referral: Referral = env.svm.get(&pda::referral(&trader.user_account()));
+    assert_eq!((referral.unclaimed_rebates, referral.referee_volume), (5_000_000, 10 * PRICE));
+    let config: Config = env.svm.get(&pda::config());
+    assert_eq!(config.insurance_fund, 5_000_000);
+
+    let without_referral = Trader::owner(trader.owner, 0).with_collateral_account();
+    env.fails_with(
+        instructions::close_position(&without_referral, address, SYMBOL, None, None),
+        ErrorCode::ReferralRequired,
+    );
+
+    // The profit repays the debt before the closing fee is taken from it.
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE + 2_000_000));
+    env.ok(instructions::close_position(&trader, address, SYMBOL, None, None));
+    let user = env.user(&trader);
+    assert_eq!((user.total_collateral, user.collateral_debt), (0, 200_000));
+    assert_eq!(user.total_pnl, 20_000_000);
+
+    env.ok(instructions::claim_rebates(referrer.owner, 0, trader.user_account()));
+    assert_eq!(env.user(&referrer).total_collateral, 5_000_000 + 5_100_000);
+
+    // The fee counts against the collateral that must cover the margin.
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE));
+    let exact = env.trader_with(PRICE / 10);
+    env.open_fails_with(&exact, args(1, 10, 10), ErrorCode::InsufficientCollateral);
+    let funded = env.trader_with(PRICE / 10 + 10_000_000);
+    let address = env.open(&funded, 1, 10, 10);
+    env.fails_with(
+        instructions::modify_position(&funded, address, SYMBOL, 1, 0, &[env.mint]),
+        ErrorCode::InsufficientCollateral,
+    );
+}
+
+#[test]
+fn positions_cannot_be_changed_by_other_users() {
+    let mut env = Env::new();
+    let victim = env.trader();
+    let attacker = env.trader();
+    let address = env.open(&victim, 1, 10, 10);
+
+    env.fails_with(
+        instructions::close_position(&attacker, address, SYMBOL, None, None),
+        ErrorCode::CannotModifyOthersPosition,
+    );
+    env.fails_with(
+        instructions::modify_position(&attacker, address, SYMBOL, -5, 0, &[]),
+        ErrorCode::CannotModifyOthersPosition,
+    );
+    assert_eq!(env.position(&address).size, 10);
+}
+
+#[test]
+fn delegates_expire_with_the_clock() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    let delegate_wallet = env.svm.wallet();
+    env.ok(instructions::create_delegate(
+        trader.owner,
+        0,
+        delegate_wallet,
+        PERMISSION_ALL,
+        u64::MAX,
+        GENESIS_TIMESTAMP + 60,
+    ));
+
+    let delegate = Trader::delegate(delegate_wallet, trader.owner, 0).with_collateral_account();
+    let address = env.open(&delegate, 1, 10, 10);
+    assert_eq!(env.position(&address).owner, trader.owner);
+
+    env.svm.warp(59);
+    env.ok(instructions::modify_position(&delegate, address, SYMBOL, 1, 0, &[env.mint]));
+
+    env.svm.warp(1);
+    env.fails_with(
+        instructions::close_position(&delegate, address, SYMBOL, None, None),
+        ErrorCode::DelegateExpired,
+    );
+    env.ok(instructions::close_position(&trader, address, SYMBOL, None, None));
+}
+
+#[test]
+fn delegate_notional_limit_covers_everything_it_opens() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    let delegate_wallet = env.svm.wallet();
+    env.ok(instructions::create_delegate(
+        trader.owner,
+        0,
+        delegate_wallet,
+        PERMISSION_ALL,
+        15 * PRICE,
+        GENESIS_TIMESTAMP + 60,
+    ));
+    let delegate = Trader::delegate(delegate_wallet, trader.owner, 0).with_collateral_account();
+    let delegate_account = pda::delegate(&trader.user_account(), &delegate_wallet);
+
+    let first = env.open(&delegate, 1, 10, 10);
+    env.open_fails_with(&delegate, args(1, 10, 10), ErrorCode::DelegateNotionalExceeded);
+    let second = env.open(&delegate, 2, 4, 10);
+    env.fails_with(
+        instructions::modify_position(&delegate, second, SYMBOL, 2, 0, &[env.mint]),
+        ErrorCode::DelegateNotionalExceeded,
+    );
+    env.ok(instructions::modify_position(&delegate, second, SYMBOL, 1, 0, &[env.mint]));
+    let used: Delegate = env.svm.get(&delegate_account);
+    assert_eq!(used.notional_used, 15 * PRICE);
+
+    // Closing does not free up the limit; the owner is not bound by it.
+    env.ok(instructions::close_position(&delegate, first, SYMBOL, None, None));
+    env.open_fails_with(&delegate, args(1, 1, 10), ErrorCode::DelegateNotionalExceeded);
+    env.open(&trader, 1, 10, 10);
+}
+
+#[test]
+fn arithmetic_edges_fail_with_error_codes() {
+    let mut env = Env::new();
+    let trader = env.trader();
+
+    env.open_fails_with(&trader, args(1, u64::MAX, 10), ErrorCode::CalculationOverflow);
+
+    // The largest size whose notional fits in a u64.
+    let size = u64::MAX / PRICE;
+    let address = env.open(&trader, 1, size, 10);
+    assert_eq!(env.user(&trader).open_notional, size * PRICE);
+
+    env.fails_with(
+        instructions::modify_position(&trader, address, SYMBOL, 1, 0, &[]),
+        ErrorCode::CalculationOverflow,
+    );
+    env.fails_with(
+        instructions::modify_position(&trader, address, SYMBOL, -(size as i64) - 1, 0, &[]),
+        ErrorCode::CalculationUnderflow,
+    );
+    env.fails_with(
+        instructions::modify_position(&trader, address, SYMBOL, i64::MIN, 0, &[]),
+        ErrorCode::CalculationUnderflow,
+    );
+    env.fails_with(
+        instructions::modify_position(&trader, address, SYMBOL, 0, -((PRICE / 10) as i64) - 1, &[]),
+        ErrorCode::CannotReduceMargin,
+    );
+    env.fails_with(
+        instructions::modify_position(&trader, address, SYMBOL, 0, i64::MIN, &[]),
+        ErrorCode::CannotReduceMargin,
+    );
+    env.open_fails_with(&trader, args(2, size, 10), ErrorCode::CalculationOverflow);
+}
+
+#[test]
+fn health_checks_require_every_open_position() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    let long = env.open(&trader, 1, 10, 10);
+    let short = env.open(&trader, 2, 5, 10);
+    let user_account = trader.user_account();
+    let mints = [env.mint];
+
+    env.ok(instructions::check_positions_health(user_account, &[(long, SYMBOL), (short, SYMBOL)], None, true, &mints));
+    env.fails_with(
+        instructions::check_positions_health(user_account, &[(long, SYMBOL)], None, true, &mints),
+        ErrorCode::PositionNotFound,
+    );
+    env.fails_with(
+        instructions::check_positions_health(user_account, &[(long, SYMBOL), (long, SYMBOL)], None, true, &mints),
+        ErrorCode::DuplicatePosition,
+    );
+    // Collateral is valued like a withdrawal, so every balance needs its mint.
+    env.fails_with(
+        instructions::check_positions_health(user_account, &[(long, SYMBOL), (short, SYMBOL)], None, true, &[]),
+        ErrorCode::CollateralMintNotFound,
+    );
+
+    // The book check counts the `Position` accounts' margins along with its slots.
+    env.ok(instructions::initialize_position_book(trader.owner, 0));
+    env.ok(instructions::open_book_position(&trader, &mints, SYMBOL, 1, 4, 10, None, None));
+    let positions = [(long, SYMBOL), (short, SYMBOL)];
+    env.ok(instructions::check_health(user_account, &positions, &[SYMBOL], true, &mints));
+    env.fails_with(
+        instructions::check_health(user_account, &[], &[SYMBOL], true, &mints),
+        ErrorCode::PositionNotFound,
+    );
+    env.fails_with(
+        instructions::check_positions_health(user_account, &positions, None, true, &mints),
+        ErrorCode::LockedCollateralMismatch,
+    );
+
+    env.svm.warp(MAX_PRICE_AGE + 1);
+    env.fails_with(
+        instructions::check_health(user_account, &positions, &[SYMBOL], true, &mints),
+        ErrorCode::StalePrice,
+    );
+}
+
+#[test]
+fn verify_user_matches_the_off_chain_check() {
+    let mut env = Env::new();
+    let trader = env.trader();
+    let first = env.open(&trader, 1, 10, 10);
+    let second = env.open(&trader, 2, 5, 10);
+    env.ok(instructions::close_position(&trader, first, SYMBOL, None, None));
+
+    env.ok(env.verify_ix(&trader, &[first, second]));
+    env.fails_with(env.verify_ix(&trader, &[first]), ErrorCode::LockedCollateralMismatch);
+    env.fails_with(env.verify_ix(&trader, &[second, second]), ErrorCode::DuplicatePosition);
+    env.fails_with(
+        instructions::verify_user(trader.user_account(), &[second], false, true, &[]),
+        ErrorCode::CollateralMintNotFound,
+    );
+
+    let other = env.trader();
+    let foreign = env.open(&other, 1, 1, 10);
+    env.fails_with(env.verify_ix(&trader, &[second, foreign]), ErrorCode::CannotModifyOthersPosition);
+
+    let positions = [env.position(&first), env.position(&second)];
+    let collateral: CollateralAccount = env.svm.get(&pda::collateral_account(&trader.user_account()));
+    let mints: [CollateralMint; 1] = [env.svm.get(&pda::collateral_mint(&env.mint))];
+    let check = |user: &UserAccount| invariants::verify_user(user, &positions, None, Some(&collateral), &mints).unwrap();
+    assert!(check(&env.user(&trader)).is_empty());
+
+    let mut user = env.user(&trader);
+    user.position_count += 1;
+    user.locked_collateral = DEPOSIT + 1;
+    env.svm.set_program_account(trader.user_account(), &user);
+    env.fails_with(env.verify_ix(&trader, &[first, second]), ErrorCode::LockedCollateralMismatch);
+    assert_eq!(
+        check(&user),
+        vec![
+            InvariantViolation::LockedCollateralMismatch {
+                locked_collateral: DEPOSIT + 1,
+                open_margin: (PRICE / 10) as u128,
+            },
+            InvariantViolation::PositionCountMismatch { position_count: 2, open_positions: 1 },
+            InvariantViolation::LockedCollateralExceedsCollateral {
+                locked_collateral: DEPOSIT + 1,
+                collateral_value: DEPOSIT,
+            },
+        ]
+    );
+}
+
+/// Encodes `state` with its discriminator in a v1 layout.
+fn v1_data<T: AnchorSerialize>(discriminator: &[u8], state: &T) -> Vec<u8> {
+    let mut data = discriminator.to_vec();
+    state.serialize(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn migrated_v1_positions_close_against_rebuilt_totals() {
+    let mut env = Env::new();
+    let owner = env.svm.wallet();
+    let trader = Trader::owner(owner, UserAccount::LEGACY_SUB_ACCOUNT_ID);
+    let find = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &position_management::ID);
+    let (user_address, user_bump) = find(&[b"user", owner.as_ref()]);
+    let (position, position_bump) = find(&[b"position", owner.as_ref(), &0u32.to_le_bytes()]);
+    let margin = PRICE / 10;
+    let user = UserAccountV1 {
+        owner,
+        bump: user_bump,
+        total_collateral: DEPOSIT,
+        locked_collateral: margin,
+        position_count: 1,
+        total_pnl: 0,
+        created_at: GENESIS_TIMESTAMP,
+        last_activity: GENESIS_TIMESTAMP,
+    };
+    let open = PositionV1 {
+        owner,
+        symbol: utils::symbol_bytes(SYMBOL),
+        bump: position_bump,
+        side: 1,
+        size: 10,
+        entry_price: PRICE,
+        leverage: 10,
+        status: 1,
+        margin,
+        liquidation_price: PRICE * 9 / 10,
+        mark_price: PRICE,
+        unrealized_pnl: 0,
+        realized_pnl: 0,
+        opened_at: GENESIS_TIMESTAMP,
+        closed_at: 0,
+        close_price: 0,
+    };
+    env.svm.set_program_data(user_address, v1_data(UserAccount::DISCRIMINATOR, &user));
+    env.svm.set_program_data(position, v1_data(Position::DISCRIMINATOR, &open));
+
+    // An open position is migrated after its user, adding its notional and open
+    // interest, which v1 never tracked.
+    env.fails_with(instructions::migrate_account(env.admin, position), ErrorCode::MigrationAccountsRequired);
+    env.ok(instructions::migrate_account(env.admin, user_address));
+    assert_eq!(trader.user_account(), user_address);
+    env.ok(instructions::migrate_open_position(env.admin, position, owner, SYMBOL));
+    assert_eq!(env.user(&trader).open_notional, 10 * PRICE);
+    assert_eq!(env.market().long_open_interest, 10);
+
+    env.ok(instructions::update_mark_price(env.admin, SYMBOL, PRICE + 1_000_000));
+    env.ok(instructions::close_position(&trader, position, SYMBOL, None, None));
+    assert_eq!(env.position(&position).realized_pnl, 10_000_000);
+    let user = env.user(&trader);
+    assert_eq!((user.position_count, user.locked_collateral, user.open_notional), (0, 0, 0));
+    assert_eq!(user.total_collateral, DEPOSIT + 10_000_000);
+    assert_eq!(env.market().long_open_interest, 0);
+
+    // The legacy address passes the checks that derive user accounts from
+    // their sub-account, and no new account can claim the legacy sub-account.
+    env.ok(instructions::initialize_user(owner, 0));
+    env.ok(instructions::transfer_collateral(owner, UserAccount::LEGACY_SUB_ACCOUNT_ID, 0, 1_000));
+    assert_eq!(env.svm.get::<UserAccount>(&pda::user_account(&owner, 0)).total_collateral, 1_000);
+    let other = env.svm.wallet();
+    let mut ix = instructions::initialize_user(other, UserAccount::LEGACY_SUB_ACCOUNT_ID);
+    ix.accounts[1].pubkey = find(&[b"user", other.as_ref(), &u16::MAX.to_le_bytes()]).0;
+    env.fails_with(ix, ErrorCode::InvalidSubAccount);
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Open { side: u8, size: u64, leverage: u16 },
+    Modify { index: usize, size_delta: i64, margin_delta: i64 },
+    Close { index: usize },
+    SetMarkPrice(u64),
+    Liquidate { index: usize },
+}
+
+fn delta() -> impl Strategy<Value = i64> {
+    prop_oneof![4 => -1_000_000i64..1_000_000, 1 => any::<i64>()]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        (1u8..=2, prop_oneof![1u64..1_000, any::<u64>()], 0u16..=120)
+            .prop_map(|(side, size, leverage)| Op::Open { side, size, leverage }),
+        (any::<usize>(), delta(), delta())
+            .prop_map(|(index, size_delta, margin_delta)| Op::Modify { index, size_delta, margin_delta }),
+        any::<usize>().prop_map(|index| Op::Close { index }),
+        (PRICE / 2..PRICE * 2).prop_map(Op::SetMarkPrice),
+        any::<usize>().prop_map(|index| Op::Liquidate { index }),
+    ]
+}
+
+fn fails(code: ErrorCode) -> std::result::Result<(), ProgramError> {
+    Err(ProgramError::Custom(u32::from(code)))
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    /// Whatever sequence of instructions runs, each either succeeds or fails with
+    /// an error its inputs explain, and the user's and market's totals stay equal
+    /// to the sums over the open positions.
+    #[test]
+    fn totals_match_open_positions(ops in prop::collection::vec(op(), 1..24)) {
+        let mut env = Env::new();
+        let trader = env.trader();
+        let liquidator = env.svm.wallet();
+        let mut positions = Vec::new();
+
+        for op in ops {
+            let pick = |index: usize| positions.get(index % positions.len().max(1)).copied();
+            let (result, expected) = match op {
+                Op::Open { side, size, leverage } => {
+                    let ix = env.open_ix(&trader, None, args(side, size, leverage));
+                    let address = ix.accounts[2].pubkey;
+                    let result = env.svm.process(&ix);
+                    if result.is_ok() {
+                        positions.push(address);
+                    }
+                    let expected = if (1..=100).contains(&leverage) {
+                        vec![Ok(()), fails(ErrorCode::CalculationOverflow), fails(ErrorCode::InsufficientCollateral)]
+                    } else {
+                        vec![fails(ErrorCode::InvalidLeverageValue)]
+                    };
+                    (result, expected)
+                }
+                Op::Modify { index, size_delta, margin_delta } => {
+                    let Some(address) = pick(index) else { continue };
+                    let open = env.position(&address).status == 1;
+                    let result = env.svm.process(&instructions::modify_position(
+                        &trader, address, SYMBOL, size_delta, margin_delta, &[env.mint],
+                    ));
+                    let expected = if open {
+                        vec![
+                            Ok(()),
+                            fails(ErrorCode::CalculationOverflow),
+                            fails(ErrorCode::CalculationUnderflow),
+                            fails(ErrorCode::CannotReduceMargin),
+                            fails(ErrorCode::InsufficientCollateral),
+                        ]
+                    } else {
+                        vec![fails(ErrorCode::PositionAlreadyClosed)]
+                    };
+                    (result, expected)
+                }
+                Op::Close { index } => {
+                    let Some(address) = pick(index) else { continue };
+                    let open = env.position(&address).status == 1;
+                    let result =
+                        env.svm.process(&instructions::close_position(&trader, address, SYMBOL, None, None));
+                    let expected = if !open {
+                        vec![fails(ErrorCode::PositionAlreadyClosed)]
+                    } else {
+                        vec![Ok(()), fails(ErrorCode::CalculationOverflow)]
+                    };
+                    (result, expected)
+                }
+                Op::SetMarkPrice(price) => {
+                    (env.svm.process(&instructions::update_mark_price(env.admin, SYMBOL, price)), vec![Ok(())])
+                }
+                Op::Liquidate { index } => {
+                    let Some(address) = pick(index) else { continue };
+                    let position = env.position(&address);
+                    let mark_price = env.market().mark_price;
+                    let liquidatable = if position.side == 1 {
+                        mark_price <= position.liquidation_price
+                    } else {
+                        mark_price >= position.liquidation_price
+                    };
+                    let result = env.svm.process(&instructions::liquidate_position(
+                        liquidator, address, trader.user_account(), SYMBOL,
+                    ));
+                    let expected = if position.status != 1 {
+                        vec![fails(ErrorCode::PositionAlreadyClosed)]
+                    } else if !liquidatable {
+                        vec![fails(ErrorCode::PositionNotLiquidatable)]
+                    } else {
+                        vec![Ok(()), fails(ErrorCode::CalculationOverflow)]
+                    };
+                    (result, expected)
+                }
+            };
+            prop_assert!(expected.contains(&result), "{:?} returned {:?}, expected one of {:?}", op, result, expected);
+
+            let open: Vec<Position> =
+                positions.iter().map(|address| env.position(address)).filter(|p| p.status == 1).collect();
+            let user = env.user(&trader);
+            let market = env.market();
+            let sum = |f: fn(&Position) -> u128| open.iter().map(f).sum::<u128>();
+
+            prop_assert_eq!(user.locked_collateral as u128, sum(|p| p.margin as u128));
+            prop_assert_eq!(user.open_notional as u128, sum(|p| p.size as u128 * p.entry_price as u128));
+            prop_assert_eq!(user.position_count as usize, open.len());
+            prop_assert_eq!(market.long_open_interest as u128, sum(|p| if p.side == 1 { p.size as u128 } else { 0 }));
+            prop_assert_eq!(market.short_open_interest as u128, sum(|p| if p.side == 2 { p.size as u128 } else { 0 }));
+
+            // A realized loss can leave the remaining margin unbacked; `verify_user`
+            // reports that shortfall and nothing else.
+            let collateral: CollateralAccount = env.svm.get(&pda::collateral_account(&trader.user_account()));
+            let mint: CollateralMint = env.svm.get(&pda::collateral_mint(&env.mint));
+            let value = utils::collateral_value(&user, Some(&collateral), &[mint]).unwrap();
+            let expected = if user.locked_collateral > value {
+                fails(ErrorCode::LockedCollateralExceedsCollateral)
+            } else {
+                Ok(())
+            };
+            prop_assert_eq!(env.svm.process(&env.verify_ix(&trader, &positions)), expected);
+        }
+    }
+}
\ No newline at end of file
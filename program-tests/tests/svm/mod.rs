@@ -0,0 +1,197 @@
+//! Bank-backed test runtime. Instructions run as signed transactions through
+//! `solana-program-test`, so `init`, system program CPIs and SPL Token transfers
+//! execute as they do on a validator.
+//!
+//! The program runs natively through its `entry` by default. Set `SBF_OUT_DIR`
+//! to the directory holding `position_management.so` (e.g. `target/deploy`
+//! after `anchor build`) to run the compiled program instead.
+
+use std::collections::HashMap;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::bpf_loader_upgradeable;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::system_program;
+use anchor_spl::token::spl_token;
+use position_management::ID;
+use solana_account::{Account, AccountSharedData};
+use solana_compute_budget_interface::ComputeBudgetInstruction;
+use solana_keypair::Keypair;
+use solana_program_test::tokio::runtime::{Builder, Runtime};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+use solana_transaction_error::TransactionError;
+
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+const MAX_COMPUTE_UNITS: u32 = 1_400_000;
+
+/// Unix timestamp of the clock when an `Svm` is created.
+pub const GENESIS_TIMESTAMP: i64 = 1_700_000_000;
+
+/// Anchor's `entry` ties the account slice to the accounts' own lifetime, which
+/// the runtime's entrypoint type does not; the slice is leaked to satisfy it.
+fn entry(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(accounts.to_vec().into_boxed_slice());
+    position_management::entry(program_id, accounts, data)
+}
+
+pub struct Svm {
+    runtime: Runtime,
+    context: ProgramTestContext,
+    signers: HashMap<Pubkey, Keypair>,
+    transactions: u32,
+}
+
+impl Svm {
+    pub fn new() -> Self {
+        let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+        let program = ProgramTest::new("position_management", ID, processor!(entry));
+        let context = runtime.block_on(program.start_with_context());
+
+        let svm = Svm { runtime, context, signers: HashMap::new(), transactions: 0 };
+        let mut clock = svm.clock();
+        clock.unix_timestamp = GENESIS_TIMESTAMP;
+        svm.context.set_sysvar(&clock);
+        svm
+    }
+
+    pub fn set_account(&mut self, address: Pubkey, account: Account) {
+        self.context.set_account(&address, &AccountSharedData::from(account));
+    }
+
+    /// Stores `state` as a rent-exempt account owned by the program, for states
+    /// no sequence of instructions reaches.
+    pub fn set_program_account<T: AccountSerialize>(&mut self, address: Pubkey, state: &T) {
+        let mut data = Vec::new();
+        state.try_serialize(&mut data).unwrap();
+        self.set_program_data(address, data);
+    }
+
+    /// Stores `data` verbatim as a rent-exempt account owned by the program,
+    /// such as one in a layout no instruction writes any more.
+    pub fn set_program_data(&mut self, address: Pubkey, data: Vec<u8>) {
+        self.set_account(address, rent_exempt(data, ID));
+    }
+
+    /// Stores the program's `ProgramData` account with `authority` as its
+    /// upgrade authority, as a deploy would.
+    pub fn set_upgrade_authority(&mut self, authority: Pubkey) {
+        // Bincode of `UpgradeableLoaderState::ProgramData { slot: 0, upgrade_authority_address: Some(authority) }`.
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(authority.as_ref());
+        let address = Pubkey::find_program_address(&[ID.as_ref()], &bpf_loader_upgradeable::ID).0;
+        self.set_account(address, rent_exempt(data, bpf_loader_upgradeable::ID));
+    }
+
+    pub fn account(&self, address: &Pubkey) -> Option<Account> {
+        self.runtime.block_on(self.context.banks_client.get_account(*address)).unwrap()
+    }
+
+    /// Deserializes a program account, panicking if it is missing or malformed.
+    pub fn get<T: AccountDeserialize>(&self, address: &Pubkey) -> T {
+        let account = self.account(address).unwrap_or_else(|| panic!("account {} not found", address));
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    /// A funded system account to sign and pay with.
+    pub fn wallet(&mut self) -> Pubkey {
+        let keypair = Keypair::new();
+        let wallet = keypair.pubkey();
+        self.set_account(
+            wallet,
+            Account { lamports: 100 * LAMPORTS_PER_SOL, owner: system_program::ID, ..Account::default() },
+        );
+        self.signers.insert(wallet, keypair);
+        wallet
+    }
+
+    /// An initialized SPL Token mint with no mint authority.
+    pub fn token_mint(&mut self, decimals: u8) -> Pubkey {
+        let address = Pubkey::new_unique();
+        let mint = spl_token::state::Mint { decimals, is_initialized: true, ..Default::default() };
+        self.set_account(address, packed(&mint));
+        address
+    }
+
+    /// A token account of `mint` owned by `owner`, holding `amount`.
+    pub fn token_account(&mut self, mint: Pubkey, owner: Pubkey, amount: u64) -> Pubkey {
+        let address = Pubkey::new_unique();
+        let account = spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        self.set_account(address, packed(&account));
+        address
+    }
+
+    /// The amount held by the token account at `address`.
+    pub fn token_balance(&self, address: &Pubkey) -> u64 {
+        let account = self.account(address).unwrap_or_else(|| panic!("account {} not found", address));
+        spl_token::state::Account::unpack(&account.data).unwrap().amount
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.runtime.block_on(self.context.banks_client.get_sysvar::<Clock>()).unwrap()
+    }
+
+    /// Advances the clock by `seconds`, one slot per 400ms.
+    pub fn warp(&mut self, seconds: i64) {
+        let mut clock = self.clock();
+        clock.unix_timestamp += seconds;
+        clock.slot += (seconds as u64) * 5 / 2;
+        self.context.set_sysvar(&clock);
+    }
+
+    /// Executes `ix` as a single-instruction transaction signed by every account
+    /// marked as a signer, each of which must be a `wallet`. A failed transaction
+    /// leaves every account as it was.
+    pub fn process(&mut self, ix: &Instruction) -> std::result::Result<(), ProgramError> {
+        let transaction = self.transaction(ix);
+        match self.runtime.block_on(self.context.banks_client.process_transaction(transaction)) {
+            Ok(()) => Ok(()),
+            Err(BanksClientError::TransactionError(TransactionError::InstructionError(_, err))) => Err(
+                ProgramError::try_from(err.clone()).unwrap_or_else(|_| panic!("instruction failed: {}", err)),
+            ),
+            Err(err) => panic!("transaction failed outside the program: {}", err),
+        }
+    }
+
+    fn transaction(&mut self, ix: &Instruction) -> Transaction {
+        // Identical transactions under one blockhash would be rejected as
+        // duplicates, so each carries a distinct compute unit limit.
+        self.transactions += 1;
+        let budget = ComputeBudgetInstruction::set_compute_unit_limit(MAX_COMPUTE_UNITS - self.transactions);
+
+        let mut signers: Vec<&Keypair> = vec![&self.context.payer];
+        for meta in ix.accounts.iter().filter(|meta| meta.is_signer) {
+            let keypair = self.signers.get(&meta.pubkey).unwrap_or_else(|| panic!("{} is not a wallet", meta.pubkey));
+            if !signers.iter().any(|signer| signer.pubkey() == meta.pubkey) {
+                signers.push(keypair);
+            }
+        }
+        Transaction::new_signed_with_payer(
+            &[budget, ix.clone()],
+            Some(&self.context.payer.pubkey()),
+            &signers,
+            self.context.last_blockhash,
+        )
+    }
+}
+
+fn rent_exempt(data: Vec<u8>, owner: Pubkey) -> Account {
+    Account { lamports: Rent::default().minimum_balance(data.len()), data, owner, executable: false, rent_epoch: 0 }
+}
+
+fn packed<T: Pack>(state: &T) -> Account {
+    let mut data = vec![0; T::LEN];
+    state.pack_into_slice(&mut data);
+    rent_exempt(data, spl_token::ID)
+}
\ No newline at end of file
@@ -34,4 +34,121 @@ pub enum ErrorCode {
 
     #[msg("Position not found")]
     PositionNotFound = 3001,
+
+    #[msg("Invalid side")]
+    InvalidSide = 4003,
+
+    #[msg("Market does not match position symbol")]
+    MarketMismatch = 3003,
+
+    #[msg("Long open interest cap exceeded")]
+    LongOpenInterestCapExceeded = 6001,
+
+    #[msg("Short open interest cap exceeded")]
+    ShortOpenInterestCapExceeded = 6002,
+
+    #[msg("User notional cap exceeded")]
+    UserNotionalCapExceeded = 6003,
+
+    #[msg("Position is not liquidatable")]
+    PositionNotLiquidatable = 3005,
+
+    #[msg("No bad debt to deleverage")]
+    NoBadDebt = 6004,
+
+    #[msg("ADL candidates are not in ranking order")]
+    InvalidAdlRanking = 6005,
+
+    #[msg("Position is not eligible for auto-deleveraging")]
+    InvalidAdlCandidate = 6006,
+
+    #[msg("Delegate has expired")]
+    DelegateExpired = 5003,
+
+    #[msg("Delegate lacks the required permission")]
+    DelegatePermissionDenied = 5004,
+
+    #[msg("Delegate notional limit exceeded")]
+    DelegateNotionalExceeded = 5005,
+
+    #[msg("Invalid delegate permissions")]
+    InvalidDelegatePermissions = 5006,
+
+    #[msg("Invalid sub-account")]
+    InvalidSubAccount = 5007,
+
+    #[msg("Invalid fee configuration")]
+    InvalidFeeConfig = 4004,
+
+    #[msg("No rebates to claim")]
+    NoRebatesToClaim = 2004,
+
+    #[msg("Cannot refer yourself")]
+    SelfReferral = 5008,
+
+    #[msg("The user's referral account must be passed")]
+    ReferralRequired = 5009,
+
+    #[msg("Account is already on the current layout")]
+    AccountAlreadyMigrated = 8001,
+
+    #[msg("Unknown account layout")]
+    UnknownAccountLayout = 8002,
+
+    #[msg("Open positions migrate with their migrated user account and market")]
+    MigrationAccountsRequired = 8003,
+
+    #[msg("Position book is full")]
+    PositionBookFull = 3006,
+
+    #[msg("Invalid position book slot")]
+    InvalidBookSlot = 3007,
+
+    #[msg("Execution price outside slippage limits")]
+    SlippageExceeded = 4005,
+
+    #[msg("Collateral haircut must be below 100%")]
+    InvalidHaircut = 4006,
+
+    #[msg("No free collateral slot for this mint")]
+    CollateralSlotsFull = 2005,
+
+    #[msg("Collateral mint account missing")]
+    CollateralMintNotFound = 2006,
+
+    #[msg("Collateral amount must be greater than zero")]
+    InvalidCollateralAmount = 2007,
+
+    #[msg("Invalid position mode")]
+    InvalidPositionMode = 4007,
+
+    #[msg("Position mode cannot change with open positions")]
+    PositionModeLocked = 3008,
+
+    #[msg("One-way mode holds a single position per symbol")]
+    PositionModeConflict = 3009,
+
+    #[msg("Reduce-only order would increase the position")]
+    ReduceOnlyViolation = 3010,
+
+    #[msg("Locked collateral does not match open position margins")]
+    LockedCollateralMismatch = 2008,
+
+    #[msg("Locked collateral exceeds collateral value")]
+    LockedCollateralExceedsCollateral = 2009,
+
+    #[msg("Position count does not match open positions")]
+    PositionCountMismatch = 3011,
+
+    #[msg("Position passed more than once")]
+    DuplicatePosition = 3012,
+
+    #[msg("One-way mode requires the symbol's position index and open position")]
+    OpenPositionRequired = 3013,
+
+    #[msg("Price is older than the maximum price age")]
+    StalePrice = 4008,
+
+    #[msg("Maximum price age must be positive")]
+    InvalidPriceAge = 4009,
 }
\ No newline at end of file
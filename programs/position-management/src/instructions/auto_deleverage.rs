@@ -0,0 +1,130 @@
+use anchor_lang::prelude::*;
+use crate::state::{Config, Market, Position, UserAccount};
+use crate::errors::ErrorCode;
+use crate::utils::{adl_score, notional_value, pro_rata, unrealized_pnl};
+
+/// Remaining accounts are `(position, user_account)` pairs of the side opposing
+/// the bankrupt positions, ordered by descending `adl_score` with ties broken by
+/// ascending position address. Only the order of the supplied pairs can be
+/// checked here, so only the config authority may choose them.
+#[derive(Accounts)]
+pub struct AutoDeleverage<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.symbol.as_ref()],
+        bump = market.bump
+    )]
+    pub market: Account<'info, Market>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, AutoDeleverage<'info>>,
+) -> Result<()> {
+    let market = &mut ctx.accounts.market;
+
+    let mark_price = market.fresh_mark_price(ctx.accounts.config.max_price_age)?;
+
+    // Bad debt left by bankrupt longs is absorbed by profitable shorts, and vice versa.
+    let (target_side, mut bad_debt, bankruptcy_price) = if market.long_bad_debt > 0 {
+        (2u8, market.long_bad_debt, market.long_bankruptcy_price)
+    } else if market.short_bad_debt > 0 {
+        (1u8, market.short_bad_debt, market.short_bankruptcy_price)
+    } else {
+        return err!(ErrorCode::NoBadDebt);
+    };
+
+    // PnL each unit gives up by closing at the bankruptcy price instead of the mark.
+    let per_unit = if target_side == 2 {
+        bankruptcy_price.checked_sub(mark_price)
+    } else {
+        mark_price.checked_sub(bankruptcy_price)
+    }
+    .filter(|d| *d > 0)
+    .ok_or(ErrorCode::InvalidPrice)?;
+
+    let candidates = ctx.remaining_accounts;
+    require!(!candidates.is_empty() && candidates.len() % 2 == 0, ErrorCode::InvalidAdlCandidate);
+
+    let now = Clock::get()?.unix_timestamp;
+    let mut previous: Option<(u128, Pubkey)> = None;
+
+    for pair in candidates.chunks(2) {
+        if bad_debt == 0 {
+            break;
+        }
+
+        let mut position = Account::<Position>::try_from(&pair[0])?;
+        let mut user = Account::<UserAccount>::try_from(&pair[1])?;
+
+        require_eq!(position.status, 1, ErrorCode::PositionAlreadyClosed);
+        require!(position.symbol == market.symbol, ErrorCode::MarketMismatch);
+        require_eq!(position.side, target_side, ErrorCode::InvalidAdlCandidate);
+        require!(
+            user.owner == position.owner && user.sub_account_id == position.sub_account_id,
+            ErrorCode::CannotModifyOthersPosition
+        );
+        require_keys_eq!(pair[1].key(), user.address()?, ErrorCode::Unauthorized);
+
+        let pnl = unrealized_pnl(position.side, position.entry_price, mark_price, position.size)?;
+        require!(pnl > 0, ErrorCode::InvalidAdlCandidate);
+
+        let score = adl_score(pnl, position.margin, position.leverage);
+        if let Some((prev_score, prev_key)) = previous {
+            require!(
+                score < prev_score || (score == prev_score && pair[0].key() > prev_key),
+                ErrorCode::InvalidAdlRanking
+            );
+        }
+        previous = Some((score, pair[0].key()));
+
+        let reduce = bad_debt.div_ceil(per_unit).min(position.size);
+        let absorbed = ((reduce as u128) * (per_unit as u128)).min(bad_debt as u128) as u64;
+        bad_debt -= absorbed;
+
+        let realized = unrealized_pnl(position.side, position.entry_price, bankruptcy_price, reduce)?;
+        let released = pro_rata(position.margin, reduce, position.size)?;
+
+        market.remove_open_interest(position.side, reduce)?;
+
+        position.size -= reduce;
+        position.margin -= released;
+        position.realized_pnl = position.realized_pnl.checked_add(realized).ok_or(ErrorCode::CalculationOverflow)?;
+
+        let notional = notional_value(reduce, position.entry_price)?;
+        user.open_notional = user.open_notional.checked_sub(notional).ok_or(ErrorCode::CalculationUnderflow)?;
+        user.locked_collateral = user.locked_collateral.checked_sub(released).ok_or(ErrorCode::CalculationUnderflow)?;
+        user.settle_pnl(realized)?;
+        user.last_activity = now;
+
+        if position.size == 0 {
+            position.status = 2;
+            position.close_price = bankruptcy_price;
+            position.closed_at = now;
+            user.position_count = user.position_count.checked_sub(1).ok_or(ErrorCode::CalculationUnderflow)?;
+        }
+
+        // Persist before the next pair so a user account listed twice sees these updates.
+        position.exit(&crate::ID)?;
+        user.exit(&crate::ID)?;
+
+        msg!("Position deleveraged by {}", reduce);
+    }
+
+    if target_side == 2 {
+        market.long_bad_debt = bad_debt;
+    } else {
+        market.short_bad_debt = bad_debt;
+    }
+
+    msg!("Auto-deleverage complete, remaining bad debt: {}", bad_debt);
+    Ok(())
+}
\ No newline at end of file
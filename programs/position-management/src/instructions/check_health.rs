@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+use crate::state::{CollateralAccount, Config, PositionBook, UserAccount};
+use crate::utils::{account_health, AccountHealth};
+
+/// Remaining accounts are `(position, market)` pairs for every open `Position`,
+/// then the `Market` of every symbol held in the book, then the `CollateralMint`s
+/// of every non-zero collateral balance.
+#[derive(Accounts)]
+pub struct CheckHealth<'info> {
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(has_one = user_account)]
+    pub position_book: AccountLoader<'info, PositionBook>,
+
+    #[account(
+        seeds = [b"collateral", user_account.key().as_ref()],
+        bump = collateral_account.bump
+    )]
+    pub collateral_account: Option<Account<'info, CollateralAccount>>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CheckHealth<'info>>,
+) -> Result<AccountHealth> {
+    account_health(
+        &ctx.accounts.user_account,
+        Some(&ctx.accounts.position_book),
+        ctx.accounts.collateral_account.as_deref(),
+        ctx.remaining_accounts,
+        ctx.accounts.config.max_price_age,
+    )
+}
\ No newline at end of file
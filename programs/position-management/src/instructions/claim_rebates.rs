@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+use crate::state::{Referral, UserAccount};
+use crate::errors::ErrorCode;
+
+#[derive(Accounts)]
+pub struct ClaimRebates<'info> {
+    pub referrer: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = referrer @ ErrorCode::Unauthorized,
+        seeds = [b"referral", referral.referee.as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Account<'info, Referral>,
+
+    #[account(
+        mut,
+        constraint = referrer_account.owner == referrer.key() @ ErrorCode::Unauthorized
+    )]
+    pub referrer_account: Account<'info, UserAccount>,
+}
+
+pub fn handler(ctx: Context<ClaimRebates>) -> Result<()> {
+    let referral = &mut ctx.accounts.referral;
+    let account = &mut ctx.accounts.referrer_account;
+
+    let amount = referral.unclaimed_rebates;
+    require!(amount > 0, ErrorCode::NoRebatesToClaim);
+
+    referral.unclaimed_rebates = 0;
+    account.credit_collateral(amount)?;
+    account.last_activity = Clock::get()?.unix_timestamp;
+
+    msg!("Rebates claimed: {}", amount);
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+use crate::state::{Config, Delegate, Market, Position, Referral, UserAccount};
+use crate::errors::ErrorCode;
+use crate::utils::{charge_trading_fee, check_slippage, notional_value, unrealized_pnl, verify_authority, PERMISSION_CLOSE};
+
+/// Slippage bounds on the mark price one position is closed at.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PriceBounds {
+    pub max_price: Option<u64>,
+    pub min_price: Option<u64>,
+}
+
+/// Remaining accounts are `(position, market)` pairs, all writable. Each
+/// position is closed at its market's mark price. `price_bounds` is either
+/// empty or holds the bounds of each pair, in order.
+#[derive(Accounts)]
+pub struct CloseAllPositions<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        seeds = [b"delegate", user_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"referral", user_account.key().as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Option<Account<'info, Referral>>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CloseAllPositions<'info>>,
+    price_bounds: Vec<PriceBounds>,
+) -> Result<()> {
+    let user = &mut ctx.accounts.user_account;
+
+    verify_authority(user, &ctx.accounts.authority.key(), ctx.accounts.delegate.as_deref(), PERMISSION_CLOSE)?;
+
+    let pairs = ctx.remaining_accounts;
+    require!(!pairs.is_empty() && pairs.len() % 2 == 0, ErrorCode::PositionNotFound);
+    require!(price_bounds.is_empty() || price_bounds.len() == pairs.len() / 2, ErrorCode::PositionNotFound);
+
+    let now = Clock::get()?.unix_timestamp;
+
+    for (index, pair) in pairs.chunks(2).enumerate() {
+        let mut position = Account::<Position>::try_from(&pair[0])?;
+        let mut market = Account::<Market>::try_from(&pair[1])?;
+
+        require!(
+            position.owner == user.owner && position.sub_account_id == user.sub_account_id,
+            ErrorCode::CannotModifyOthersPosition
+        );
+        require_eq!(position.status, 1, ErrorCode::PositionAlreadyClosed);
+        require!(position.symbol == market.symbol, ErrorCode::MarketMismatch);
+
+        let exit_price = market.fresh_mark_price(ctx.accounts.config.max_price_age)?;
+        if let Some(bounds) = price_bounds.get(index) {
+            check_slippage(exit_price, bounds.max_price, bounds.min_price)?;
+        }
+
+        let pnl = unrealized_pnl(position.side, position.entry_price, exit_price, position.size)?;
+
+        position.status = 2;
+        position.close_price = exit_price;
+        position.realized_pnl = pnl;
+        position.closed_at = now;
+
+        market.remove_open_interest(position.side, position.size)?;
+
+        let notional = notional_value(position.size, position.entry_price)?;
+        user.open_notional = user.open_notional.checked_sub(notional).ok_or(ErrorCode::CalculationUnderflow)?;
+        user.locked_collateral = user.locked_collateral.checked_sub(position.margin).ok_or(ErrorCode::CalculationUnderflow)?;
+
+        let exit_notional = notional_value(position.size, exit_price)?;
+        user.settle_pnl(pnl)?;
+        charge_trading_fee(&mut ctx.accounts.config, user, ctx.accounts.referral.as_deref_mut(), exit_notional)?;
+        user.position_count = user.position_count.checked_sub(1).ok_or(ErrorCode::CalculationUnderflow)?;
+
+        // Persist before the next pair so a market listed twice sees these updates.
+        position.exit(&crate::ID)?;
+        market.exit(&crate::ID)?;
+
+        msg!("Position closed at {}", exit_price);
+    }
+
+    user.last_activity = now;
+
+    msg!("Closed {} positions", pairs.len() / 2);
+    Ok(())
+}
\ No newline at end of file
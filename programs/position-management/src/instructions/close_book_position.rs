@@ -0,0 +1,75 @@
+use anchor_lang::prelude::*;
+use crate::state::{Config, Delegate, Market, PositionBook, PositionSlot, Referral, UserAccount};
+use crate::errors::ErrorCode;
+use crate::utils::{charge_trading_fee, notional_value, unrealized_pnl, verify_authority, PERMISSION_CLOSE};
+
+#[derive(Accounts)]
+pub struct CloseBookPosition<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut, has_one = user_account)]
+    pub position_book: AccountLoader<'info, PositionBook>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.symbol.as_ref()],
+        bump = market.bump
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        seeds = [b"delegate", user_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    #[account(
+        mut,
+        seeds = [b"referral", user_account.key().as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Option<Account<'info, Referral>>,
+}
+
+pub fn handler(
+    ctx: Context<CloseBookPosition>,
+    slot_index: u8,
+    max_price: Option<u64>,
+    min_price: Option<u64>,
+) -> Result<()> {
+    let user = &mut ctx.accounts.user_account;
+    let market = &mut ctx.accounts.market;
+    let mut book = ctx.accounts.position_book.load_mut()?;
+
+    verify_authority(user, &ctx.accounts.authority.key(), ctx.accounts.delegate.as_deref(), PERMISSION_CLOSE)?;
+
+    let slot = *book.slots.get(slot_index as usize).ok_or(ErrorCode::InvalidBookSlot)?;
+    require!(slot.is_open(), ErrorCode::PositionAlreadyClosed);
+    require!(slot.symbol == market.symbol, ErrorCode::MarketMismatch);
+    let exit_price = market.execution_price(max_price, min_price, ctx.accounts.config.max_price_age)?;
+
+    let pnl = unrealized_pnl(slot.side, slot.entry_price, exit_price, slot.size)?;
+
+    book.slots[slot_index as usize] = PositionSlot::default();
+    book.open_count -= 1;
+
+    market.remove_open_interest(slot.side, slot.size)?;
+
+    let notional = notional_value(slot.size, slot.entry_price)?;
+    user.open_notional = user.open_notional.checked_sub(notional).ok_or(ErrorCode::CalculationUnderflow)?;
+    user.locked_collateral = user.locked_collateral.checked_sub(slot.margin).ok_or(ErrorCode::CalculationUnderflow)?;
+
+    let exit_notional = notional_value(slot.size, exit_price)?;
+    user.settle_pnl(pnl)?;
+    charge_trading_fee(&mut ctx.accounts.config, user, ctx.accounts.referral.as_deref_mut(), exit_notional)?;
+    user.last_activity = Clock::get()?.unix_timestamp;
+
+    msg!("Book position closed in slot {}", slot_index);
+    Ok(())
+}
\ No newline at end of file
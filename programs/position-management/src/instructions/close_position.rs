@@ -1,43 +1,73 @@
 use anchor_lang::prelude::*;
-use crate::state::{Position, UserAccount};
+use crate::state::{Config, Delegate, Market, Position, Referral, UserAccount};
 use crate::errors::ErrorCode;
+use crate::utils::{charge_trading_fee, notional_value, unrealized_pnl, verify_authority, PERMISSION_CLOSE};
 
 #[derive(Accounts)]
 pub struct ClosePosition<'info> {
     #[account(mut)]
-    pub owner: Signer<'info>,
+    pub authority: Signer<'info>,
 
     #[account(mut)]
     pub user_account: Account<'info, UserAccount>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = position.owner == user_account.owner
+            && position.sub_account_id == user_account.sub_account_id @ ErrorCode::CannotModifyOthersPosition
+    )]
     pub position: Account<'info, Position>,
+
+    #[account(
+        mut,
+        seeds = [b"market", position.symbol.as_ref()],
+        bump = market.bump
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        seeds = [b"delegate", user_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"referral", user_account.key().as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Option<Account<'info, Referral>>,
 }
 
-pub fn handler(
-    ctx: Context<ClosePosition>,
-    exit_price: u64,
-) -> Result<()> {
+pub fn handler(ctx: Context<ClosePosition>, max_price: Option<u64>, min_price: Option<u64>) -> Result<()> {
     let position = &mut ctx.accounts.position;
     let user = &mut ctx.accounts.user_account;
+    let market = &mut ctx.accounts.market;
+
+    verify_authority(user, &ctx.accounts.authority.key(), ctx.accounts.delegate.as_deref(), PERMISSION_CLOSE)?;
 
     require_eq!(position.status, 1, ErrorCode::PositionAlreadyClosed);
-    require_neq!(exit_price, 0, ErrorCode::InvalidPrice);
+    let exit_price = market.execution_price(max_price, min_price, ctx.accounts.config.max_price_age)?;
 
-    let is_long = position.side == 1;
-    let pnl = if is_long {
-        ((exit_price as i64) - (position.entry_price as i64)) * (position.size as i64)
-    } else {
-        ((position.entry_price as i64) - (exit_price as i64)) * (position.size as i64)
-    };
+    let pnl = unrealized_pnl(position.side, position.entry_price, exit_price, position.size)?;
 
     position.status = 2;
     position.close_price = exit_price;
     position.realized_pnl = pnl;
     position.closed_at = Clock::get()?.unix_timestamp;
 
+    market.remove_open_interest(position.side, position.size)?;
+
+    let notional = notional_value(position.size, position.entry_price)?;
+    user.open_notional = user.open_notional.checked_sub(notional).ok_or(ErrorCode::CalculationUnderflow)?;
     user.locked_collateral = user.locked_collateral.checked_sub(position.margin).ok_or(ErrorCode::CalculationUnderflow)?;
-    user.total_pnl = (user.total_pnl as i128).checked_add(pnl as i128).ok_or(ErrorCode::CalculationOverflow)? as i64;
+
+    let exit_notional = notional_value(position.size, exit_price)?;
+    user.settle_pnl(pnl)?;
+    charge_trading_fee(&mut ctx.accounts.config, user, ctx.accounts.referral.as_deref_mut(), exit_notional)?;
     user.position_count = user.position_count.checked_sub(1).ok_or(ErrorCode::CalculationUnderflow)?;
     user.last_activity = Clock::get()?.unix_timestamp;
 
@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+use crate::state::{Delegate, UserAccount};
+use crate::errors::ErrorCode;
+use crate::utils::PERMISSION_ALL;
+
+#[derive(Accounts)]
+#[instruction(delegate: Pubkey)]
+pub struct CreateDelegate<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(has_one = owner @ ErrorCode::Unauthorized)]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = Delegate::LEN,
+        seeds = [b"delegate", user_account.key().as_ref(), delegate.as_ref()],
+        bump
+    )]
+    pub delegate_account: Account<'info, Delegate>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(
+    ctx: Context<CreateDelegate>,
+    delegate: Pubkey,
+    permissions: u8,
+    max_notional: u64,
+    expires_at: i64,
+) -> Result<()> {
+    require!(
+        permissions != 0 && permissions & !PERMISSION_ALL == 0,
+        ErrorCode::InvalidDelegatePermissions
+    );
+
+    let now = Clock::get()?.unix_timestamp;
+    require!(expires_at > now, ErrorCode::DelegateExpired);
+
+    let delegate_account = &mut ctx.accounts.delegate_account;
+    delegate_account.user_account = ctx.accounts.user_account.key();
+    delegate_account.delegate = delegate;
+    delegate_account.bump = ctx.bumps.delegate_account;
+    delegate_account.permissions = permissions;
+    delegate_account.max_notional = max_notional;
+    delegate_account.notional_used = 0;
+    delegate_account.expires_at = expires_at;
+    delegate_account.created_at = now;
+
+    msg!("Delegate created");
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,64 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, TransferChecked};
+use crate::state::{CollateralAccount, CollateralMint, UserAccount};
+use crate::errors::ErrorCode;
+
+#[derive(Accounts)]
+pub struct DepositCollateral<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(mut, has_one = owner @ ErrorCode::Unauthorized)]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", user_account.key().as_ref()],
+        bump = collateral_account.bump
+    )]
+    pub collateral_account: Account<'info, CollateralAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_mint", mint.key().as_ref()],
+        bump = collateral_mint.bump,
+        has_one = mint,
+        has_one = vault
+    )]
+    pub collateral_mint: Account<'info, CollateralMint>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = mint, token::authority = owner)]
+    pub owner_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+pub fn handler(ctx: Context<DepositCollateral>, amount: u64) -> Result<()> {
+    require!(amount > 0, ErrorCode::InvalidCollateralAmount);
+
+    token::transfer_checked(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.owner_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.vault.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(),
+            },
+        ),
+        amount,
+        ctx.accounts.mint.decimals,
+    )?;
+
+    let collateral_mint = &mut ctx.accounts.collateral_mint;
+    ctx.accounts.collateral_account.deposit(collateral_mint.mint, amount)?;
+    collateral_mint.total_deposits = collateral_mint.total_deposits.checked_add(amount).ok_or(ErrorCode::CalculationOverflow)?;
+    ctx.accounts.user_account.last_activity = Clock::get()?.unix_timestamp;
+
+    msg!("Collateral deposited");
+    Ok(())
+}
\ No newline at end of file
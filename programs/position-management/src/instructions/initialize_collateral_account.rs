@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+use crate::state::{CollateralAccount, CollateralBalance, UserAccount};
+use crate::errors::ErrorCode;
+use crate::utils::MAX_COLLATERAL_MINTS;
+
+#[derive(Accounts)]
+pub struct InitializeCollateralAccount<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(has_one = owner @ ErrorCode::Unauthorized)]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = CollateralAccount::LEN,
+        seeds = [b"collateral", user_account.key().as_ref()],
+        bump
+    )]
+    pub collateral_account: Account<'info, CollateralAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<InitializeCollateralAccount>) -> Result<()> {
+    let collateral = &mut ctx.accounts.collateral_account;
+
+    collateral.user_account = ctx.accounts.user_account.key();
+    collateral.bump = ctx.bumps.collateral_account;
+    collateral.balances = [CollateralBalance::default(); MAX_COLLATERAL_MINTS];
+
+    msg!("Collateral account initialized");
+    Ok(())
+}
\ No newline at end of file
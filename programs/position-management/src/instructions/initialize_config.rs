@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::bpf_loader_upgradeable;
+use crate::state::Config;
+use crate::errors::ErrorCode;
+
+/// Only the program's upgrade authority can create the config, so whoever
+/// deploys the program decides who administers it.
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Config::LEN,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(authority.key()) @ ErrorCode::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(
+    ctx: Context<InitializeConfig>,
+    max_user_notional: u64,
+    fee_bps: u16,
+    referral_share_bps: u16,
+    max_price_age: i64,
+) -> Result<()> {
+    Config::validate_fees(fee_bps, referral_share_bps)?;
+    Config::validate_price_age(max_price_age)?;
+
+    let config = &mut ctx.accounts.config;
+
+    config.authority = ctx.accounts.authority.key();
+    config.bump = ctx.bumps.config;
+    config.max_user_notional = max_user_notional;
+    config.insurance_fund = 0;
+    config.fee_bps = fee_bps;
+    config.referral_share_bps = referral_share_bps;
+    config.max_price_age = max_price_age;
+
+    msg!("Config initialized");
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,52 @@
+use anchor_lang::prelude::*;
+use crate::state::{Config, Market};
+use crate::errors::ErrorCode;
+use crate::utils::symbol_bytes;
+
+#[derive(Accounts)]
+#[instruction(symbol: String)]
+pub struct InitializeMarket<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Market::LEN,
+        seeds = [b"market", symbol_bytes(&symbol).as_ref()],
+        bump
+    )]
+    pub market: Account<'info, Market>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(
+    ctx: Context<InitializeMarket>,
+    symbol: String,
+    max_open_interest: u64,
+) -> Result<()> {
+    let market = &mut ctx.accounts.market;
+
+    market.symbol = symbol_bytes(&symbol);
+    market.bump = ctx.bumps.market;
+    market.long_open_interest = 0;
+    market.short_open_interest = 0;
+    market.max_open_interest = max_open_interest;
+    market.mark_price = 0;
+    market.last_price_update = 0;
+    market.long_bad_debt = 0;
+    market.long_bankruptcy_price = 0;
+    market.short_bad_debt = 0;
+    market.short_bankruptcy_price = 0;
+
+    msg!("Market initialized");
+    Ok(())
+}
\ No newline at end of file
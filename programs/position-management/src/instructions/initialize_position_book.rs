@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+use crate::state::{PositionBook, UserAccount};
+use crate::errors::ErrorCode;
+
+#[derive(Accounts)]
+pub struct InitializePositionBook<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(has_one = owner @ ErrorCode::Unauthorized)]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = PositionBook::LEN,
+        seeds = [b"position_book", user_account.key().as_ref()],
+        bump
+    )]
+    pub position_book: AccountLoader<'info, PositionBook>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<InitializePositionBook>) -> Result<()> {
+    let mut book = ctx.accounts.position_book.load_init()?;
+
+    book.user_account = ctx.accounts.user_account.key();
+    book.bump = ctx.bumps.position_book;
+    book.open_count = 0;
+
+    msg!("Position book initialized");
+    Ok(())
+}
\ No newline at end of file
@@ -1,7 +1,9 @@
 use anchor_lang::prelude::*;
 use crate::state::UserAccount;
+use crate::errors::ErrorCode;
 
 #[derive(Accounts)]
+#[instruction(sub_account_id: u16)]
 pub struct InitializeUser<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
@@ -10,7 +12,7 @@ pub struct InitializeUser<'info> {
         init,
         payer = owner,
         space = UserAccount::LEN,
-        seeds = [b"user", owner.key().as_ref()],
+        seeds = [b"user", owner.key().as_ref(), &sub_account_id.to_le_bytes()],
         bump
     )]
     pub user_account: Account<'info, UserAccount>,
@@ -18,16 +20,23 @@ pub struct InitializeUser<'info> {
     pub system_program: Program<'info, System>,
 }
 
-pub fn handler(ctx: Context<InitializeUser>) -> Result<()> {
+pub fn handler(ctx: Context<InitializeUser>, sub_account_id: u16) -> Result<()> {
     let user = &mut ctx.accounts.user_account;
     let owner = &ctx.accounts.owner;
+    require_neq!(sub_account_id, UserAccount::LEGACY_SUB_ACCOUNT_ID, ErrorCode::InvalidSubAccount);
 
+    user.version = UserAccount::VERSION;
     user.owner = owner.key();
+    user.sub_account_id = sub_account_id;
     user.bump = ctx.bumps.user_account;
     user.total_collateral = 0;
     user.locked_collateral = 0;
     user.position_count = 0;
+    user.next_position_id = 0;
+    user.open_notional = 0;
     user.total_pnl = 0;
+    user.collateral_debt = 0;
+    user.has_referral = false;
     user.created_at = Clock::get()?.unix_timestamp;
     user.last_activity = Clock::get()?.unix_timestamp;
 
@@ -0,0 +1,63 @@
+use anchor_lang::prelude::*;
+use crate::state::{Config, Market, PositionBook, PositionSlot, UserAccount};
+use crate::errors::ErrorCode;
+use crate::utils::{liquidation_loss, notional_value, settle_liquidation};
+
+#[derive(Accounts)]
+pub struct LiquidateBookPosition<'info> {
+    #[account(mut)]
+    pub liquidator: Signer<'info>,
+
+    #[account(mut)]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut, has_one = user_account)]
+    pub position_book: AccountLoader<'info, PositionBook>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.symbol.as_ref()],
+        bump = market.bump
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+pub fn handler(
+    ctx: Context<LiquidateBookPosition>,
+    slot_index: u8,
+) -> Result<()> {
+    let user = &mut ctx.accounts.user_account;
+    let market = &mut ctx.accounts.market;
+    let mut book = ctx.accounts.position_book.load_mut()?;
+
+    let slot = *book.slots.get(slot_index as usize).ok_or(ErrorCode::InvalidBookSlot)?;
+    require!(slot.is_open(), ErrorCode::PositionAlreadyClosed);
+    require!(slot.symbol == market.symbol, ErrorCode::MarketMismatch);
+
+    settle_liquidation(
+        &mut ctx.accounts.config,
+        market,
+        slot.side,
+        slot.entry_price,
+        slot.size,
+        slot.margin,
+        slot.liquidation_price,
+    )?;
+
+    book.slots[slot_index as usize] = PositionSlot::default();
+    book.open_count -= 1;
+
+    market.remove_open_interest(slot.side, slot.size)?;
+
+    let realized_pnl = liquidation_loss(slot.margin)?;
+    let notional = notional_value(slot.size, slot.entry_price)?;
+    user.open_notional = user.open_notional.checked_sub(notional).ok_or(ErrorCode::CalculationUnderflow)?;
+    user.locked_collateral = user.locked_collateral.checked_sub(slot.margin).ok_or(ErrorCode::CalculationUnderflow)?;
+    user.settle_pnl(realized_pnl)?;
+
+    msg!("Book position liquidated in slot {}", slot_index);
+    Ok(())
+}
\ No newline at end of file
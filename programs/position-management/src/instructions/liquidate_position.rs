@@ -1,17 +1,32 @@
 use anchor_lang::prelude::*;
-use crate::state::{Position, UserAccount};
+use crate::state::{Config, Market, Position, UserAccount};
 use crate::errors::ErrorCode;
+use crate::utils::{liquidation_loss, notional_value, settle_liquidation};
 
 #[derive(Accounts)]
 pub struct LiquidatePosition<'info> {
     #[account(mut)]
     pub liquidator: Signer<'info>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = position.owner == user_account.owner
+            && position.sub_account_id == user_account.sub_account_id @ ErrorCode::CannotModifyOthersPosition
+    )]
     pub position: Account<'info, Position>,
 
     #[account(mut)]
     pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"market", position.symbol.as_ref()],
+        bump = market.bump
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
 }
 
 pub fn handler(
@@ -19,13 +34,35 @@ pub fn handler(
 ) -> Result<()> {
     let position = &mut ctx.accounts.position;
     let user = &mut ctx.accounts.user_account;
+    let market = &mut ctx.accounts.market;
+    let config = &mut ctx.accounts.config;
 
     require_eq!(position.status, 1, ErrorCode::PositionAlreadyClosed);
 
+    let mark_price = settle_liquidation(
+        config,
+        market,
+        position.side,
+        position.entry_price,
+        position.size,
+        position.margin,
+        position.liquidation_price,
+    )?;
+
+    let realized_pnl = liquidation_loss(position.margin)?;
+
     position.status = 3;
+    position.mark_price = mark_price;
+    position.close_price = mark_price;
+    position.realized_pnl = realized_pnl;
     position.closed_at = Clock::get()?.unix_timestamp;
 
+    market.remove_open_interest(position.side, position.size)?;
+
+    let notional = notional_value(position.size, position.entry_price)?;
+    user.open_notional = user.open_notional.checked_sub(notional).ok_or(ErrorCode::CalculationUnderflow)?;
     user.locked_collateral = user.locked_collateral.checked_sub(position.margin).ok_or(ErrorCode::CalculationUnderflow)?;
+    user.settle_pnl(realized_pnl)?;
     user.position_count = user.position_count.checked_sub(1).ok_or(ErrorCode::CalculationUnderflow)?;
 
     msg!("Position liquidated");
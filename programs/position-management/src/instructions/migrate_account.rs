@@ -0,0 +1,74 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use crate::errors::ErrorCode;
+use crate::state::{upgrade_account_data, Market, Position, UserAccount};
+use crate::utils::notional_value;
+
+#[derive(Accounts)]
+pub struct MigrateAccount<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: ownership is checked here and the layout is validated by `upgrade_account_data`.
+    #[account(mut, owner = crate::ID @ ErrorCode::UnknownAccountLayout)]
+    pub account: UncheckedAccount<'info>,
+
+    /// The migrated owner of an open position, whose notional it is added to.
+    #[account(mut)]
+    pub user_account: Option<Account<'info, UserAccount>>,
+
+    /// The market of an open position, whose open interest it is added to.
+    #[account(mut)]
+    pub market: Option<Account<'info, Market>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<MigrateAccount>) -> Result<()> {
+    let account = ctx.accounts.account.to_account_info();
+
+    let upgraded = upgrade_account_data(&account.try_borrow_data()?)?;
+
+    // v1 never tracked notional or open interest, so an open position adds
+    // its own as it is migrated and closes can release them again.
+    if upgraded.starts_with(Position::DISCRIMINATOR) {
+        let position = Position::try_deserialize(&mut upgraded.as_slice())?;
+        if position.status == 1 {
+            let (Some(user), Some(market)) = (ctx.accounts.user_account.as_mut(), ctx.accounts.market.as_mut()) else {
+                return err!(ErrorCode::MigrationAccountsRequired);
+            };
+            require!(
+                user.owner == position.owner && user.sub_account_id == position.sub_account_id,
+                ErrorCode::CannotModifyOthersPosition
+            );
+            require!(market.symbol == position.symbol, ErrorCode::MarketMismatch);
+
+            let notional = notional_value(position.size, position.entry_price)?;
+            user.open_notional = user.open_notional.checked_add(notional).ok_or(ErrorCode::CalculationOverflow)?;
+            market.add_open_interest(position.side, position.size)?;
+        }
+    }
+
+    let rent_due = Rent::get()?
+        .minimum_balance(upgraded.len())
+        .saturating_sub(account.lamports());
+    if rent_due > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            rent_due,
+        )?;
+    }
+
+    account.resize(upgraded.len())?;
+    account.try_borrow_mut_data()?.copy_from_slice(&upgraded);
+
+    msg!("Account migrated");
+    Ok(())
+}
\ No newline at end of file
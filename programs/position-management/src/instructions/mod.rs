@@ -1,13 +1,69 @@
 #![allow(ambiguous_glob_reexports)]
 
+pub mod initialize_config;
+pub mod update_config;
+pub mod initialize_market;
+pub mod update_market;
+pub mod update_mark_price;
 pub mod initialize_user;
+pub mod transfer_collateral;
+pub mod create_delegate;
+pub mod revoke_delegate;
+pub mod register_referral;
+pub mod claim_rebates;
 pub mod open_position;
 pub mod modify_position;
 pub mod close_position;
+pub mod close_all_positions;
+pub mod transfer_position;
 pub mod liquidate_position;
+pub mod auto_deleverage;
+pub mod migrate_account;
+pub mod initialize_position_book;
+pub mod open_book_position;
+pub mod close_book_position;
+pub mod liquidate_book_position;
+pub mod check_health;
+pub mod check_positions_health;
+pub mod register_collateral_mint;
+pub mod update_collateral_mint;
+pub mod update_collateral_price;
+pub mod initialize_collateral_account;
+pub mod deposit_collateral;
+pub mod withdraw_collateral;
+pub mod set_position_mode;
+pub mod verify_user;
 
+pub use initialize_config::*;
+pub use update_config::*;
+pub use initialize_market::*;
+pub use update_market::*;
+pub use update_mark_price::*;
 pub use initialize_user::*;
+pub use transfer_collateral::*;
+pub use create_delegate::*;
+pub use revoke_delegate::*;
+pub use register_referral::*;
+pub use claim_rebates::*;
 pub use open_position::*;
 pub use modify_position::*;
 pub use close_position::*;
-pub use liquidate_position::*;
\ No newline at end of file
+pub use close_all_positions::*;
+pub use transfer_position::*;
+pub use liquidate_position::*;
+pub use auto_deleverage::*;
+pub use migrate_account::*;
+pub use initialize_position_book::*;
+pub use open_book_position::*;
+pub use close_book_position::*;
+pub use liquidate_book_position::*;
+pub use check_health::*;
+pub use check_positions_health::*;
+pub use register_collateral_mint::*;
+pub use update_collateral_mint::*;
+pub use update_collateral_price::*;
+pub use initialize_collateral_account::*;
+pub use deposit_collateral::*;
+pub use withdraw_collateral::*;
+pub use set_position_mode::*;
+pub use verify_user::*;
\ No newline at end of file
@@ -1,17 +1,57 @@
 use anchor_lang::prelude::*;
-use crate::state::{Position, UserAccount};
+use crate::state::{CollateralAccount, Config, Delegate, Market, Position, Referral, UserAccount};
 use crate::errors::ErrorCode;
+use crate::utils::{
+    apply_delta, charge_trading_fee, check_margin, load_collateral_mints, notional_value, verify_authority,
+    PERMISSION_MODIFY,
+};
 
+/// Remaining accounts are the `CollateralMint`s of the user's non-zero collateral
+/// balances, used to value them after a resize fee or added margin.
 #[derive(Accounts)]
 pub struct ModifyPosition<'info> {
     #[account(mut)]
-    pub owner: Signer<'info>,
+    pub authority: Signer<'info>,
 
     #[account(mut)]
     pub user_account: Account<'info, UserAccount>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = position.owner == user_account.owner
+            && position.sub_account_id == user_account.sub_account_id @ ErrorCode::CannotModifyOthersPosition
+    )]
     pub position: Account<'info, Position>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"market", position.symbol.as_ref()],
+        bump = market.bump
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        seeds = [b"delegate", user_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    #[account(
+        mut,
+        seeds = [b"referral", user_account.key().as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Option<Account<'info, Referral>>,
+
+    #[account(
+        seeds = [b"collateral", user_account.key().as_ref()],
+        bump = collateral_account.bump
+    )]
+    pub collateral_account: Option<Account<'info, CollateralAccount>>,
 }
 
 pub fn handler(
@@ -21,26 +61,63 @@ pub fn handler(
 ) -> Result<()> {
     let position = &mut ctx.accounts.position;
     let user = &mut ctx.accounts.user_account;
+    let market = &mut ctx.accounts.market;
+    let delegate = ctx.accounts.delegate.as_deref_mut();
+
+    verify_authority(user, &ctx.accounts.authority.key(), delegate.as_deref(), PERMISSION_MODIFY)?;
 
     require_eq!(position.status, 1, ErrorCode::PositionAlreadyClosed);
 
     if size_delta != 0 {
-        let new_size = if size_delta > 0 {
-            position.size.checked_add(size_delta as u64).ok_or(ErrorCode::CalculationOverflow)?
+        let new_size = apply_delta(position.size, size_delta)?;
+
+        let old_notional = notional_value(position.size, position.entry_price)?;
+        let new_notional = notional_value(new_size, position.entry_price)?;
+        if size_delta > 0 {
+            if let Some(delegate) = delegate {
+                delegate.use_notional(new_notional - old_notional)?;
+            }
+            let open_notional = user.open_notional
+                .checked_add(new_notional - old_notional)
+                .ok_or(ErrorCode::CalculationOverflow)?;
+            require!(open_notional <= ctx.accounts.config.max_user_notional, ErrorCode::UserNotionalCapExceeded);
+            market.add_open_interest(position.side, size_delta.unsigned_abs())?;
+            user.open_notional = open_notional;
         } else {
-            position.size.checked_sub((-size_delta) as u64).ok_or(ErrorCode::CalculationUnderflow)?
-        };
+            market.remove_open_interest(position.side, size_delta.unsigned_abs())?;
+            user.open_notional = user.open_notional
+                .checked_sub(old_notional - new_notional)
+                .ok_or(ErrorCode::CalculationUnderflow)?;
+        }
+        charge_trading_fee(
+            &mut ctx.accounts.config,
+            user,
+            ctx.accounts.referral.as_deref_mut(),
+            new_notional.abs_diff(old_notional),
+        )?;
         position.size = new_size;
     }
 
     if margin_delta != 0 {
         if margin_delta > 0 {
-            position.margin = position.margin.checked_add(margin_delta as u64).ok_or(ErrorCode::CalculationOverflow)?;
-            user.locked_collateral = user.locked_collateral.checked_add(margin_delta as u64).ok_or(ErrorCode::CalculationOverflow)?;
+            position.margin = apply_delta(position.margin, margin_delta)?;
         } else {
-            position.margin = position.margin.checked_sub((-margin_delta) as u64).ok_or(ErrorCode::CannotReduceMargin)?;
-            user.locked_collateral = user.locked_collateral.checked_sub((-margin_delta) as u64).ok_or(ErrorCode::CalculationUnderflow)?;
+            position.margin = apply_delta(position.margin, margin_delta).map_err(|_| error!(ErrorCode::CannotReduceMargin))?;
         }
+        user.locked_collateral = apply_delta(user.locked_collateral, margin_delta)?;
+    }
+
+    // The locked margin must still be covered once the fee and any added margin
+    // are accounted for.
+    if size_delta != 0 || margin_delta > 0 {
+        let mints = load_collateral_mints(ctx.remaining_accounts)?;
+        check_margin(
+            user,
+            ctx.accounts.collateral_account.as_deref(),
+            &mints,
+            0,
+            ctx.accounts.config.max_price_age,
+        )?;
     }
 
     msg!("Position modified");
@@ -0,0 +1,116 @@
+use anchor_lang::prelude::*;
+use crate::state::{CollateralAccount, Config, Delegate, Market, PositionBook, PositionSlot, Referral, UserAccount};
+use crate::errors::ErrorCode;
+use crate::utils::{
+    charge_trading_fee, check_margin, initial_margin, liquidation_price, load_collateral_mints, notional_value,
+    symbol_bytes, verify_authority, PERMISSION_OPEN, POSITION_MODE_ONE_WAY,
+};
+
+/// Remaining accounts are the `CollateralMint`s of the user's non-zero collateral
+/// balances, used to value them for the margin check.
+#[derive(Accounts)]
+#[instruction(symbol: String)]
+pub struct OpenBookPosition<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut, has_one = user_account)]
+    pub position_book: AccountLoader<'info, PositionBook>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        constraint = market.symbol == symbol_bytes(&symbol) @ ErrorCode::MarketMismatch
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        seeds = [b"delegate", user_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    #[account(
+        mut,
+        seeds = [b"referral", user_account.key().as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Option<Account<'info, Referral>>,
+
+    #[account(
+        seeds = [b"collateral", user_account.key().as_ref()],
+        bump = collateral_account.bump
+    )]
+    pub collateral_account: Option<Account<'info, CollateralAccount>>,
+}
+
+pub fn handler(
+    ctx: Context<OpenBookPosition>,
+    symbol: String,
+    side: u8,
+    size: u64,
+    leverage: u16,
+    max_price: Option<u64>,
+    min_price: Option<u64>,
+) -> Result<()> {
+    let user = &mut ctx.accounts.user_account;
+    let market = &mut ctx.accounts.market;
+    let delegate = ctx.accounts.delegate.as_deref_mut();
+    let mut book = ctx.accounts.position_book.load_mut()?;
+
+    verify_authority(user, &ctx.accounts.authority.key(), delegate.as_deref(), PERMISSION_OPEN)?;
+
+    require!(size > 0, ErrorCode::InvalidPositionSize);
+    require!((1..=100).contains(&leverage), ErrorCode::InvalidLeverageValue);
+    require!(side == 1 || side == 2, ErrorCode::InvalidSide);
+    // Book slots are never netted, so they only exist in hedge mode.
+    require!(user.position_mode != POSITION_MODE_ONE_WAY, ErrorCode::PositionModeConflict);
+    let entry_price = market.execution_price(max_price, min_price, ctx.accounts.config.max_price_age)?;
+
+    let slot_index = book.free_slot().ok_or(ErrorCode::PositionBookFull)?;
+    let initial_margin = initial_margin(entry_price, leverage)?;
+
+    let notional = notional_value(size, entry_price)?;
+    if let Some(delegate) = delegate {
+        delegate.use_notional(notional)?;
+    }
+    let open_notional = user.open_notional.checked_add(notional).ok_or(ErrorCode::CalculationOverflow)?;
+    require!(open_notional <= ctx.accounts.config.max_user_notional, ErrorCode::UserNotionalCapExceeded);
+    market.add_open_interest(side, size)?;
+    charge_trading_fee(&mut ctx.accounts.config, user, ctx.accounts.referral.as_deref_mut(), notional)?;
+
+    let mints = load_collateral_mints(ctx.remaining_accounts)?;
+    check_margin(
+        user,
+        ctx.accounts.collateral_account.as_deref(),
+        &mints,
+        initial_margin,
+        ctx.accounts.config.max_price_age,
+    )?;
+
+    book.slots[slot_index] = PositionSlot {
+        symbol: symbol_bytes(&symbol),
+        size,
+        entry_price,
+        margin: initial_margin,
+        liquidation_price: liquidation_price(side, entry_price)?,
+        opened_at: Clock::get()?.unix_timestamp,
+        leverage,
+        side,
+        status: 1,
+        padding: [0; 4],
+    };
+    book.open_count += 1;
+
+    user.locked_collateral = user.locked_collateral.checked_add(initial_margin).ok_or(ErrorCode::CalculationOverflow)?;
+    user.open_notional = open_notional;
+    user.last_activity = Clock::get()?.unix_timestamp;
+
+    msg!("Book position opened in slot {}", slot_index);
+    Ok(())
+}
\ No newline at end of file
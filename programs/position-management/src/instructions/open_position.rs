@@ -1,65 +1,225 @@
 use anchor_lang::prelude::*;
-use crate::state::{Position, UserAccount};
+use crate::state::{CollateralAccount, Config, Delegate, Market, Position, PositionIndex, Referral, UserAccount};
 use crate::errors::ErrorCode;
+use crate::utils::{
+    charge_trading_fee, check_margin, initial_margin, liquidation_price, load_collateral_mints,
+    notional_value, pro_rata, symbol_bytes, unrealized_pnl, verify_authority, PERMISSION_OPEN, POSITION_MODE_ONE_WAY,
+};
 
+/// `existing_position` is the user's open position on the symbol. In one-way mode
+/// or with `reduce_only`, an open on its opposite side reduces it first and any
+/// remainder opens `position` (never with `reduce_only`). One-way mode also
+/// requires `position_index`, and `existing_position` must be the position it
+/// records. Remaining accounts are the `CollateralMint`s of the user's non-zero
+/// collateral balances, used to value them for the margin check.
 #[derive(Accounts)]
+#[instruction(symbol: String)]
 pub struct OpenPosition<'info> {
     #[account(mut)]
-    pub owner: Signer<'info>,
+    pub authority: Signer<'info>,
 
     #[account(mut)]
     pub user_account: Account<'info, UserAccount>,
 
     #[account(
         init,
-        payer = owner,
+        payer = authority,
         space = Position::LEN,
-        seeds = [b"position", owner.key().as_ref(), &user_account.position_count.to_le_bytes()],
+        seeds = [
+            b"position",
+            user_account.owner.as_ref(),
+            &user_account.sub_account_id.to_le_bytes(),
+            &user_account.next_position_id.to_le_bytes()
+        ],
         bump
     )]
-    pub position: Account<'info, Position>,
+    pub position: Option<Account<'info, Position>>,
+
+    #[account(
+        mut,
+        constraint = existing_position.owner == user_account.owner
+            && existing_position.sub_account_id == user_account.sub_account_id @ ErrorCode::CannotModifyOthersPosition,
+        constraint = existing_position.symbol == symbol_bytes(&symbol) @ ErrorCode::MarketMismatch
+    )]
+    pub existing_position: Option<Account<'info, Position>>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = PositionIndex::LEN,
+        seeds = [b"position_index", user_account.key().as_ref(), symbol_bytes(&symbol).as_ref()],
+        bump
+    )]
+    pub position_index: Option<Account<'info, PositionIndex>>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        constraint = market.symbol == symbol_bytes(&symbol) @ ErrorCode::MarketMismatch
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        seeds = [b"delegate", user_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    #[account(
+        mut,
+        seeds = [b"referral", user_account.key().as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Option<Account<'info, Referral>>,
+
+    #[account(
+        seeds = [b"collateral", user_account.key().as_ref()],
+        bump = collateral_account.bump
+    )]
+    pub collateral_account: Option<Account<'info, CollateralAccount>>,
 
     pub system_program: Program<'info, System>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn handler(
     ctx: Context<OpenPosition>,
     symbol: String,
     side: u8,
     size: u64,
     leverage: u16,
-    entry_price: u64,
+    max_price: Option<u64>,
+    min_price: Option<u64>,
+    reduce_only: bool,
 ) -> Result<()> {
-    let position = &mut ctx.accounts.position;
     let user = &mut ctx.accounts.user_account;
-    let owner = &ctx.accounts.owner;
+    let market = &mut ctx.accounts.market;
+    let delegate = ctx.accounts.delegate.as_deref_mut();
+
+    verify_authority(user, &ctx.accounts.authority.key(), delegate.as_deref(), PERMISSION_OPEN)?;
 
     require!(size > 0, ErrorCode::InvalidPositionSize);
-    require!(leverage >= 1 && leverage <= 100, ErrorCode::InvalidLeverageValue);
-    require_neq!(entry_price, 0, ErrorCode::InvalidPrice);
+    require!((1..=100).contains(&leverage), ErrorCode::InvalidLeverageValue);
+    require!(side == 1 || side == 2, ErrorCode::InvalidSide);
+    let entry_price = market.execution_price(max_price, min_price, ctx.accounts.config.max_price_age)?;
+
+    let one_way = user.position_mode == POSITION_MODE_ONE_WAY;
+    if one_way {
+        let index = ctx.accounts.position_index.as_deref().ok_or(ErrorCode::OpenPositionRequired)?;
+        if index.position != Pubkey::default() {
+            let existing = ctx.accounts.existing_position.as_ref().map(|existing| existing.key());
+            require!(existing == Some(index.position), ErrorCode::OpenPositionRequired);
+        }
+    }
+    let now = Clock::get()?.unix_timestamp;
+    let mut remaining = size;
+
+    match ctx.accounts.existing_position.as_deref_mut() {
+        Some(existing) if existing.status == 1 && existing.side != side && (one_way || reduce_only) => {
+            // Reduce the opposing position; in one-way mode any remainder flips it.
+            let reduce = remaining.min(existing.size);
+            let pnl = unrealized_pnl(existing.side, existing.entry_price, entry_price, reduce)?;
+            let released = pro_rata(existing.margin, reduce, existing.size)?;
+
+            market.remove_open_interest(existing.side, reduce)?;
+
+            existing.size -= reduce;
+            existing.margin -= released;
+            existing.realized_pnl = existing.realized_pnl.checked_add(pnl).ok_or(ErrorCode::CalculationOverflow)?;
 
-    let initial_margin = entry_price.checked_div(leverage as u64).ok_or(ErrorCode::CalculationUnderflow)?;
+            let notional = notional_value(reduce, existing.entry_price)?;
+            user.open_notional = user.open_notional.checked_sub(notional).ok_or(ErrorCode::CalculationUnderflow)?;
+            user.locked_collateral = user.locked_collateral.checked_sub(released).ok_or(ErrorCode::CalculationUnderflow)?;
+            user.settle_pnl(pnl)?;
 
-    let mut sym_bytes = [0u8; 16];
-    let s = symbol.as_bytes();
-    let len = s.len().min(16);
-    sym_bytes[..len].copy_from_slice(&s[..len]);
+            let exit_notional = notional_value(reduce, entry_price)?;
+            charge_trading_fee(&mut ctx.accounts.config, user, ctx.accounts.referral.as_deref_mut(), exit_notional)?;
 
-    position.owner = owner.key();
-    position.symbol = sym_bytes;
+            if existing.size == 0 {
+                existing.status = 2;
+                existing.close_price = entry_price;
+                existing.closed_at = now;
+                user.position_count = user.position_count.checked_sub(1).ok_or(ErrorCode::CalculationUnderflow)?;
+            }
+
+            remaining -= reduce;
+            msg!("Position reduced by {}", reduce);
+        }
+        Some(existing) if existing.status == 1 && existing.side == side && one_way => {
+            return err!(ErrorCode::PositionModeConflict);
+        }
+        _ => {
+            require!(!reduce_only, ErrorCode::ReduceOnlyViolation);
+        }
+    }
+
+    // Reduce-only opens never add exposure; any size beyond the position is dropped.
+    if remaining == 0 || reduce_only {
+        // The closing fee must leave the remaining margin covered.
+        let mints = load_collateral_mints(ctx.remaining_accounts)?;
+        check_margin(user, ctx.accounts.collateral_account.as_deref(), &mints, 0, ctx.accounts.config.max_price_age)?;
+
+        // Nothing was opened, so the new position account is refunded and its
+        // address stays free for the next open.
+        if let Some(position) = ctx.accounts.position.take() {
+            position.close(ctx.accounts.authority.to_account_info())?;
+        }
+        user.last_activity = now;
+        return Ok(());
+    }
+
+    let position = ctx.accounts.position.as_deref_mut().ok_or(ErrorCode::PositionNotFound)?;
+    let initial_margin = initial_margin(entry_price, leverage)?;
+
+    let notional = notional_value(remaining, entry_price)?;
+    if let Some(delegate) = delegate {
+        delegate.use_notional(notional)?;
+    }
+    let open_notional = user.open_notional.checked_add(notional).ok_or(ErrorCode::CalculationOverflow)?;
+    require!(open_notional <= ctx.accounts.config.max_user_notional, ErrorCode::UserNotionalCapExceeded);
+    market.add_open_interest(side, remaining)?;
+    charge_trading_fee(&mut ctx.accounts.config, user, ctx.accounts.referral.as_deref_mut(), notional)?;
+
+    let mints = load_collateral_mints(ctx.remaining_accounts)?;
+    check_margin(
+        user,
+        ctx.accounts.collateral_account.as_deref(),
+        &mints,
+        initial_margin,
+        ctx.accounts.config.max_price_age,
+    )?;
+
+    position.version = Position::VERSION;
+    position.owner = user.owner;
+    position.sub_account_id = user.sub_account_id;
+    position.symbol = symbol_bytes(&symbol);
     position.side = side;
-    position.size = size;
+    position.size = remaining;
     position.entry_price = entry_price;
     position.leverage = leverage;
     position.margin = initial_margin;
     position.status = 1;
-    position.opened_at = Clock::get()?.unix_timestamp;
-    position.liquidation_price = if side == 1 { entry_price * 90 / 100 } else { entry_price * 110 / 100 };
-    position.bump = ctx.bumps.position;
+    position.opened_at = now;
+    position.liquidation_price = liquidation_price(side, entry_price)?;
+    position.bump = ctx.bumps.position.ok_or(ErrorCode::PositionNotFound)?;
 
     user.locked_collateral = user.locked_collateral.checked_add(initial_margin).ok_or(ErrorCode::CalculationOverflow)?;
     user.position_count = user.position_count.checked_add(1).ok_or(ErrorCode::CalculationOverflow)?;
-    user.last_activity = Clock::get()?.unix_timestamp;
+    user.next_position_id = user.next_position_id.checked_add(1).ok_or(ErrorCode::CalculationOverflow)?;
+    user.open_notional = open_notional;
+    user.last_activity = now;
+
+    if one_way {
+        let position_key = ctx.accounts.position.as_ref().map(|position| position.key()).unwrap_or_default();
+        let index = ctx.accounts.position_index.as_deref_mut().ok_or(ErrorCode::OpenPositionRequired)?;
+        index.user_account = user.key();
+        index.symbol = symbol_bytes(&symbol);
+        index.bump = ctx.bumps.position_index.ok_or(ErrorCode::OpenPositionRequired)?;
+        index.position = position_key;
+    }
 
     msg!("Position opened");
     Ok(())
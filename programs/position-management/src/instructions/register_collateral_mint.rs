@@ -0,0 +1,64 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use crate::state::{CollateralMint, Config};
+use crate::errors::ErrorCode;
+
+#[derive(Accounts)]
+pub struct RegisterCollateralMint<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = CollateralMint::LEN,
+        seeds = [b"collateral_mint", mint.key().as_ref()],
+        bump
+    )]
+    pub collateral_mint: Account<'info, CollateralMint>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"vault", mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = collateral_mint
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(
+    ctx: Context<RegisterCollateralMint>,
+    oracle: Pubkey,
+    haircut_bps: u16,
+) -> Result<()> {
+    CollateralMint::validate_haircut(haircut_bps)?;
+
+    let collateral_mint = &mut ctx.accounts.collateral_mint;
+    collateral_mint.mint = ctx.accounts.mint.key();
+    collateral_mint.vault = ctx.accounts.vault.key();
+    collateral_mint.oracle = oracle;
+    collateral_mint.bump = ctx.bumps.collateral_mint;
+    collateral_mint.vault_bump = ctx.bumps.vault;
+    collateral_mint.decimals = ctx.accounts.mint.decimals;
+    collateral_mint.haircut_bps = haircut_bps;
+    collateral_mint.price = 0;
+    collateral_mint.last_price_update = 0;
+    collateral_mint.total_deposits = 0;
+
+    msg!("Collateral mint registered");
+    Ok(())
+}
\ No newline at end of file
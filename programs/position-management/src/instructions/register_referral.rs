@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+use crate::state::{Referral, UserAccount};
+use crate::errors::ErrorCode;
+
+#[derive(Accounts)]
+pub struct RegisterReferral<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(mut, has_one = owner @ ErrorCode::Unauthorized)]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = Referral::LEN,
+        seeds = [b"referral", user_account.key().as_ref()],
+        bump
+    )]
+    pub referral: Account<'info, Referral>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<RegisterReferral>, referrer: Pubkey) -> Result<()> {
+    require_keys_neq!(referrer, ctx.accounts.owner.key(), ErrorCode::SelfReferral);
+
+    let referral = &mut ctx.accounts.referral;
+    referral.referee = ctx.accounts.user_account.key();
+    referral.referrer = referrer;
+    referral.bump = ctx.bumps.referral;
+    referral.referee_volume = 0;
+    referral.unclaimed_rebates = 0;
+    referral.total_rebates = 0;
+    referral.created_at = Clock::get()?.unix_timestamp;
+    ctx.accounts.user_account.has_referral = true;
+
+    msg!("Referral registered");
+    Ok(())
+}
\ No newline at end of file
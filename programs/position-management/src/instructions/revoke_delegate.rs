@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+use crate::state::{Delegate, UserAccount};
+use crate::errors::ErrorCode;
+
+#[derive(Accounts)]
+pub struct RevokeDelegate<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(has_one = owner @ ErrorCode::Unauthorized)]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"delegate", user_account.key().as_ref(), delegate_account.delegate.as_ref()],
+        bump = delegate_account.bump
+    )]
+    pub delegate_account: Account<'info, Delegate>,
+}
+
+pub fn handler(_ctx: Context<RevokeDelegate>) -> Result<()> {
+    msg!("Delegate revoked");
+    Ok(())
+}
\ No newline at end of file
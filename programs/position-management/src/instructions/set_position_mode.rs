@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+use crate::state::UserAccount;
+use crate::errors::ErrorCode;
+use crate::utils::{POSITION_MODE_HEDGE, POSITION_MODE_ONE_WAY};
+
+#[derive(Accounts)]
+pub struct SetPositionMode<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(mut, has_one = owner @ ErrorCode::Unauthorized)]
+    pub user_account: Account<'info, UserAccount>,
+}
+
+pub fn handler(ctx: Context<SetPositionMode>, position_mode: u8) -> Result<()> {
+    require!(
+        position_mode == POSITION_MODE_HEDGE || position_mode == POSITION_MODE_ONE_WAY,
+        ErrorCode::InvalidPositionMode
+    );
+
+    let user = &mut ctx.accounts.user_account;
+    require!(user.position_count == 0 && user.open_notional == 0, ErrorCode::PositionModeLocked);
+
+    user.position_mode = position_mode;
+    user.last_activity = Clock::get()?.unix_timestamp;
+
+    msg!("Position mode set to {}", position_mode);
+    Ok(())
+}
\ No newline at end of file
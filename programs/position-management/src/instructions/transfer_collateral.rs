@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+use crate::state::UserAccount;
+use crate::errors::ErrorCode;
+
+#[derive(Accounts)]
+pub struct TransferCollateral<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = owner @ ErrorCode::Unauthorized,
+        constraint = from_account.address()? == from_account.key() @ ErrorCode::Unauthorized
+    )]
+    pub from_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        has_one = owner @ ErrorCode::Unauthorized,
+        constraint = to_account.address()? == to_account.key() @ ErrorCode::Unauthorized,
+        constraint = to_account.key() != from_account.key() @ ErrorCode::InvalidSubAccount
+    )]
+    pub to_account: Account<'info, UserAccount>,
+}
+
+pub fn handler(ctx: Context<TransferCollateral>, amount: u64) -> Result<()> {
+    let from = &mut ctx.accounts.from_account;
+    let to = &mut ctx.accounts.to_account;
+
+    let free_collateral = from.total_collateral.checked_sub(from.locked_collateral).ok_or(ErrorCode::CalculationUnderflow)?;
+    require!(amount <= free_collateral, ErrorCode::InsufficientCollateral);
+
+    from.total_collateral -= amount;
+    to.credit_collateral(amount)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    from.last_activity = now;
+    to.last_activity = now;
+
+    msg!("Collateral transferred between sub-accounts");
+    Ok(())
+}
\ No newline at end of file
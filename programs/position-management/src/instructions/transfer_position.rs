@@ -0,0 +1,80 @@
+use anchor_lang::prelude::*;
+use crate::state::{CollateralAccount, Config, Position, UserAccount};
+use crate::errors::ErrorCode;
+use crate::utils::{check_margin, load_collateral_mints, notional_value, POSITION_MODE_HEDGE};
+
+/// Both accounts must be in hedge mode, since a one-way account tracks its
+/// position per symbol. The receiver must be able to cover the margin it takes
+/// on; remaining accounts are the `CollateralMint`s of its non-zero balances.
+#[derive(Accounts)]
+pub struct TransferPosition<'info> {
+    pub from_owner: Signer<'info>,
+
+    pub to_owner: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = from_account.owner == from_owner.key() @ ErrorCode::Unauthorized
+    )]
+    pub from_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        constraint = to_account.owner == to_owner.key() @ ErrorCode::Unauthorized,
+        constraint = to_account.key() != from_account.key() @ ErrorCode::InvalidSubAccount
+    )]
+    pub to_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        constraint = position.owner == from_account.owner
+            && position.sub_account_id == from_account.sub_account_id @ ErrorCode::CannotModifyOthersPosition
+    )]
+    pub position: Account<'info, Position>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [b"collateral", to_account.key().as_ref()],
+        bump = to_collateral_account.bump
+    )]
+    pub to_collateral_account: Option<Account<'info, CollateralAccount>>,
+}
+
+pub fn handler(ctx: Context<TransferPosition>) -> Result<()> {
+    let position = &mut ctx.accounts.position;
+    let from = &mut ctx.accounts.from_account;
+    let to = &mut ctx.accounts.to_account;
+
+    require_eq!(position.status, 1, ErrorCode::PositionAlreadyClosed);
+    require!(
+        from.position_mode == POSITION_MODE_HEDGE && to.position_mode == POSITION_MODE_HEDGE,
+        ErrorCode::PositionModeConflict
+    );
+
+    let notional = notional_value(position.size, position.entry_price)?;
+    let to_notional = to.open_notional.checked_add(notional).ok_or(ErrorCode::CalculationOverflow)?;
+    require!(to_notional <= ctx.accounts.config.max_user_notional, ErrorCode::UserNotionalCapExceeded);
+
+    from.locked_collateral = from.locked_collateral.checked_sub(position.margin).ok_or(ErrorCode::CalculationUnderflow)?;
+    from.open_notional = from.open_notional.checked_sub(notional).ok_or(ErrorCode::CalculationUnderflow)?;
+    from.position_count = from.position_count.checked_sub(1).ok_or(ErrorCode::CalculationUnderflow)?;
+
+    to.locked_collateral = to.locked_collateral.checked_add(position.margin).ok_or(ErrorCode::CalculationOverflow)?;
+    to.open_notional = to_notional;
+    to.position_count = to.position_count.checked_add(1).ok_or(ErrorCode::CalculationOverflow)?;
+
+    let mints = load_collateral_mints(ctx.remaining_accounts)?;
+    check_margin(to, ctx.accounts.to_collateral_account.as_deref(), &mints, 0, ctx.accounts.config.max_price_age)?;
+
+    position.owner = to.owner;
+    position.sub_account_id = to.sub_account_id;
+
+    let now = Clock::get()?.unix_timestamp;
+    from.last_activity = now;
+    to.last_activity = now;
+
+    msg!("Position transferred");
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+use crate::state::{CollateralMint, Config};
+use crate::errors::ErrorCode;
+
+#[derive(Accounts)]
+pub struct UpdateCollateralMint<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_mint", collateral_mint.mint.as_ref()],
+        bump = collateral_mint.bump
+    )]
+    pub collateral_mint: Account<'info, CollateralMint>,
+}
+
+pub fn handler(
+    ctx: Context<UpdateCollateralMint>,
+    oracle: Pubkey,
+    haircut_bps: u16,
+) -> Result<()> {
+    CollateralMint::validate_haircut(haircut_bps)?;
+
+    let collateral_mint = &mut ctx.accounts.collateral_mint;
+    collateral_mint.oracle = oracle;
+    collateral_mint.haircut_bps = haircut_bps;
+
+    msg!("Collateral mint updated");
+    Ok(())
+}
\ No newline at end of file
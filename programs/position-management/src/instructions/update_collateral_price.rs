@@ -0,0 +1,27 @@
+use anchor_lang::prelude::*;
+use crate::state::CollateralMint;
+use crate::errors::ErrorCode;
+
+#[derive(Accounts)]
+pub struct UpdateCollateralPrice<'info> {
+    pub oracle: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_mint", collateral_mint.mint.as_ref()],
+        bump = collateral_mint.bump,
+        has_one = oracle @ ErrorCode::Unauthorized
+    )]
+    pub collateral_mint: Account<'info, CollateralMint>,
+}
+
+pub fn handler(ctx: Context<UpdateCollateralPrice>, price: u64) -> Result<()> {
+    require_neq!(price, 0, ErrorCode::InvalidPrice);
+
+    let collateral_mint = &mut ctx.accounts.collateral_mint;
+    collateral_mint.price = price;
+    collateral_mint.last_price_update = Clock::get()?.unix_timestamp;
+
+    msg!("Collateral price updated");
+    Ok(())
+}
\ No newline at end of file
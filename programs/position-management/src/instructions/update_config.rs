@@ -0,0 +1,36 @@
+use anchor_lang::prelude::*;
+use crate::state::Config;
+use crate::errors::ErrorCode;
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+pub fn handler(
+    ctx: Context<UpdateConfig>,
+    max_user_notional: u64,
+    fee_bps: u16,
+    referral_share_bps: u16,
+    max_price_age: i64,
+) -> Result<()> {
+    Config::validate_fees(fee_bps, referral_share_bps)?;
+    Config::validate_price_age(max_price_age)?;
+
+    let config = &mut ctx.accounts.config;
+    config.max_user_notional = max_user_notional;
+    config.fee_bps = fee_bps;
+    config.referral_share_bps = referral_share_bps;
+    config.max_price_age = max_price_age;
+
+    msg!("Config updated");
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,33 @@
+use anchor_lang::prelude::*;
+use crate::state::{Config, Market};
+use crate::errors::ErrorCode;
+
+#[derive(Accounts)]
+pub struct UpdateMarkPrice<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.symbol.as_ref()],
+        bump = market.bump
+    )]
+    pub market: Account<'info, Market>,
+}
+
+pub fn handler(ctx: Context<UpdateMarkPrice>, price: u64) -> Result<()> {
+    require_neq!(price, 0, ErrorCode::InvalidPrice);
+
+    let market = &mut ctx.accounts.market;
+    market.mark_price = price;
+    market.last_price_update = Clock::get()?.unix_timestamp;
+
+    msg!("Mark price updated");
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+use crate::state::{Config, Market};
+use crate::errors::ErrorCode;
+
+#[derive(Accounts)]
+pub struct UpdateMarket<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.symbol.as_ref()],
+        bump = market.bump
+    )]
+    pub market: Account<'info, Market>,
+}
+
+pub fn handler(ctx: Context<UpdateMarket>, max_open_interest: u64) -> Result<()> {
+    ctx.accounts.market.max_open_interest = max_open_interest;
+
+    msg!("Market updated");
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,57 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use crate::state::{CollateralAccount, CollateralMint, Position, PositionBook, UserAccount};
+use crate::errors::ErrorCode;
+use crate::utils::{collateral_value, load_collateral_mints, UserInvariants};
+
+/// Read-only consistency check. Remaining accounts are the user's `Position`
+/// accounts, closed ones included or not, followed by the `CollateralMint`s of
+/// its non-zero collateral balances. Pass `position_book` if the user has one,
+/// since its slots lock collateral too.
+#[derive(Accounts)]
+pub struct VerifyUser<'info> {
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(has_one = user_account)]
+    pub position_book: Option<AccountLoader<'info, PositionBook>>,
+
+    #[account(
+        seeds = [b"collateral", user_account.key().as_ref()],
+        bump = collateral_account.bump
+    )]
+    pub collateral_account: Option<Account<'info, CollateralAccount>>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, VerifyUser<'info>>,
+) -> Result<()> {
+    let user = &ctx.accounts.user_account;
+
+    let split = ctx
+        .remaining_accounts
+        .iter()
+        .position(|info| info.try_borrow_data().is_ok_and(|data| data.starts_with(CollateralMint::DISCRIMINATOR)))
+        .unwrap_or(ctx.remaining_accounts.len());
+    let (positions, mints) = ctx.remaining_accounts.split_at(split);
+
+    let mut invariants = UserInvariants::default();
+    for (index, info) in positions.iter().enumerate() {
+        require!(positions[..index].iter().all(|other| other.key != info.key), ErrorCode::DuplicatePosition);
+        let position = Account::<Position>::try_from(info)?;
+        require!(
+            position.owner == user.owner && position.sub_account_id == user.sub_account_id,
+            ErrorCode::CannotModifyOthersPosition
+        );
+        invariants.add_position(&position);
+    }
+    if let Some(book) = &ctx.accounts.position_book {
+        invariants.add_book(&*book.load()?);
+    }
+
+    let mints = load_collateral_mints(mints)?;
+    let value = collateral_value(user, ctx.accounts.collateral_account.as_deref(), &mints)?;
+    invariants.verify(user, value)?;
+
+    msg!("User account verified");
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,112 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, TransferChecked};
+use crate::state::{CollateralAccount, CollateralMint, Config, PositionBook, UserAccount};
+use crate::errors::ErrorCode;
+use crate::utils::{check_margin, collateral_value, load_collateral_mints, load_open_positions, unrealized_pnl};
+
+/// Remaining accounts are `(position, market)` pairs for every open position, then
+/// the `Market` of every symbol held in `position_book`, then the `CollateralMint`s
+/// of every other non-zero balance. Together they value the account after the
+/// withdrawal, with open positions at their mark prices.
+#[derive(Accounts)]
+pub struct WithdrawCollateral<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(mut, has_one = owner @ ErrorCode::Unauthorized)]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(has_one = user_account)]
+    pub position_book: Option<AccountLoader<'info, PositionBook>>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", user_account.key().as_ref()],
+        bump = collateral_account.bump
+    )]
+    pub collateral_account: Account<'info, CollateralAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_mint", mint.key().as_ref()],
+        bump = collateral_mint.bump,
+        has_one = mint,
+        has_one = vault
+    )]
+    pub collateral_mint: Account<'info, CollateralMint>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = mint)]
+    pub owner_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, WithdrawCollateral<'info>>,
+    amount: u64,
+) -> Result<()> {
+    require!(amount > 0, ErrorCode::InvalidCollateralAmount);
+
+    let collateral_mint = &mut ctx.accounts.collateral_mint;
+    ctx.accounts.collateral_account.withdraw(collateral_mint.mint, amount)?;
+    collateral_mint.total_deposits = collateral_mint.total_deposits.checked_sub(amount).ok_or(ErrorCode::CalculationUnderflow)?;
+
+    let user = &ctx.accounts.user_account;
+    let max_price_age = ctx.accounts.config.max_price_age;
+    let (unrealized, rest) = open_position_pnl(user, ctx.accounts.position_book.as_ref(), ctx.remaining_accounts, max_price_age)?;
+
+    let mut mints = load_collateral_mints(rest)?;
+    mints.push((**collateral_mint).clone());
+    check_margin(user, Some(&ctx.accounts.collateral_account), &mints, 0, max_price_age)?;
+
+    // Unrealized losses count against what may leave the account.
+    let value = collateral_value(user, Some(&ctx.accounts.collateral_account), &mints)?;
+    let equity = (value as i128) + unrealized - (user.locked_collateral as i128);
+    require!(equity >= 0, ErrorCode::InsufficientCollateral);
+
+    let mint_key = ctx.accounts.collateral_mint.mint;
+    let signer_seeds: &[&[&[u8]]] = &[&[b"collateral_mint", mint_key.as_ref(), &[ctx.accounts.collateral_mint.bump]]];
+    token::transfer_checked(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.owner_token_account.to_account_info(),
+                authority: ctx.accounts.collateral_mint.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        amount,
+        ctx.accounts.mint.decimals,
+    )?;
+
+    ctx.accounts.user_account.last_activity = Clock::get()?.unix_timestamp;
+
+    msg!("Collateral withdrawn");
+    Ok(())
+}
+
+/// Unrealized PnL of every open position and book slot at fresh mark prices,
+/// along with the remaining accounts left after the positions and book markets.
+fn open_position_pnl<'info>(
+    user: &UserAccount,
+    position_book: Option<&AccountLoader<'info, PositionBook>>,
+    accounts: &'info [AccountInfo<'info>],
+    max_price_age: i64,
+) -> Result<(i128, &'info [AccountInfo<'info>])> {
+    let (positions, rest) = load_open_positions(user, position_book, accounts, max_price_age)?;
+
+    let mut pnl: i128 = 0;
+    for position in &positions {
+        pnl += unrealized_pnl(position.side, position.entry_price, position.mark_price, position.size)? as i128;
+    }
+    Ok((pnl, rest))
+}
\ No newline at end of file
@@ -8,28 +8,109 @@ pub mod utils;
 pub mod errors;
 
 use instructions::*;
+use utils::AccountHealth;
 
 #[program]
 pub mod position_management {
     use super::*;
 
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        max_user_notional: u64,
+        fee_bps: u16,
+        referral_share_bps: u16,
+        max_price_age: i64,
+    ) -> Result<()> {
+        instructions::initialize_config::handler(ctx, max_user_notional, fee_bps, referral_share_bps, max_price_age)
+    }
+
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        max_user_notional: u64,
+        fee_bps: u16,
+        referral_share_bps: u16,
+        max_price_age: i64,
+    ) -> Result<()> {
+        instructions::update_config::handler(ctx, max_user_notional, fee_bps, referral_share_bps, max_price_age)
+    }
+
+    pub fn initialize_market(
+        ctx: Context<InitializeMarket>,
+        symbol: String,
+        max_open_interest: u64,
+    ) -> Result<()> {
+        instructions::initialize_market::handler(ctx, symbol, max_open_interest)
+    }
+
+    pub fn update_market(
+        ctx: Context<UpdateMarket>,
+        max_open_interest: u64,
+    ) -> Result<()> {
+        instructions::update_market::handler(ctx, max_open_interest)
+    }
+
+    pub fn update_mark_price(
+        ctx: Context<UpdateMarkPrice>,
+        price: u64,
+    ) -> Result<()> {
+        instructions::update_mark_price::handler(ctx, price)
+    }
+
     pub fn initialize_user(
-        ctx: Context<InitializeUser>
+        ctx: Context<InitializeUser>,
+        sub_account_id: u16,
+    ) -> Result<()> {
+        instructions::initialize_user::handler(ctx, sub_account_id)
+    }
+
+    pub fn transfer_collateral(
+        ctx: Context<TransferCollateral>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::transfer_collateral::handler(ctx, amount)
+    }
+
+    pub fn create_delegate(
+        ctx: Context<CreateDelegate>,
+        delegate: Pubkey,
+        permissions: u8,
+        max_notional: u64,
+        expires_at: i64,
     ) -> Result<()> {
-        instructions::initialize_user::handler(ctx)
+        instructions::create_delegate::handler(ctx, delegate, permissions, max_notional, expires_at)
     }
 
+    pub fn revoke_delegate(
+        ctx: Context<RevokeDelegate>,
+    ) -> Result<()> {
+        instructions::revoke_delegate::handler(ctx)
+    }
+
+    pub fn register_referral(
+        ctx: Context<RegisterReferral>,
+        referrer: Pubkey,
+    ) -> Result<()> {
+        instructions::register_referral::handler(ctx, referrer)
+    }
+
+    pub fn claim_rebates(
+        ctx: Context<ClaimRebates>,
+    ) -> Result<()> {
+        instructions::claim_rebates::handler(ctx)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn open_position(
         ctx: Context<OpenPosition>,
         symbol: String,
         side: u8,
         size: u64,
         leverage: u16,
-        entry_price: u64,
+        max_price: Option<u64>,
+        min_price: Option<u64>,
+        reduce_only: bool,
     ) -> Result<()> {
-        instructions::open_position::handler(
-            ctx, symbol, side, size, leverage, entry_price
-        )
+        instructions::open_position::handler(ctx, symbol, side, size, leverage, max_price, min_price, reduce_only)
     }
 
     pub fn modify_position(
@@ -42,9 +123,23 @@ pub mod position_management {
 
     pub fn close_position(
         ctx: Context<ClosePosition>,
-        exit_price: u64,
+        max_price: Option<u64>,
+        min_price: Option<u64>,
+    ) -> Result<()> {
+        instructions::close_position::handler(ctx, max_price, min_price)
+    }
+
+    pub fn close_all_positions<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CloseAllPositions<'info>>,
+        price_bounds: Vec<PriceBounds>,
+    ) -> Result<()> {
+        instructions::close_all_positions::handler(ctx, price_bounds)
+    }
+
+    pub fn transfer_position(
+        ctx: Context<TransferPosition>,
     ) -> Result<()> {
-        instructions::close_position::handler(ctx, exit_price)
+        instructions::transfer_position::handler(ctx)
     }
 
     pub fn liquidate_position(
@@ -52,4 +147,118 @@ pub mod position_management {
     ) -> Result<()> {
         instructions::liquidate_position::handler(ctx)
     }
+
+    pub fn auto_deleverage<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AutoDeleverage<'info>>,
+    ) -> Result<()> {
+        instructions::auto_deleverage::handler(ctx)
+    }
+
+    pub fn migrate_account(
+        ctx: Context<MigrateAccount>,
+    ) -> Result<()> {
+        instructions::migrate_account::handler(ctx)
+    }
+
+    pub fn initialize_position_book(
+        ctx: Context<InitializePositionBook>,
+    ) -> Result<()> {
+        instructions::initialize_position_book::handler(ctx)
+    }
+
+    pub fn open_book_position(
+        ctx: Context<OpenBookPosition>,
+        symbol: String,
+        side: u8,
+        size: u64,
+        leverage: u16,
+        max_price: Option<u64>,
+        min_price: Option<u64>,
+    ) -> Result<()> {
+        instructions::open_book_position::handler(ctx, symbol, side, size, leverage, max_price, min_price)
+    }
+
+    pub fn close_book_position(
+        ctx: Context<CloseBookPosition>,
+        slot_index: u8,
+        max_price: Option<u64>,
+        min_price: Option<u64>,
+    ) -> Result<()> {
+        instructions::close_book_position::handler(ctx, slot_index, max_price, min_price)
+    }
+
+    pub fn liquidate_book_position(
+        ctx: Context<LiquidateBookPosition>,
+        slot_index: u8,
+    ) -> Result<()> {
+        instructions::liquidate_book_position::handler(ctx, slot_index)
+    }
+
+    pub fn check_health<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CheckHealth<'info>>,
+    ) -> Result<AccountHealth> {
+        instructions::check_health::handler(ctx)
+    }
+
+    pub fn check_positions_health<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CheckPositionsHealth<'info>>,
+    ) -> Result<AccountHealth> {
+        instructions::check_positions_health::handler(ctx)
+    }
+
+    pub fn register_collateral_mint(
+        ctx: Context<RegisterCollateralMint>,
+        oracle: Pubkey,
+        haircut_bps: u16,
+    ) -> Result<()> {
+        instructions::register_collateral_mint::handler(ctx, oracle, haircut_bps)
+    }
+
+    pub fn update_collateral_mint(
+        ctx: Context<UpdateCollateralMint>,
+        oracle: Pubkey,
+        haircut_bps: u16,
+    ) -> Result<()> {
+        instructions::update_collateral_mint::handler(ctx, oracle, haircut_bps)
+    }
+
+    pub fn update_collateral_price(
+        ctx: Context<UpdateCollateralPrice>,
+        price: u64,
+    ) -> Result<()> {
+        instructions::update_collateral_price::handler(ctx, price)
+    }
+
+    pub fn initialize_collateral_account(
+        ctx: Context<InitializeCollateralAccount>,
+    ) -> Result<()> {
+        instructions::initialize_collateral_account::handler(ctx)
+    }
+
+    pub fn deposit_collateral(
+        ctx: Context<DepositCollateral>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::deposit_collateral::handler(ctx, amount)
+    }
+
+    pub fn withdraw_collateral<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawCollateral<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::withdraw_collateral::handler(ctx, amount)
+    }
+
+    pub fn set_position_mode(
+        ctx: Context<SetPositionMode>,
+        position_mode: u8,
+    ) -> Result<()> {
+        instructions::set_position_mode::handler(ctx, position_mode)
+    }
+
+    pub fn verify_user<'info>(
+        ctx: Context<'_, '_, 'info, 'info, VerifyUser<'info>>,
+    ) -> Result<()> {
+        instructions::verify_user::handler(ctx)
+    }
 }
\ No newline at end of file
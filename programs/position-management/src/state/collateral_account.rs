@@ -0,0 +1,48 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::utils::MAX_COLLATERAL_MINTS;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct CollateralBalance {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Per-mint collateral balances of a `UserAccount`, held alongside its quote
+/// `total_collateral`.
+#[account]
+pub struct CollateralAccount {
+    pub user_account: Pubkey,
+    pub bump: u8,
+    pub balances: [CollateralBalance; MAX_COLLATERAL_MINTS],
+}
+
+impl CollateralAccount {
+    pub const LEN: usize = 8 + 32 + 1 + (32 + 8) * MAX_COLLATERAL_MINTS;
+
+    pub fn deposit(&mut self, mint: Pubkey, amount: u64) -> Result<()> {
+        let index = match self.balances.iter().position(|b| b.mint == mint) {
+            Some(index) => index,
+            None => self
+                .balances
+                .iter()
+                .position(|b| b.amount == 0)
+                .ok_or(ErrorCode::CollateralSlotsFull)?,
+        };
+
+        let balance = &mut self.balances[index];
+        balance.mint = mint;
+        balance.amount = balance.amount.checked_add(amount).ok_or(ErrorCode::CalculationOverflow)?;
+        Ok(())
+    }
+
+    pub fn withdraw(&mut self, mint: Pubkey, amount: u64) -> Result<()> {
+        let balance = self
+            .balances
+            .iter_mut()
+            .find(|b| b.mint == mint && b.amount > 0)
+            .ok_or(ErrorCode::InsufficientCollateral)?;
+        balance.amount = balance.amount.checked_sub(amount).ok_or(ErrorCode::InsufficientCollateral)?;
+        Ok(())
+    }
+}
\ No newline at end of file
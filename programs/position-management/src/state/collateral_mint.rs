@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::utils::BPS_DENOMINATOR;
+
+/// An accepted collateral mint. `price` is pushed by `oracle` in quote units per
+/// whole token; deposits are valued at `price` less `haircut_bps`.
+#[account]
+pub struct CollateralMint {
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub oracle: Pubkey,
+    pub bump: u8,
+    pub vault_bump: u8,
+    pub decimals: u8,
+    pub haircut_bps: u16,
+    pub price: u64,
+    pub last_price_update: i64,
+    pub total_deposits: u64,
+}
+
+impl CollateralMint {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 1 + 1 + 1 + 2 + 8 + 8 + 8;
+
+    /// Haircut-weighted quote value of `amount` base units of this mint.
+    pub fn weighted_value(&self, amount: u64) -> Result<u64> {
+        require_neq!(self.price, 0, ErrorCode::InvalidPrice);
+
+        let weight = (BPS_DENOMINATOR - self.haircut_bps) as u128;
+        let value = (amount as u128)
+            .checked_mul(self.price as u128)
+            .and_then(|v| v.checked_mul(weight))
+            .ok_or(ErrorCode::CalculationOverflow)?
+            / (BPS_DENOMINATOR as u128)
+            / 10u128.pow(self.decimals as u32);
+        u64::try_from(value).map_err(|_| error!(ErrorCode::CalculationOverflow))
+    }
+
+    pub fn validate_haircut(haircut_bps: u16) -> Result<()> {
+        require!(haircut_bps < BPS_DENOMINATOR, ErrorCode::InvalidHaircut);
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::utils::{BPS_DENOMINATOR, MAX_FEE_BPS};
+
+#[account]
+pub struct Config {
+    pub authority: Pubkey,
+    pub bump: u8,
+    pub max_user_notional: u64,
+    pub insurance_fund: u64,
+    pub fee_bps: u16,
+    pub referral_share_bps: u16,
+    /// Seconds after which mark and collateral prices are rejected as stale.
+    pub max_price_age: i64,
+}
+
+impl Config {
+    pub const LEN: usize = 8 + 32 + 1 + 8 + 8 + 2 + 2 + 8;
+
+    pub fn validate_fees(fee_bps: u16, referral_share_bps: u16) -> Result<()> {
+        require!(
+            fee_bps <= MAX_FEE_BPS && referral_share_bps <= BPS_DENOMINATOR,
+            ErrorCode::InvalidFeeConfig
+        );
+        Ok(())
+    }
+
+    pub fn validate_price_age(max_price_age: i64) -> Result<()> {
+        require!(max_price_age > 0, ErrorCode::InvalidPriceAge);
+        Ok(())
+    }
+}
\ No newline at end of file
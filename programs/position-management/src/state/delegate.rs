@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+
+#[account]
+pub struct Delegate {
+    pub user_account: Pubkey,
+    pub delegate: Pubkey,
+    pub bump: u8,
+    pub permissions: u8,
+    pub max_notional: u64,
+    /// Notional opened through this delegate so far, capped by `max_notional`.
+    pub notional_used: u64,
+    pub expires_at: i64,
+    pub created_at: i64,
+}
+
+impl Delegate {
+    pub const LEN: usize = 8 + 32 + 32 + 1 + 1 + 8 + 8 + 8 + 8;
+
+    /// Counts `notional` against the delegate's limit. Closing does not give it
+    /// back, so the limit bounds everything the delegate opens over its lifetime.
+    pub fn use_notional(&mut self, notional: u64) -> Result<()> {
+        let used = self.notional_used.checked_add(notional).ok_or(ErrorCode::CalculationOverflow)?;
+        require!(used <= self.max_notional, ErrorCode::DelegateNotionalExceeded);
+        self.notional_used = used;
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,140 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use crate::errors::ErrorCode;
+use crate::state::{Position, UserAccount};
+
+/// `Position` layout as originally deployed, before the `version` byte,
+/// sub-accounts and reserved space were added.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PositionV1 {
+    pub owner: Pubkey,
+    pub symbol: [u8; 16],
+    pub bump: u8,
+    pub side: u8,
+    pub size: u64,
+    pub entry_price: u64,
+    pub leverage: u16,
+    pub status: u8,
+    pub margin: u64,
+    pub liquidation_price: u64,
+    pub mark_price: u64,
+    pub unrealized_pnl: i64,
+    pub realized_pnl: i64,
+    pub opened_at: i64,
+    pub closed_at: i64,
+    pub close_price: u64,
+}
+
+impl PositionV1 {
+    pub const LEN: usize = 8 + 32 + 16 + 1 + 1 + 8 + 8 + 2 + 1 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8;
+}
+
+impl From<PositionV1> for Position {
+    fn from(v1: PositionV1) -> Self {
+        Position {
+            version: Position::VERSION,
+            owner: v1.owner,
+            sub_account_id: UserAccount::LEGACY_SUB_ACCOUNT_ID,
+            symbol: v1.symbol,
+            bump: v1.bump,
+            side: v1.side,
+            size: v1.size,
+            entry_price: v1.entry_price,
+            leverage: v1.leverage,
+            status: v1.status,
+            margin: v1.margin,
+            liquidation_price: v1.liquidation_price,
+            mark_price: v1.mark_price,
+            unrealized_pnl: v1.unrealized_pnl,
+            realized_pnl: v1.realized_pnl,
+            opened_at: v1.opened_at,
+            closed_at: v1.closed_at,
+            close_price: v1.close_price,
+            reserved: [0; 64],
+        }
+    }
+}
+
+/// `UserAccount` layout as originally deployed, before the `version` byte,
+/// sub-accounts, the notional cap and reserved space were added.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct UserAccountV1 {
+    pub owner: Pubkey,
+    pub bump: u8,
+    pub total_collateral: u64,
+    pub locked_collateral: u64,
+    pub position_count: u32,
+    pub total_pnl: i64,
+    pub created_at: i64,
+    pub last_activity: i64,
+}
+
+impl UserAccountV1 {
+    pub const LEN: usize = 8 + 32 + 1 + 8 + 8 + 4 + 8 + 8 + 8;
+}
+
+/// The migrated account keeps its v1 address, so it gets the legacy
+/// sub-account. `open_notional` starts at 0 and is rebuilt as its open
+/// positions are migrated. v1 positions were derived from `position_count`
+/// without a sub-account, so new ones seeded from `next_position_id` never
+/// collide with them.
+impl From<UserAccountV1> for UserAccount {
+    fn from(v1: UserAccountV1) -> Self {
+        UserAccount {
+            version: UserAccount::VERSION,
+            owner: v1.owner,
+            sub_account_id: UserAccount::LEGACY_SUB_ACCOUNT_ID,
+            bump: v1.bump,
+            total_collateral: v1.total_collateral,
+            locked_collateral: v1.locked_collateral,
+            position_count: v1.position_count,
+            open_notional: 0,
+            total_pnl: v1.total_pnl,
+            created_at: v1.created_at,
+            last_activity: v1.last_activity,
+            position_mode: 0,
+            next_position_id: 0,
+            collateral_debt: 0,
+            has_referral: false,
+            reserved: [0; 50],
+        }
+    }
+}
+
+/// Returns the layout version of a `Position` or `UserAccount` body. Versioned
+/// layouts store it in the byte after the discriminator; v1 accounts predate
+/// that byte and are recognised by their length instead, so a stored version
+/// of 1 is never valid.
+pub fn account_version(data: &[u8], current_len: usize, v1_len: usize) -> Option<u8> {
+    match data.len() {
+        len if len == v1_len => Some(1),
+        len if len == current_len => data.get(8).copied().filter(|version| *version > 1),
+        _ => None,
+    }
+}
+
+/// Re-encodes a v1 `Position` or `UserAccount` (discriminator included) in the
+/// current layout, dispatching on the account's layout version.
+pub fn upgrade_account_data(data: &[u8]) -> Result<Vec<u8>> {
+    require!(data.len() >= 8, ErrorCode::UnknownAccountLayout);
+    let (discriminator, mut body) = data.split_at(8);
+
+    let mut upgraded = Vec::new();
+    if discriminator == Position::DISCRIMINATOR {
+        match account_version(data, Position::LEN, PositionV1::LEN) {
+            Some(1) => Position::from(PositionV1::deserialize(&mut body)?).try_serialize(&mut upgraded)?,
+            Some(Position::VERSION) => return err!(ErrorCode::AccountAlreadyMigrated),
+            _ => return err!(ErrorCode::UnknownAccountLayout),
+        }
+    } else if discriminator == UserAccount::DISCRIMINATOR {
+        match account_version(data, UserAccount::LEN, UserAccountV1::LEN) {
+            Some(1) => UserAccount::from(UserAccountV1::deserialize(&mut body)?).try_serialize(&mut upgraded)?,
+            Some(UserAccount::VERSION) => return err!(ErrorCode::AccountAlreadyMigrated),
+            _ => return err!(ErrorCode::UnknownAccountLayout),
+        }
+    } else {
+        return err!(ErrorCode::UnknownAccountLayout);
+    }
+
+    Ok(upgraded)
+}
\ No newline at end of file
@@ -0,0 +1,75 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::utils::{check_price_age, check_slippage};
+
+#[account]
+pub struct Market {
+    pub symbol: [u8; 16],
+    pub bump: u8,
+    pub long_open_interest: u64,
+    pub short_open_interest: u64,
+    pub max_open_interest: u64,
+    pub mark_price: u64,
+    pub last_price_update: i64,
+    pub long_bad_debt: u64,
+    pub long_bankruptcy_price: u64,
+    pub short_bad_debt: u64,
+    pub short_bankruptcy_price: u64,
+}
+
+impl Market {
+    pub const LEN: usize = 8 + 16 + 1 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8;
+
+    /// The mark price, which must be set and no older than `max_age` seconds.
+    pub fn fresh_mark_price(&self, max_age: i64) -> Result<u64> {
+        require_neq!(self.mark_price, 0, ErrorCode::InvalidPrice);
+        check_price_age(self.last_price_update, max_age)?;
+        Ok(self.mark_price)
+    }
+
+    /// The price trades execute at: the fresh mark price, which fails with
+    /// `SlippageExceeded` if it is outside the optional bounds.
+    pub fn execution_price(&self, max_price: Option<u64>, min_price: Option<u64>, max_age: i64) -> Result<u64> {
+        let price = self.fresh_mark_price(max_age)?;
+        check_slippage(price, max_price, min_price)?;
+        Ok(price)
+    }
+
+    pub fn add_open_interest(&mut self, side: u8, size: u64) -> Result<()> {
+        if side == 1 {
+            let oi = self.long_open_interest.checked_add(size).ok_or(ErrorCode::CalculationOverflow)?;
+            require!(oi <= self.max_open_interest, ErrorCode::LongOpenInterestCapExceeded);
+            self.long_open_interest = oi;
+        } else {
+            let oi = self.short_open_interest.checked_add(size).ok_or(ErrorCode::CalculationOverflow)?;
+            require!(oi <= self.max_open_interest, ErrorCode::ShortOpenInterestCapExceeded);
+            self.short_open_interest = oi;
+        }
+        Ok(())
+    }
+
+    pub fn remove_open_interest(&mut self, side: u8, size: u64) -> Result<()> {
+        if side == 1 {
+            self.long_open_interest = self.long_open_interest.checked_sub(size).ok_or(ErrorCode::CalculationUnderflow)?;
+        } else {
+            self.short_open_interest = self.short_open_interest.checked_sub(size).ok_or(ErrorCode::CalculationUnderflow)?;
+        }
+        Ok(())
+    }
+
+    /// Adds `amount` to the side's bad debt. The side's bankruptcy price becomes
+    /// the average over all outstanding debt, weighted by amount, so earlier
+    /// events are still deleveraged at their own price.
+    pub fn record_bad_debt(&mut self, side: u8, amount: u64, bankruptcy_price: u64) -> Result<()> {
+        let (debt, price) = if side == 1 {
+            (&mut self.long_bad_debt, &mut self.long_bankruptcy_price)
+        } else {
+            (&mut self.short_bad_debt, &mut self.short_bankruptcy_price)
+        };
+        let total = debt.checked_add(amount).ok_or(ErrorCode::CalculationOverflow)?;
+        let weighted = (*debt as u128) * (*price as u128) + (amount as u128) * (bankruptcy_price as u128);
+        *price = u64::try_from(weighted / (total.max(1) as u128)).map_err(|_| error!(ErrorCode::CalculationOverflow))?;
+        *debt = total;
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -1,5 +1,23 @@
+pub mod collateral_account;
+pub mod collateral_mint;
+pub mod config;
+pub mod delegate;
+pub mod legacy;
+pub mod market;
 pub mod position;
+pub mod position_book;
+pub mod position_index;
+pub mod referral;
 pub mod user_account;
 
+pub use collateral_account::{CollateralAccount, CollateralBalance};
+pub use collateral_mint::CollateralMint;
+pub use config::Config;
+pub use delegate::Delegate;
+pub use legacy::{account_version, upgrade_account_data, PositionV1, UserAccountV1};
+pub use market::Market;
 pub use position::Position;
+pub use position_book::{PositionBook, PositionSlot};
+pub use position_index::PositionIndex;
+pub use referral::Referral;
 pub use user_account::UserAccount;
\ No newline at end of file
@@ -2,7 +2,9 @@ use anchor_lang::prelude::*;
 
 #[account]
 pub struct Position {
+    pub version: u8,
     pub owner: Pubkey,
+    pub sub_account_id: u16,
     pub symbol: [u8; 16],
     pub bump: u8,
     pub side: u8,
@@ -18,8 +20,10 @@ pub struct Position {
     pub opened_at: i64,
     pub closed_at: i64,
     pub close_price: u64,
+    pub reserved: [u8; 64],
 }
 
 impl Position {
-    pub const LEN: usize = 8 + 32 + 16 + 1 + 1 + 8 + 8 + 2 + 1 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8;
+    pub const VERSION: u8 = 2;
+    pub const LEN: usize = 8 + 1 + 32 + 2 + 16 + 1 + 1 + 8 + 8 + 2 + 1 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 64;
 }
\ No newline at end of file
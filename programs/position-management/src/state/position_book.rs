@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+use crate::utils::MAX_BOOK_SLOTS;
+
+#[zero_copy]
+#[derive(Default)]
+pub struct PositionSlot {
+    pub symbol: [u8; 16],
+    pub size: u64,
+    pub entry_price: u64,
+    pub margin: u64,
+    pub liquidation_price: u64,
+    pub opened_at: i64,
+    pub leverage: u16,
+    pub side: u8,
+    pub status: u8,
+    pub padding: [u8; 4],
+}
+
+impl PositionSlot {
+    pub fn is_open(&self) -> bool {
+        self.status == 1
+    }
+}
+
+/// Fixed-size, zero-copy store for a user's positions, read in place through
+/// `AccountLoader` so health checks and liquidations skip Borsh deserialization.
+#[account(zero_copy)]
+pub struct PositionBook {
+    pub user_account: Pubkey,
+    pub bump: u8,
+    pub open_count: u8,
+    pub padding: [u8; 6],
+    pub slots: [PositionSlot; MAX_BOOK_SLOTS],
+}
+
+impl PositionBook {
+    pub const LEN: usize = 8 + std::mem::size_of::<PositionBook>();
+
+    pub fn free_slot(&self) -> Option<usize> {
+        self.slots.iter().position(|slot| !slot.is_open())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,16 @@
+use anchor_lang::prelude::*;
+
+/// Records a one-way mode user's position on a symbol, so `open_position` can
+/// require it as `existing_position`.
+#[account]
+pub struct PositionIndex {
+    pub user_account: Pubkey,
+    pub symbol: [u8; 16],
+    pub bump: u8,
+    /// The latest position opened on the symbol; it may since have been closed.
+    pub position: Pubkey,
+}
+
+impl PositionIndex {
+    pub const LEN: usize = 8 + 32 + 16 + 1 + 32;
+}
\ No newline at end of file
@@ -0,0 +1,16 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct Referral {
+    pub referee: Pubkey,
+    pub referrer: Pubkey,
+    pub bump: u8,
+    pub referee_volume: u64,
+    pub unclaimed_rebates: u64,
+    pub total_rebates: u64,
+    pub created_at: i64,
+}
+
+impl Referral {
+    pub const LEN: usize = 8 + 32 + 32 + 1 + 8 + 8 + 8 + 8;
+}
\ No newline at end of file
@@ -1,17 +1,75 @@
 use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
 
 #[account]
 pub struct UserAccount {
+    pub version: u8,
     pub owner: Pubkey,
+    pub sub_account_id: u16,
     pub bump: u8,
     pub total_collateral: u64,
     pub locked_collateral: u64,
     pub position_count: u32,
+    pub open_notional: u64,
     pub total_pnl: i64,
     pub created_at: i64,
     pub last_activity: i64,
+    pub position_mode: u8,
+    /// Seeds the next `Position` opened. Only ever increases, so a closed
+    /// position's address is never derived again.
+    pub next_position_id: u32,
+    /// Realized losses and fees that `total_collateral` could not cover. It is
+    /// deducted from the collateral value and repaid first by realized profits.
+    pub collateral_debt: u64,
+    /// Set by `register_referral`. Every fee is then charged with the referral
+    /// account, so trades cannot leave out the referrer's rebate.
+    pub has_referral: bool,
+    pub reserved: [u8; 50],
 }
 
 impl UserAccount {
-    pub const LEN: usize = 8 + 32 + 1 + 8 + 8 + 4 + 8 + 8 + 8;
+    pub const VERSION: u8 = 2;
+    pub const LEN: usize = 8 + 1 + 32 + 2 + 1 + 8 + 8 + 4 + 8 + 8 + 8 + 8 + 1 + 4 + 8 + 1 + 50;
+    /// Sub-account of accounts migrated from v1, which stay at the original
+    /// `[b"user", owner]` address. `initialize_user` never hands it out.
+    pub const LEGACY_SUB_ACCOUNT_ID: u16 = u16::MAX;
+
+    /// The PDA this account lives at: `[b"user", owner, sub_account_id]`, or
+    /// `[b"user", owner]` for the legacy sub-account.
+    pub fn address(&self) -> Result<Pubkey> {
+        let sub_account_id = self.sub_account_id.to_le_bytes();
+        let bump = [self.bump];
+        let seeds: &[&[u8]] = if self.sub_account_id == Self::LEGACY_SUB_ACCOUNT_ID {
+            &[b"user", self.owner.as_ref(), &bump]
+        } else {
+            &[b"user", self.owner.as_ref(), &sub_account_id, &bump]
+        };
+        Pubkey::create_program_address(seeds, &crate::ID).map_err(|_| error!(ErrorCode::Unauthorized))
+    }
+
+    /// Books realized PnL against the user's collateral and `total_pnl`.
+    pub fn settle_pnl(&mut self, pnl: i64) -> Result<()> {
+        self.total_pnl = self.total_pnl.checked_add(pnl).ok_or(ErrorCode::CalculationOverflow)?;
+        if pnl >= 0 {
+            self.credit_collateral(pnl.unsigned_abs())
+        } else {
+            self.debit_collateral(pnl.unsigned_abs())
+        }
+    }
+
+    /// Repays `collateral_debt` and adds the rest to `total_collateral`.
+    pub fn credit_collateral(&mut self, amount: u64) -> Result<()> {
+        let repaid = amount.min(self.collateral_debt);
+        self.collateral_debt -= repaid;
+        self.total_collateral = self.total_collateral.checked_add(amount - repaid).ok_or(ErrorCode::CalculationOverflow)?;
+        Ok(())
+    }
+
+    /// Draws on `total_collateral` and records what it cannot cover as debt.
+    pub fn debit_collateral(&mut self, amount: u64) -> Result<()> {
+        let drawn = amount.min(self.total_collateral);
+        self.total_collateral -= drawn;
+        self.collateral_debt = self.collateral_debt.checked_add(amount - drawn).ok_or(ErrorCode::CalculationOverflow)?;
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,22 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::state::{Delegate, UserAccount};
+
+/// Accepts the account owner, or a delegate holding `permission` that has not expired.
+/// The caller's account constraints must already bind `delegate` to the user account and signer.
+pub fn verify_authority(
+    user: &UserAccount,
+    authority: &Pubkey,
+    delegate: Option<&Delegate>,
+    permission: u8,
+) -> Result<()> {
+    if *authority == user.owner {
+        return Ok(());
+    }
+
+    let delegate = delegate.ok_or(ErrorCode::Unauthorized)?;
+    require!(delegate.permissions & permission == permission, ErrorCode::DelegatePermissionDenied);
+    require!(Clock::get()?.unix_timestamp < delegate.expires_at, ErrorCode::DelegateExpired);
+
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::state::{CollateralAccount, CollateralMint, UserAccount};
+use crate::utils::oracle::check_price_age;
+
+/// Reads `CollateralMint` accounts passed as remaining accounts.
+pub fn load_collateral_mints(accounts: &[AccountInfo]) -> Result<Vec<CollateralMint>> {
+    accounts
+        .iter()
+        .map(|info| {
+            require_keys_eq!(*info.owner, crate::ID, ErrorCode::CollateralMintNotFound);
+            CollateralMint::try_deserialize(&mut &info.try_borrow_data()?[..])
+        })
+        .collect()
+}
+
+/// Haircut-weighted collateral value: the quote `total_collateral` plus every
+/// non-zero mint balance at its oracle price, less `collateral_debt`. `mints`
+/// must include the `CollateralMint` of each of those balances.
+pub fn collateral_value(
+    user: &UserAccount,
+    collateral: Option<&CollateralAccount>,
+    mints: &[CollateralMint],
+) -> Result<u64> {
+    let mut value = user.total_collateral;
+
+    let Some(collateral) = collateral else {
+        return Ok(value.saturating_sub(user.collateral_debt));
+    };
+
+    for balance in collateral.balances.iter().filter(|b| b.amount > 0) {
+        let mint = mints
+            .iter()
+            .find(|m| m.mint == balance.mint)
+            .ok_or(ErrorCode::CollateralMintNotFound)?;
+        value = value.checked_add(mint.weighted_value(balance.amount)?).ok_or(ErrorCode::CalculationOverflow)?;
+    }
+
+    Ok(value.saturating_sub(user.collateral_debt))
+}
+
+/// Fails with `InsufficientCollateral` unless the weighted collateral covers the
+/// user's locked margin plus `additional_margin`, and with `StalePrice` if any of
+/// `mints` was priced more than `max_price_age` seconds ago.
+pub fn check_margin(
+    user: &UserAccount,
+    collateral: Option<&CollateralAccount>,
+    mints: &[CollateralMint],
+    additional_margin: u64,
+    max_price_age: i64,
+) -> Result<()> {
+    let required = user.locked_collateral.checked_add(additional_margin).ok_or(ErrorCode::CalculationOverflow)?;
+    if required == 0 {
+        return Ok(());
+    }
+    for mint in mints {
+        check_price_age(mint.last_price_update, max_price_age)?;
+    }
+    require!(collateral_value(user, collateral, mints)? >= required, ErrorCode::InsufficientCollateral);
+    Ok(())
+}
\ No newline at end of file
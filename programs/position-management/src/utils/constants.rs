@@ -1,4 +1,16 @@
 pub const PRICE_DECIMALS: u32 = 6;
 pub const PRICE_MULTIPLIER: u64 = 1_000_000;
 pub const MIN_LEVERAGE: u16 = 1;
-pub const MAX_LEVERAGE: u16 = 1000;
\ No newline at end of file
+pub const MAX_LEVERAGE: u16 = 1000;
+pub const ADL_SCORE_PRECISION: u128 = 1_000_000;
+pub const BPS_DENOMINATOR: u16 = 10_000;
+pub const MAX_FEE_BPS: u16 = 1_000;
+pub const PERMISSION_OPEN: u8 = 1 << 0;
+pub const PERMISSION_CLOSE: u8 = 1 << 1;
+pub const PERMISSION_MODIFY: u8 = 1 << 2;
+pub const PERMISSION_ALL: u8 = PERMISSION_OPEN | PERMISSION_CLOSE | PERMISSION_MODIFY;
+pub const MAINTENANCE_MARGIN_BPS: u16 = 5_000;
+pub const MAX_BOOK_SLOTS: usize = 32;
+pub const MAX_COLLATERAL_MINTS: usize = 8;
+pub const POSITION_MODE_HEDGE: u8 = 0;
+pub const POSITION_MODE_ONE_WAY: u8 = 1;
\ No newline at end of file
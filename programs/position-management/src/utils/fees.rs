@@ -0,0 +1,32 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::state::{Config, Referral, UserAccount};
+use crate::utils::math::bps_of;
+
+/// Debits the trading fee on `notional` from the user's collateral: first from
+/// `total_collateral`, where realized PnL settles, with any shortfall booked as
+/// `collateral_debt` against the deposited collateral. The referrer's share
+/// accrues on the referral account and the remainder goes to the insurance fund.
+/// Fails with `ReferralRequired` if the user has a referral and it is not passed.
+pub fn charge_trading_fee(
+    config: &mut Config,
+    user: &mut UserAccount,
+    referral: Option<&mut Referral>,
+    notional: u64,
+) -> Result<()> {
+    require!(referral.is_some() || !user.has_referral, ErrorCode::ReferralRequired);
+
+    let fee = bps_of(notional, config.fee_bps)?;
+    user.debit_collateral(fee)?;
+
+    let mut rebate = 0;
+    if let Some(referral) = referral {
+        rebate = bps_of(fee, config.referral_share_bps)?;
+        referral.referee_volume = referral.referee_volume.saturating_add(notional);
+        referral.unclaimed_rebates = referral.unclaimed_rebates.checked_add(rebate).ok_or(ErrorCode::CalculationOverflow)?;
+        referral.total_rebates = referral.total_rebates.checked_add(rebate).ok_or(ErrorCode::CalculationOverflow)?;
+    }
+
+    config.insurance_fund = config.insurance_fund.checked_add(fee - rebate).ok_or(ErrorCode::CalculationOverflow)?;
+    Ok(())
+}
\ No newline at end of file
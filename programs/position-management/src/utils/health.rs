@@ -0,0 +1,157 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::state::{CollateralAccount, Market, Position, PositionBook, UserAccount};
+use crate::utils::collateral::{collateral_value, load_collateral_mints};
+use crate::utils::constants::MAINTENANCE_MARGIN_BPS;
+use crate::utils::math::{bps_of, unrealized_pnl};
+use crate::utils::oracle::check_price_age;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AccountHealth {
+    pub equity: i64,
+    pub maintenance_margin: u64,
+    pub open_positions: u32,
+    pub healthy: bool,
+}
+
+/// An open `Position` or book slot with its market's fresh mark price.
+pub struct OpenPosition {
+    pub side: u8,
+    pub entry_price: u64,
+    pub size: u64,
+    pub margin: u64,
+    pub mark_price: u64,
+}
+
+/// Every open position of `user`, from `accounts` laid out as `(position, market)`
+/// pairs for each open `Position` followed by the `Market` of every symbol held in
+/// `position_book`, along with the accounts left after them. Fails with `StalePrice`
+/// on a mark older than `max_price_age`, and with `LockedCollateralMismatch` unless
+/// the margins add up to `locked_collateral`, so none is left out.
+pub fn load_open_positions<'info>(
+    user: &UserAccount,
+    position_book: Option<&AccountLoader<'info, PositionBook>>,
+    accounts: &'info [AccountInfo<'info>],
+    max_price_age: i64,
+) -> Result<(Vec<OpenPosition>, &'info [AccountInfo<'info>])> {
+    let pair_count = user.position_count as usize;
+    require!(accounts.len() >= pair_count * 2, ErrorCode::PositionNotFound);
+    let (pairs, mut rest) = accounts.split_at(pair_count * 2);
+
+    let mut positions = Vec::with_capacity(pair_count);
+    let mut margin: u64 = 0;
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(pair_count);
+    for pair in pairs.chunks(2) {
+        require!(!seen.contains(pair[0].key), ErrorCode::DuplicatePosition);
+        seen.push(*pair[0].key);
+
+        let position = Account::<Position>::try_from(&pair[0])?;
+        let market = Account::<Market>::try_from(&pair[1])?;
+        require!(
+            position.owner == user.owner && position.sub_account_id == user.sub_account_id,
+            ErrorCode::CannotModifyOthersPosition
+        );
+        require_eq!(position.status, 1, ErrorCode::PositionAlreadyClosed);
+        require!(position.symbol == market.symbol, ErrorCode::MarketMismatch);
+
+        margin = margin.checked_add(position.margin).ok_or(ErrorCode::CalculationOverflow)?;
+        positions.push(OpenPosition {
+            side: position.side,
+            entry_price: position.entry_price,
+            size: position.size,
+            margin: position.margin,
+            mark_price: market.fresh_mark_price(max_price_age)?,
+        });
+    }
+
+    if let Some(position_book) = position_book {
+        let book = position_book.load()?;
+        let mut symbols: Vec<[u8; 16]> = Vec::new();
+        for slot in book.slots.iter().filter(|slot| slot.is_open()) {
+            if !symbols.contains(&slot.symbol) {
+                symbols.push(slot.symbol);
+            }
+        }
+        require!(rest.len() >= symbols.len(), ErrorCode::MarketMismatch);
+        let (market_infos, after) = rest.split_at(symbols.len());
+        let markets = market_infos.iter().map(Account::<Market>::try_from).collect::<Result<Vec<_>>>()?;
+        rest = after;
+
+        for slot in book.slots.iter().filter(|slot| slot.is_open()) {
+            let market = markets
+                .iter()
+                .find(|market| market.symbol == slot.symbol)
+                .ok_or(ErrorCode::MarketMismatch)?;
+            margin = margin.checked_add(slot.margin).ok_or(ErrorCode::CalculationOverflow)?;
+            positions.push(OpenPosition {
+                side: slot.side,
+                entry_price: slot.entry_price,
+                size: slot.size,
+                margin: slot.margin,
+                mark_price: market.fresh_mark_price(max_price_age)?,
+            });
+        }
+    }
+
+    require_eq!(margin, user.locked_collateral, ErrorCode::LockedCollateralMismatch);
+    Ok((positions, rest))
+}
+
+/// Health of `user` over every open position and its haircut-weighted collateral.
+/// `accounts` are laid out as for `load_open_positions`, followed by the
+/// `CollateralMint`s of every non-zero balance in `collateral`.
+pub fn account_health<'info>(
+    user: &UserAccount,
+    position_book: Option<&AccountLoader<'info, PositionBook>>,
+    collateral: Option<&CollateralAccount>,
+    accounts: &'info [AccountInfo<'info>],
+    max_price_age: i64,
+) -> Result<AccountHealth> {
+    let (positions, rest) = load_open_positions(user, position_book, accounts, max_price_age)?;
+
+    let mut health = HealthAccumulator::default();
+    for position in &positions {
+        health.add_position(position.side, position.entry_price, position.size, position.margin, position.mark_price)?;
+    }
+
+    let mints = load_collateral_mints(rest)?;
+    for mint in &mints {
+        check_price_age(mint.last_price_update, max_price_age)?;
+    }
+    health.finish(user, collateral_value(user, collateral, &mints)?)
+}
+
+/// Cross-margin health: free collateral plus each position's margin and PnL at the
+/// mark, against `MAINTENANCE_MARGIN_BPS` of the total margin in use.
+#[derive(Default)]
+pub struct HealthAccumulator {
+    equity: i128,
+    maintenance_margin: u128,
+    open_positions: u32,
+}
+
+impl HealthAccumulator {
+    pub fn add_position(&mut self, side: u8, entry_price: u64, size: u64, margin: u64, mark_price: u64) -> Result<()> {
+        require_neq!(mark_price, 0, ErrorCode::InvalidPrice);
+
+        let pnl = unrealized_pnl(side, entry_price, mark_price, size)?;
+        self.equity += (margin as i128) + (pnl as i128);
+        self.maintenance_margin += bps_of(margin, MAINTENANCE_MARGIN_BPS)? as u128;
+        self.open_positions += 1;
+        Ok(())
+    }
+
+    /// `collateral_value` is the user's weighted collateral net of debt, as
+    /// returned by `collateral_value`.
+    pub fn finish(self, user: &UserAccount, collateral_value: u64) -> Result<AccountHealth> {
+        let free_collateral = collateral_value as i128 - user.locked_collateral as i128;
+        let equity = self.equity + free_collateral;
+
+        Ok(AccountHealth {
+            equity: i64::try_from(equity).map_err(|_| error!(ErrorCode::CalculationOverflow))?,
+            maintenance_margin: u64::try_from(self.maintenance_margin).map_err(|_| error!(ErrorCode::CalculationOverflow))?,
+            open_positions: self.open_positions,
+            healthy: equity >= self.maintenance_margin as i128,
+        })
+    }
+}
\ No newline at end of file
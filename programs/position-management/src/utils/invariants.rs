@@ -0,0 +1,103 @@
+use std::fmt;
+
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::state::{Position, PositionBook, UserAccount};
+
+/// A way in which a `UserAccount`'s totals disagree with its positions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// `locked_collateral` is not the sum of open position and book slot margins.
+    LockedCollateralMismatch { locked_collateral: u64, open_margin: u128 },
+    /// `position_count` is not the number of open `Position` accounts.
+    PositionCountMismatch { position_count: u32, open_positions: u32 },
+    /// More collateral is locked than the user's collateral is worth.
+    LockedCollateralExceedsCollateral { locked_collateral: u64, collateral_value: u64 },
+}
+
+impl InvariantViolation {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::LockedCollateralMismatch { .. } => ErrorCode::LockedCollateralMismatch,
+            Self::PositionCountMismatch { .. } => ErrorCode::PositionCountMismatch,
+            Self::LockedCollateralExceedsCollateral { .. } => ErrorCode::LockedCollateralExceedsCollateral,
+        }
+    }
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LockedCollateralMismatch { locked_collateral, open_margin } => {
+                write!(f, "locked_collateral {} != open margin {}", locked_collateral, open_margin)
+            }
+            Self::PositionCountMismatch { position_count, open_positions } => {
+                write!(f, "position_count {} != open positions {}", position_count, open_positions)
+            }
+            Self::LockedCollateralExceedsCollateral { locked_collateral, collateral_value } => {
+                write!(f, "locked_collateral {} > collateral value {}", locked_collateral, collateral_value)
+            }
+        }
+    }
+}
+
+/// Totals over a user's positions, compared against its `UserAccount`. Closed
+/// positions are skipped, so every position the user has ever opened may be added.
+#[derive(Default)]
+pub struct UserInvariants {
+    open_margin: u128,
+    open_positions: u32,
+}
+
+impl UserInvariants {
+    pub fn add_position(&mut self, position: &Position) {
+        if position.status == 1 {
+            self.open_margin += position.margin as u128;
+            self.open_positions += 1;
+        }
+    }
+
+    /// Book slots lock margin but are not counted in `position_count`.
+    pub fn add_book(&mut self, book: &PositionBook) {
+        for slot in book.slots.iter().filter(|slot| slot.is_open()) {
+            self.open_margin += slot.margin as u128;
+        }
+    }
+
+    /// `collateral_value` is the user's haircut-weighted collateral, as computed
+    /// by `collateral_value`; mint balances count alongside `total_collateral`.
+    pub fn violations(&self, user: &UserAccount, collateral_value: u64) -> Vec<InvariantViolation> {
+        let mut violations = Vec::new();
+        if user.locked_collateral as u128 != self.open_margin {
+            violations.push(InvariantViolation::LockedCollateralMismatch {
+                locked_collateral: user.locked_collateral,
+                open_margin: self.open_margin,
+            });
+        }
+        if user.position_count != self.open_positions {
+            violations.push(InvariantViolation::PositionCountMismatch {
+                position_count: user.position_count,
+                open_positions: self.open_positions,
+            });
+        }
+        if user.locked_collateral > collateral_value {
+            violations.push(InvariantViolation::LockedCollateralExceedsCollateral {
+                locked_collateral: user.locked_collateral,
+                collateral_value,
+            });
+        }
+        violations
+    }
+
+    /// Logs every violation and fails with the first one's error code.
+    pub fn verify(&self, user: &UserAccount, collateral_value: u64) -> Result<()> {
+        let violations = self.violations(user, collateral_value);
+        for violation in &violations {
+            msg!("Invariant violated: {}", violation);
+        }
+        match violations.first() {
+            Some(violation) => Err(error!(violation.error_code())),
+            None => Ok(()),
+        }
+    }
+}
\ No newline at end of file
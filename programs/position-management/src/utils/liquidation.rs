@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::state::{Config, Market};
+use crate::utils::math::{bankruptcy_price, unrealized_pnl};
+
+/// Checks that the market's mark price has crossed `liquidation_price`, then settles the
+/// forfeited margin against the insurance fund. Whatever margin survives the loss goes to
+/// the fund; a loss beyond the margin is drawn from the fund, and any shortfall is left on
+/// the market as bad debt for auto-deleveraging. Returns the mark price used, which
+/// must be no older than `Config.max_price_age`.
+pub fn settle_liquidation(
+    config: &mut Config,
+    market: &mut Market,
+    side: u8,
+    entry_price: u64,
+    size: u64,
+    margin: u64,
+    liquidation_price: u64,
+) -> Result<u64> {
+    let mark_price = market.fresh_mark_price(config.max_price_age)?;
+
+    let liquidatable = if side == 1 {
+        mark_price <= liquidation_price
+    } else {
+        mark_price >= liquidation_price
+    };
+    require!(liquidatable, ErrorCode::PositionNotLiquidatable);
+
+    let pnl = unrealized_pnl(side, entry_price, mark_price, size)?;
+    let equity = (margin as i128) + (pnl as i128);
+    if equity >= 0 {
+        config.insurance_fund = config.insurance_fund.checked_add(equity as u64).ok_or(ErrorCode::CalculationOverflow)?;
+    } else {
+        let deficit = u64::try_from(-equity).map_err(|_| error!(ErrorCode::CalculationOverflow))?;
+        let covered = deficit.min(config.insurance_fund);
+        config.insurance_fund -= covered;
+
+        let bad_debt = deficit - covered;
+        if bad_debt > 0 {
+            let price = bankruptcy_price(side, entry_price, margin, size)?;
+            market.record_bad_debt(side, bad_debt, price)?;
+            msg!("Bad debt recorded: {}", bad_debt);
+        }
+    }
+
+    Ok(mark_price)
+}
\ No newline at end of file
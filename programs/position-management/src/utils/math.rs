@@ -0,0 +1,84 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::utils::constants::{ADL_SCORE_PRECISION, BPS_DENOMINATOR};
+
+pub fn notional_value(size: u64, price: u64) -> Result<u64> {
+    let notional = (size as u128).checked_mul(price as u128).ok_or(ErrorCode::CalculationOverflow)?;
+    u64::try_from(notional).map_err(|_| error!(ErrorCode::CalculationOverflow))
+}
+
+pub fn initial_margin(entry_price: u64, leverage: u16) -> Result<u64> {
+    Ok(entry_price.checked_div(leverage as u64).ok_or(ErrorCode::CalculationUnderflow)?)
+}
+
+pub fn liquidation_price(side: u8, entry_price: u64) -> Result<u64> {
+    let percent: u128 = if side == 1 { 90 } else { 110 };
+    u64::try_from(entry_price as u128 * percent / 100).map_err(|_| error!(ErrorCode::CalculationOverflow))
+}
+
+/// Applies a signed change to an unsigned quantity such as size or margin.
+pub fn apply_delta(value: u64, delta: i64) -> Result<u64> {
+    if delta >= 0 {
+        Ok(value.checked_add(delta.unsigned_abs()).ok_or(ErrorCode::CalculationOverflow)?)
+    } else {
+        Ok(value.checked_sub(delta.unsigned_abs()).ok_or(ErrorCode::CalculationUnderflow)?)
+    }
+}
+
+/// Share of `amount` attributable to `part` of `total`, rounded down.
+pub fn pro_rata(amount: u64, part: u64, total: u64) -> Result<u64> {
+    require!(part <= total, ErrorCode::InvalidPositionSize);
+    let share = (amount as u128 * part as u128).checked_div(total as u128).ok_or(ErrorCode::InvalidPositionSize)?;
+    Ok(share as u64)
+}
+
+/// Realized PnL of a liquidation, which forfeits the whole margin.
+pub fn liquidation_loss(margin: u64) -> Result<i64> {
+    let margin = i64::try_from(margin).map_err(|_| error!(ErrorCode::CalculationOverflow))?;
+    Ok(-margin)
+}
+
+pub fn bps_of(amount: u64, bps: u16) -> Result<u64> {
+    let value = (amount as u128) * (bps as u128) / (BPS_DENOMINATOR as u128);
+    u64::try_from(value).map_err(|_| error!(ErrorCode::CalculationOverflow))
+}
+
+pub fn unrealized_pnl(side: u8, entry_price: u64, price: u64, size: u64) -> Result<i64> {
+    let diff = if side == 1 {
+        price as i128 - entry_price as i128
+    } else {
+        entry_price as i128 - price as i128
+    };
+    let pnl = diff.checked_mul(size as i128).ok_or(ErrorCode::CalculationOverflow)?;
+    i64::try_from(pnl).map_err(|_| error!(ErrorCode::CalculationOverflow))
+}
+
+/// Fails with `SlippageExceeded` if `price` is outside the optional bounds.
+pub fn check_slippage(price: u64, max_price: Option<u64>, min_price: Option<u64>) -> Result<()> {
+    if let Some(max_price) = max_price {
+        require!(price <= max_price, ErrorCode::SlippageExceeded);
+    }
+    if let Some(min_price) = min_price {
+        require!(price >= min_price, ErrorCode::SlippageExceeded);
+    }
+    Ok(())
+}
+
+/// Price at which `margin + unrealized_pnl` reaches zero.
+pub fn bankruptcy_price(side: u8, entry_price: u64, margin: u64, size: u64) -> Result<u64> {
+    let per_unit = margin.checked_div(size).ok_or(ErrorCode::InvalidPositionSize)?;
+    if side == 1 {
+        Ok(entry_price.saturating_sub(per_unit))
+    } else {
+        Ok(entry_price.checked_add(per_unit).ok_or(ErrorCode::CalculationOverflow)?)
+    }
+}
+
+/// Auto-deleveraging rank: PnL as a fraction of margin, scaled by leverage.
+/// Higher scores are deleveraged first; losing positions score zero.
+pub fn adl_score(unrealized_pnl: i64, margin: u64, leverage: u16) -> u128 {
+    if unrealized_pnl <= 0 {
+        return 0;
+    }
+    (unrealized_pnl as u128) * (leverage as u128) * ADL_SCORE_PRECISION / (margin.max(1) as u128)
+}
\ No newline at end of file
@@ -1,3 +1,21 @@
+pub mod authority;
+pub mod collateral;
 pub mod constants;
+pub mod fees;
+pub mod health;
+pub mod invariants;
+pub mod liquidation;
+pub mod math;
+pub mod oracle;
+pub mod symbol;
 
-pub use constants::*;
\ No newline at end of file
+pub use authority::*;
+pub use collateral::*;
+pub use constants::*;
+pub use fees::*;
+pub use health::*;
+pub use invariants::*;
+pub use liquidation::*;
+pub use math::*;
+pub use oracle::*;
+pub use symbol::*;
\ No newline at end of file
@@ -0,0 +1,10 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+
+/// Fails with `StalePrice` if a price last set at `updated_at` is more than
+/// `max_age` seconds old.
+pub fn check_price_age(updated_at: i64, max_age: i64) -> Result<()> {
+    let age = Clock::get()?.unix_timestamp.saturating_sub(updated_at);
+    require!(age <= max_age, ErrorCode::StalePrice);
+    Ok(())
+}
\ No newline at end of file
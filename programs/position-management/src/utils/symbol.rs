@@ -0,0 +1,7 @@
+pub fn symbol_bytes(symbol: &str) -> [u8; 16] {
+    let mut sym_bytes = [0u8; 16];
+    let s = symbol.as_bytes();
+    let len = s.len().min(16);
+    sym_bytes[..len].copy_from_slice(&s[..len]);
+    sym_bytes
+}
\ No newline at end of file
@@ -0,0 +1,98 @@
+//! Properties of the margin and PnL helpers the handlers are built on. Each
+//! helper is checked against the exact result computed in `i128`: it must never
+//! panic, and must return an error exactly when that result does not fit.
+
+use position_management::utils::{
+    apply_delta, bankruptcy_price, liquidation_loss, liquidation_price, notional_value, pro_rata, unrealized_pnl,
+};
+use proptest::prelude::*;
+
+/// Values biased towards the edges of their type, where overflows live.
+fn price() -> impl Strategy<Value = u64> {
+    prop_oneof![1u64..1_000_000_000_000, any::<u64>(), Just(u64::MAX), Just(i64::MAX as u64 + 1)]
+}
+
+fn side() -> impl Strategy<Value = u8> {
+    1u8..=2
+}
+
+proptest! {
+    #[test]
+    fn unrealized_pnl_is_exact_or_an_error(side in side(), entry in price(), exit in price(), size in price()) {
+        let diff = if side == 1 { exit as i128 - entry as i128 } else { entry as i128 - exit as i128 };
+        let exact = diff.checked_mul(size as i128).and_then(|pnl| i64::try_from(pnl).ok());
+        prop_assert_eq!(unrealized_pnl(side, entry, exit, size).ok(), exact);
+    }
+
+    #[test]
+    fn unrealized_pnl_is_symmetric_between_sides(entry in price(), exit in price(), size in price()) {
+        let long = unrealized_pnl(1, entry, exit, size);
+        let short = unrealized_pnl(2, entry, exit, size);
+        match (long, short) {
+            (Ok(long), Ok(short)) => prop_assert_eq!(long as i128, -(short as i128)),
+            // `i64::MIN` has no positive counterpart, so only one side may fit.
+            (Ok(pnl), Err(_)) | (Err(_), Ok(pnl)) => prop_assert_eq!(pnl, i64::MIN),
+            (Err(_), Err(_)) => {}
+        }
+        prop_assert_eq!(unrealized_pnl(1, entry, entry, size).ok(), Some(0));
+    }
+
+    #[test]
+    fn notional_value_is_exact_or_an_error(size in price(), price in price()) {
+        let exact = u64::try_from(size as u128 * price as u128).ok();
+        prop_assert_eq!(notional_value(size, price).ok(), exact);
+    }
+
+    #[test]
+    fn apply_delta_is_exact_or_an_error(value in any::<u64>(), delta in any::<i64>()) {
+        let exact = u64::try_from(value as i128 + delta as i128).ok();
+        prop_assert_eq!(apply_delta(value, delta).ok(), exact);
+    }
+
+    #[test]
+    fn pro_rata_never_exceeds_the_amount(amount in any::<u64>(), total in 1u64.., part in any::<u64>()) {
+        let part = part % (total as u128 + 1) as u64;
+        let share = pro_rata(amount, part, total).unwrap();
+        prop_assert!(share <= amount);
+        prop_assert_eq!(share as u128, amount as u128 * part as u128 / total as u128);
+        prop_assert_eq!(pro_rata(amount, total, total).ok(), Some(amount));
+    }
+
+    #[test]
+    fn pro_rata_rejects_parts_outside_the_total(amount in any::<u64>(), total in any::<u64>(), part in any::<u64>()) {
+        prop_assume!(total == 0 || part > total);
+        prop_assert!(pro_rata(amount, part, total).is_err());
+    }
+
+    #[test]
+    fn liquidation_price_brackets_the_entry(entry in price()) {
+        let long = liquidation_price(1, entry).unwrap();
+        prop_assert!(long <= entry);
+        match liquidation_price(2, entry) {
+            Ok(short) => prop_assert!(short >= entry),
+            Err(_) => prop_assert!(entry as u128 * 110 / 100 > u64::MAX as u128),
+        }
+    }
+
+    #[test]
+    fn liquidation_loss_is_the_whole_margin(margin in any::<u64>()) {
+        match liquidation_loss(margin) {
+            Ok(loss) => prop_assert_eq!(loss as i128, -(margin as i128)),
+            Err(_) => prop_assert!(margin > i64::MAX as u64),
+        }
+    }
+
+    #[test]
+    fn bankruptcy_price_moves_against_the_position(
+        side in side(),
+        entry in price(),
+        margin in any::<u64>(),
+        size in any::<u64>(),
+    ) {
+        match bankruptcy_price(side, entry, margin, size) {
+            Ok(price) if side == 1 => prop_assert!(price <= entry),
+            Ok(price) => prop_assert!(price >= entry),
+            Err(_) => prop_assert!(size == 0 || side == 2),
+        }
+    }
+}
\ No newline at end of file
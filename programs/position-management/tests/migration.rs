@@ -0,0 +1,177 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use position_management::errors::ErrorCode;
+use position_management::state::{upgrade_account_data, Position, PositionV1, UserAccount, UserAccountV1};
+
+fn encode<T: AnchorSerialize>(discriminator: &[u8], value: &T) -> Vec<u8> {
+    let mut data = discriminator.to_vec();
+    value.serialize(&mut data).unwrap();
+    data
+}
+
+fn position_v1() -> PositionV1 {
+    PositionV1 {
+        owner: Pubkey::new_unique(),
+        symbol: *b"BTC-PERP\0\0\0\0\0\0\0\0",
+        bump: 254,
+        side: 2,
+        size: 1_000,
+        entry_price: 50_000_000_000,
+        leverage: 10,
+        status: 1,
+        margin: 5_000_000_000,
+        liquidation_price: 55_000_000_000,
+        mark_price: 49_000_000_000,
+        unrealized_pnl: 1_000_000_000_000,
+        realized_pnl: -7,
+        opened_at: 1_700_000_000,
+        closed_at: 0,
+        close_price: 0,
+    }
+}
+
+fn user_account_v1() -> UserAccountV1 {
+    UserAccountV1 {
+        owner: Pubkey::new_unique(),
+        bump: 253,
+        total_collateral: 10_000,
+        locked_collateral: 4_000,
+        position_count: 2,
+        total_pnl: -250,
+        created_at: 1_700_000_000,
+        last_activity: 1_700_000_500,
+    }
+}
+
+#[test]
+fn v1_position_deserializes_and_upgrades_to_v2() {
+    let v1 = position_v1();
+    let data = encode(Position::DISCRIMINATOR, &v1);
+    assert_eq!(data.len(), PositionV1::LEN);
+
+    let upgraded = upgrade_account_data(&data).unwrap();
+    assert_eq!(upgraded.len(), Position::LEN);
+
+    let position = Position::try_deserialize(&mut upgraded.as_slice()).unwrap();
+    assert_eq!(position.version, Position::VERSION);
+    assert_eq!(position.owner, v1.owner);
+    assert_eq!(position.sub_account_id, UserAccount::LEGACY_SUB_ACCOUNT_ID);
+    assert_eq!(position.symbol, v1.symbol);
+    assert_eq!(position.bump, v1.bump);
+    assert_eq!(position.side, v1.side);
+    assert_eq!(position.size, v1.size);
+    assert_eq!(position.entry_price, v1.entry_price);
+    assert_eq!(position.leverage, v1.leverage);
+    assert_eq!(position.status, v1.status);
+    assert_eq!(position.margin, v1.margin);
+    assert_eq!(position.liquidation_price, v1.liquidation_price);
+    assert_eq!(position.mark_price, v1.mark_price);
+    assert_eq!(position.unrealized_pnl, v1.unrealized_pnl);
+    assert_eq!(position.realized_pnl, v1.realized_pnl);
+    assert_eq!(position.opened_at, v1.opened_at);
+    assert_eq!(position.closed_at, v1.closed_at);
+    assert_eq!(position.close_price, v1.close_price);
+    assert_eq!(position.reserved, [0; 64]);
+}
+
+#[test]
+fn v1_user_account_deserializes_and_upgrades_to_v2() {
+    let v1 = user_account_v1();
+    let data = encode(UserAccount::DISCRIMINATOR, &v1);
+    assert_eq!(data.len(), UserAccountV1::LEN);
+
+    let upgraded = upgrade_account_data(&data).unwrap();
+    assert_eq!(upgraded.len(), UserAccount::LEN);
+
+    let user = UserAccount::try_deserialize(&mut upgraded.as_slice()).unwrap();
+    assert_eq!(user.version, UserAccount::VERSION);
+    assert_eq!(user.owner, v1.owner);
+    assert_eq!(user.sub_account_id, UserAccount::LEGACY_SUB_ACCOUNT_ID);
+    assert_eq!(user.bump, v1.bump);
+    assert_eq!(user.total_collateral, v1.total_collateral);
+    assert_eq!(user.locked_collateral, v1.locked_collateral);
+    assert_eq!(user.position_count, v1.position_count);
+    assert_eq!(user.open_notional, 0);
+    assert_eq!(user.total_pnl, v1.total_pnl);
+    assert_eq!(user.created_at, v1.created_at);
+    assert_eq!(user.last_activity, v1.last_activity);
+    assert_eq!(user.position_mode, 0);
+    assert_eq!(user.next_position_id, 0);
+    assert_eq!(user.collateral_debt, 0);
+    assert!(!user.has_referral);
+    assert_eq!(user.reserved, [0; 50]);
+}
+
+#[test]
+fn v1_accounts_do_not_deserialize_as_v2() {
+    let data = encode(Position::DISCRIMINATOR, &position_v1());
+    assert!(Position::try_deserialize(&mut data.as_slice()).is_err());
+
+    let data = encode(UserAccount::DISCRIMINATOR, &user_account_v1());
+    assert!(UserAccount::try_deserialize(&mut data.as_slice()).is_err());
+}
+
+#[test]
+fn upgraded_accounts_are_not_migrated_twice() {
+    let data = encode(Position::DISCRIMINATOR, &position_v1());
+    let upgraded = upgrade_account_data(&data).unwrap();
+    assert_eq!(
+        upgrade_account_data(&upgraded).unwrap_err(),
+        ErrorCode::AccountAlreadyMigrated.into()
+    );
+
+    let data = encode(UserAccount::DISCRIMINATOR, &user_account_v1());
+    let upgraded = upgrade_account_data(&data).unwrap();
+    assert_eq!(
+        upgrade_account_data(&upgraded).unwrap_err(),
+        ErrorCode::AccountAlreadyMigrated.into()
+    );
+}
+
+#[test]
+fn unknown_layouts_are_rejected() {
+    let data = encode(&[0u8; 8], &position_v1());
+    assert_eq!(
+        upgrade_account_data(&data).unwrap_err(),
+        ErrorCode::UnknownAccountLayout.into()
+    );
+
+    let mut data = encode(Position::DISCRIMINATOR, &position_v1());
+    data.pop();
+    assert_eq!(
+        upgrade_account_data(&data).unwrap_err(),
+        ErrorCode::UnknownAccountLayout.into()
+    );
+}
+
+#[test]
+fn current_length_accounts_dispatch_on_the_version_byte() {
+    let data = encode(Position::DISCRIMINATOR, &position_v1());
+    let mut upgraded = upgrade_account_data(&data).unwrap();
+    upgraded[8] = Position::VERSION + 1;
+    assert_eq!(
+        upgrade_account_data(&upgraded).unwrap_err(),
+        ErrorCode::UnknownAccountLayout.into()
+    );
+
+    let data = encode(UserAccount::DISCRIMINATOR, &user_account_v1());
+    let mut upgraded = upgrade_account_data(&data).unwrap();
+    upgraded[8] = 1;
+    assert_eq!(
+        upgrade_account_data(&upgraded).unwrap_err(),
+        ErrorCode::UnknownAccountLayout.into()
+    );
+}
+
+#[test]
+fn v2_lengths_match_serialized_size() {
+    let position: Position = position_v1().into();
+    let mut data = Vec::new();
+    position.try_serialize(&mut data).unwrap();
+    assert_eq!(data.len(), Position::LEN);
+
+    let user: UserAccount = user_account_v1().into();
+    let mut data = Vec::new();
+    user.try_serialize(&mut data).unwrap();
+    assert_eq!(data.len(), UserAccount::LEN);
+}
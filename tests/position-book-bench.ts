@@ -0,0 +1,174 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program, BN } from "@coral-xyz/anchor";
+import { ComputeBudgetProgram, PublicKey } from "@solana/web3.js";
+import { createMint, getOrCreateAssociatedTokenAccount, mintTo } from "@solana/spl-token";
+import { PositionManagement } from "../target/types/position_management";
+
+// Compares the compute units spent on a cross-margin health check over
+// individual `Position` accounts with the same check over a `PositionBook`.
+// Run against a local validator: `anchor run bench`.
+describe("position-book-bench", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+
+  const program = anchor.workspace.positionManagement as Program<PositionManagement>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+  const owner = provider.wallet.publicKey;
+
+  const SYMBOL = "SOL-PERP";
+  const POSITION_COUNTS = [1, 4, 8, 12];
+
+  const u16le = (value: number) => new BN(value).toArrayLike(Buffer, "le", 2);
+  const u32le = (value: number) => new BN(value).toArrayLike(Buffer, "le", 4);
+  const symbolBytes = (symbol: string) => {
+    const bytes = Buffer.alloc(16);
+    bytes.write(symbol);
+    return bytes;
+  };
+  const pda = (seeds: Buffer[]) =>
+    PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+
+  const configPda = pda([Buffer.from("config")]);
+  const marketPda = pda([Buffer.from("market"), symbolBytes(SYMBOL)]);
+  const payer = (provider.wallet as anchor.Wallet).payer;
+  let collateralMint: PublicKey;
+  let collateralMintPda: PublicKey;
+  let ownerTokenAccount: PublicKey;
+  const computeLimit = ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 });
+
+  const unitsConsumed = async (signature: string) => {
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    return tx.meta.computeUnitsConsumed;
+  };
+
+  before(async () => {
+    await program.methods.initializeConfig(new BN("18446744073709551615"), 0, 0, new BN(60)).rpc();
+    await program.methods
+      .initializeMarket(SYMBOL, new BN("18446744073709551615"))
+      .accountsPartial({ config: configPda, market: marketPda })
+      .rpc();
+    await program.methods
+      .updateMarkPrice(new BN(100_000_000))
+      .accountsPartial({ config: configPda, market: marketPda })
+      .rpc();
+
+    // A 6-decimal stablecoin priced at 1 quote unit per base unit, with no haircut.
+    collateralMint = await createMint(provider.connection, payer, owner, null, 6);
+    collateralMintPda = pda([Buffer.from("collateral_mint"), collateralMint.toBuffer()]);
+    ownerTokenAccount = (
+      await getOrCreateAssociatedTokenAccount(provider.connection, payer, collateralMint, owner)
+    ).address;
+    await mintTo(provider.connection, payer, collateralMint, ownerTokenAccount, owner, 100_000_000_000);
+    await program.methods
+      .registerCollateralMint(owner, 0)
+      .accountsPartial({ config: configPda, mint: collateralMint, collateralMint: collateralMintPda })
+      .rpc();
+    await program.methods
+      .updateCollateralPrice(new BN(1_000_000))
+      .accountsPartial({ collateralMint: collateralMintPda })
+      .rpc();
+  });
+
+  const fundUser = async (userAccount: PublicKey) => {
+    const collateralAccount = pda([Buffer.from("collateral"), userAccount.toBuffer()]);
+    await program.methods.initializeCollateralAccount().accountsPartial({ userAccount, collateralAccount }).rpc();
+    await program.methods
+      .depositCollateral(new BN(1_000_000_000))
+      .accountsPartial({
+        userAccount,
+        collateralAccount,
+        collateralMint: collateralMintPda,
+        mint: collateralMint,
+        ownerTokenAccount,
+      })
+      .rpc();
+    return collateralAccount;
+  };
+
+  POSITION_COUNTS.forEach((count, index) => {
+    it(`health check with ${count} positions`, async () => {
+      // One pair of sub-accounts per run: one for each layout.
+      const accountsSubId = index * 2;
+      const bookSubId = index * 2 + 1;
+      const accountsUser = pda([Buffer.from("user"), owner.toBuffer(), u16le(accountsSubId)]);
+      const bookUser = pda([Buffer.from("user"), owner.toBuffer(), u16le(bookSubId)]);
+      const book = pda([Buffer.from("position_book"), bookUser.toBuffer()]);
+
+      await program.methods.initializeUser(accountsSubId).accountsPartial({ userAccount: accountsUser }).rpc();
+      await program.methods.initializeUser(bookSubId).accountsPartial({ userAccount: bookUser }).rpc();
+      const accountsCollateral = await fundUser(accountsUser);
+      const bookCollateral = await fundUser(bookUser);
+      const collateralMints = [{ pubkey: collateralMintPda, isSigner: false, isWritable: false }];
+      await program.methods
+        .initializePositionBook()
+        .accountsPartial({ userAccount: bookUser, positionBook: book })
+        .rpc();
+
+      const remaining = [];
+      for (let i = 0; i < count; i++) {
+        const position = pda([
+          Buffer.from("position"),
+          owner.toBuffer(),
+          u16le(accountsSubId),
+          u32le(i),
+        ]);
+        await program.methods
+          .openPosition(SYMBOL, 1, new BN(1), 10, null, null, false)
+          .accountsPartial({
+            userAccount: accountsUser,
+            position,
+            config: configPda,
+            market: marketPda,
+            collateralAccount: accountsCollateral,
+          })
+          .remainingAccounts(collateralMints)
+          .rpc();
+        await program.methods
+          .openBookPosition(SYMBOL, 1, new BN(1), 10, null, null)
+          .accountsPartial({
+            userAccount: bookUser,
+            positionBook: book,
+            config: configPda,
+            market: marketPda,
+            collateralAccount: bookCollateral,
+          })
+          .remainingAccounts(collateralMints)
+          .rpc();
+        remaining.push(
+          { pubkey: position, isSigner: false, isWritable: false },
+          { pubkey: marketPda, isSigner: false, isWritable: false }
+        );
+      }
+
+      const accountsSignature = await program.methods
+        .checkPositionsHealth()
+        .accountsPartial({
+          userAccount: accountsUser,
+          config: configPda,
+          positionBook: null,
+          collateralAccount: accountsCollateral,
+        })
+        .remainingAccounts([...remaining, ...collateralMints])
+        .preInstructions([computeLimit])
+        .rpc({ commitment: "confirmed" });
+      const bookSignature = await program.methods
+        .checkHealth()
+        .accountsPartial({
+          userAccount: bookUser,
+          config: configPda,
+          positionBook: book,
+          collateralAccount: bookCollateral,
+        })
+        .remainingAccounts([{ pubkey: marketPda, isSigner: false, isWritable: false }, ...collateralMints])
+        .preInstructions([computeLimit])
+        .rpc({ commitment: "confirmed" });
+
+      console.log(
+        `    positions=${count} position accounts=${await unitsConsumed(accountsSignature)} CU, ` +
+          `position book=${await unitsConsumed(bookSignature)} CU`
+      );
+    });
+  });
+});